@@ -0,0 +1,66 @@
+use crate::modpack::ModpackMeta;
+use clap_complete::Shell;
+
+/// Name of the hidden subcommand completion scripts call to get values that depend on the current pack
+pub const COMPLETE_COMMAND: &str = "__complete";
+
+/// Values that can be completed from the pack in the current directory
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum DynamicValues {
+    /// Names of the mods in the pack
+    ModNames,
+}
+
+/// Get the values to complete with, one per line. Nothing is returned if there is no pack here
+pub fn dynamic_values(values: DynamicValues) -> Vec<String> {
+    let Ok(modpack_meta) = ModpackMeta::load_from_current_directory() else {
+        return vec![];
+    };
+    match values {
+        DynamicValues::ModNames => modpack_meta.mods.keys().cloned().collect(),
+    }
+}
+
+/// Subcommands whose name argument is a mod already in the pack
+const MOD_NAME_COMMANDS: [&str; 3] = ["remove", "forbid", "open"];
+
+/// Shell code to append to the static completions, so that mod names are completed from the current pack.
+/// Only bash, zsh and fish are supported
+pub fn dynamic_completions(shell: Shell, bin_name: &str) -> Option<String> {
+    let commands = MOD_NAME_COMMANDS.join(" ");
+    let mod_names = format!("{bin_name} {COMPLETE_COMMAND} mod-names 2>/dev/null");
+    let function = format!("_{bin_name}_with_mod_names");
+    match shell {
+        Shell::Bash => Some(format!(
+            r#"
+{function}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if [[ ${{COMP_CWORD}} -ge 2 && " {commands} " == *" ${{COMP_WORDS[1]}} "* && "${{cur}}" != -* ]]; then
+        COMPREPLY=( $(compgen -W "$({mod_names})" -- "${{cur}}") )
+        return 0
+    fi
+    _{bin_name} "$@"
+}}
+complete -F {function} -o bashdefault -o default {bin_name}
+"#
+        )),
+        Shell::Zsh => Some(format!(
+            r#"
+{function}() {{
+    if (( CURRENT >= 3 )) && [[ " {commands} " == *" ${{words[2]}} "* && ${{words[CURRENT]}} != -* ]]; then
+        local -a mod_names
+        mod_names=(${{(f)"$({mod_names})"}})
+        compadd -a mod_names
+        return
+    fi
+    _{bin_name} "$@"
+}}
+compdef {function} {bin_name}
+"#
+        )),
+        Shell::Fish => Some(format!(
+            "\ncomplete -c {bin_name} -n \"__fish_seen_subcommand_from {commands}\" -f -a \"({mod_names})\"\n"
+        )),
+        _ => None,
+    }
+}
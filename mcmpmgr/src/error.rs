@@ -0,0 +1,51 @@
+use crate::{mod_meta::ModProvider, providers::ProviderErrorKind};
+
+/// Failures of the core library that callers may want to handle differently to other errors
+///
+/// Library functions still return `anyhow::Result`, with these somewhere in the error chain.
+/// Use [`McmpmgrError::find`] to get at them
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum McmpmgrError {
+    /// None of a mod's providers have a version of it matching its constraints
+    #[error("Failed to pin mod '{name}' (providers={providers:#?}) with constraint {version} and all its deps")]
+    ModNotFound {
+        name: String,
+        version: String,
+        providers: Option<Vec<ModProvider>>,
+    },
+    /// The version of a mod that would be pinned doesn't support the minecraft version the modpack requires
+    #[error("{name}@{version} does not support minecraft version {required} (supports {supported}), which the modpack requires since strict_mc_version is set")]
+    VersionConflict {
+        name: String,
+        version: String,
+        required: String,
+        supported: String,
+    },
+    /// A downloaded file doesn't match the hash it was pinned with
+    #[error("{algorithm} hash mismatch\nExpected:\n{expected}\nGot:\n{actual}")]
+    HashMismatch {
+        algorithm: String,
+        expected: String,
+        actual: String,
+    },
+    /// A provider failed for a reason other than not having the mod, so resolution stopped rather than trying the next one
+    #[error("Failed to resolve {name}@{version} with provider {provider} ({kind} error): {message}. {hint}")]
+    ProviderUnavailable {
+        name: String,
+        version: String,
+        provider: ModProvider,
+        kind: ProviderErrorKind,
+        message: String,
+        hint: &'static str,
+    },
+    /// A modpack directory or its manifest can't be used
+    #[error("{0}")]
+    InvalidPack(String),
+}
+
+impl McmpmgrError {
+    /// Find the mcmpmgr error that caused an error, if there is one
+    pub fn find(error: &anyhow::Error) -> Option<&Self> {
+        error.chain().find_map(|cause| cause.downcast_ref::<Self>())
+    }
+}
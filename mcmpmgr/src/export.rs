@@ -0,0 +1,616 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    modpack::{ModLoader, ModpackMeta, MODPACK_FILENAME},
+    profiles::DEFAULT_MODS_DIR_NAME,
+    providers::{all_hashes, loader::Loader, DownloadSide, FileSource},
+    resolver::{DependencyVersions, DownloadCache, PinnedPackMeta, MODPACK_LOCK_FILENAME},
+};
+
+/// Directory within a bundle that the pinned mod files are stored in
+const BUNDLE_MODS_DIR_NAME: &str = ".bundled-mods";
+
+/// Name of the index listing the pack's mods in an mrpack
+const MRPACK_INDEX_FILENAME: &str = "modrinth.index.json";
+
+/// Formats a modpack can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A zip containing the server side mods and files, along with start scripts
+    ServerPack,
+    /// A tar.zst containing the pack, its lock and every pinned mod file, to install from without internet access
+    Bundle,
+    /// A Modrinth modpack (.mrpack), which launchers such as Prism and the Modrinth app can import
+    Mrpack,
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::ServerPack => write!(f, "server-pack"),
+            ExportFormat::Bundle => write!(f, "bundle"),
+            ExportFormat::Mrpack => write!(f, "mrpack"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "server-pack" => Ok(Self::ServerPack),
+            "bundle" => Ok(Self::Bundle),
+            "mrpack" => Ok(Self::Mrpack),
+            _ => anyhow::bail!("Invalid export format: {}", s),
+        }
+    }
+}
+
+/// Export the modpack in `pack_dir` to a runnable server pack zip at `output`
+///
+/// If `include_installer` is set, the modloader's server installer is downloaded into the pack too
+pub async fn export_server_pack(
+    pack_dir: &Path,
+    output: &Path,
+    include_installer: bool,
+) -> Result<()> {
+    let modpack_meta = ModpackMeta::load_from_directory(pack_dir)?;
+    let mut pack_lock =
+        PinnedPackMeta::load_from_directory(pack_dir, DependencyVersions::Latest).await?;
+    if modpack_meta.prefix_load_order {
+        pack_lock.prefix_load_order()?;
+    }
+    let staging_dir = tempfile::tempdir()?;
+    let mods_dir = staging_dir.path().join(DEFAULT_MODS_DIR_NAME);
+    std::fs::create_dir_all(&mods_dir)?;
+
+    pack_lock
+        .download_mods(&mods_dir, DownloadSide::Server, false)
+        .await?;
+    modpack_meta.install_files(
+        pack_dir,
+        staging_dir.path(),
+        DownloadSide::Server,
+        false,
+        &Default::default(),
+        false,
+    )?;
+
+    let server_jar = if include_installer {
+        let loader_version = pack_loader_version(&modpack_meta, &pack_lock).await?;
+        let installer = Loader::new()
+            .download_server_installer(
+                &modpack_meta.modloader,
+                &modpack_meta.mc_version,
+                &loader_version,
+            )
+            .await?;
+        std::fs::write(
+            staging_dir.path().join(&installer.filename),
+            &installer.contents,
+        )?;
+        Some(installer.filename)
+    } else {
+        None
+    };
+
+    let (start_sh, start_bat) = start_scripts(&modpack_meta.modloader, server_jar.as_deref());
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(std::fs::File::create(output)?);
+    zip.start_file("start.sh", file_options.unix_permissions(0o755))?;
+    zip.write_all(start_sh.as_bytes())?;
+    zip.start_file("start.bat", file_options)?;
+    zip.write_all(start_bat.as_bytes())?;
+    add_dir_to_zip(&mut zip, staging_dir.path(), &PathBuf::new(), file_options)?;
+    zip.finish()?;
+
+    println!("Exported server pack to {}", output.display());
+    Ok(())
+}
+
+/// Export the modpack in `pack_dir` to an offline bundle at `output`
+///
+/// The bundle holds the pack metadata, lock and files, along with every pinned mod file for both sides,
+/// so that it can be installed from without any network access
+pub async fn export_bundle(pack_dir: &Path, output: &Path) -> Result<()> {
+    let modpack_meta = ModpackMeta::load_from_directory(pack_dir)?;
+    let pack_lock =
+        PinnedPackMeta::load_from_directory(pack_dir, DependencyVersions::Latest).await?;
+    let staging_dir = tempfile::tempdir()?;
+    pack_lock
+        .download_mods(&staging_dir.path().to_path_buf(), DownloadSide::Both, true)
+        .await?;
+
+    let mut bundle = tar::Builder::new(zstd::Encoder::new(std::fs::File::create(output)?, 0)?);
+    bundle.append_path_with_name(pack_dir.join(MODPACK_FILENAME), MODPACK_FILENAME)?;
+    // Writing the lock directly makes sure it exists, even if it was only just resolved
+    let lock_contents = toml::to_string(&pack_lock)?;
+    let mut lock_header = tar::Header::new_gnu();
+    lock_header.set_size(lock_contents.len() as u64);
+    lock_header.set_mode(0o644);
+    lock_header.set_cksum();
+    bundle.append_data(
+        &mut lock_header,
+        MODPACK_LOCK_FILENAME,
+        lock_contents.as_bytes(),
+    )?;
+    for rel_path in modpack_meta.files.iter().flat_map(|files| files.keys()) {
+        let source_path = pack_dir.join(rel_path);
+        if source_path.is_dir() {
+            bundle.append_dir_all(rel_path, &source_path)?;
+        } else {
+            bundle.append_path_with_name(&source_path, rel_path)?;
+        }
+    }
+    bundle.append_dir_all(BUNDLE_MODS_DIR_NAME, staging_dir.path())?;
+    bundle.into_inner()?.finish()?;
+
+    println!("Exported offline bundle to {}", output.display());
+    Ok(())
+}
+
+/// The `modrinth.index.json` of an mrpack
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MrpackIndex {
+    format_version: u32,
+    game: &'static str,
+    version_id: String,
+    name: String,
+    files: Vec<MrpackFile>,
+    /// Map of `minecraft` or a modloader -> the version the pack needs
+    dependencies: BTreeMap<&'static str, String>,
+}
+
+/// A mod file in an mrpack, which launchers download when the pack is imported
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MrpackFile {
+    path: String,
+    hashes: BTreeMap<String, String>,
+    env: MrpackEnv,
+    downloads: Vec<String>,
+    file_size: usize,
+}
+
+/// Whether a file in an mrpack is `required`, `optional` or `unsupported` on each side
+#[derive(Debug, Clone, Serialize)]
+struct MrpackEnv {
+    client: String,
+    server: String,
+}
+
+/// Export the modpack in `pack_dir` to a Modrinth modpack at `output`
+///
+/// Mods are listed in the index to be downloaded from their pinned urls, keeping whether they are
+/// required, optional or unsupported on each side. The pack's files are added as overrides, with
+/// client or server only files going in `client-overrides` or `server-overrides`
+pub async fn export_mrpack(pack_dir: &Path, output: &Path, version_id: &str) -> Result<()> {
+    let modpack_meta = ModpackMeta::load_from_directory(pack_dir)?;
+    let mut pack_lock =
+        PinnedPackMeta::load_from_directory(pack_dir, DependencyVersions::Latest).await?;
+    if modpack_meta.prefix_load_order {
+        pack_lock.prefix_load_order()?;
+    }
+    let mods_dir = tempfile::tempdir()?;
+    // The mods are only downloaded to get their sizes and any hashes missing from the lock
+    pack_lock
+        .download_mods(&mods_dir.path().to_path_buf(), DownloadSide::Both, true)
+        .await?;
+    let loader_version = pack_loader_version(&modpack_meta, &pack_lock).await?;
+    let index = mrpack_index(
+        &modpack_meta,
+        &pack_lock,
+        mods_dir.path(),
+        version_id,
+        &loader_version,
+    )?;
+
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(std::fs::File::create(output)?);
+    zip.start_file(MRPACK_INDEX_FILENAME, file_options)?;
+    zip.write_all(serde_json::to_string_pretty(&index)?.as_bytes())?;
+    for (side, overrides_name) in [
+        (DownloadSide::Both, "overrides"),
+        (DownloadSide::Client, "client-overrides"),
+        (DownloadSide::Server, "server-overrides"),
+    ] {
+        let overrides_dir = tempfile::tempdir()?;
+        files_only_for_side(&modpack_meta, side).install_files(
+            pack_dir,
+            overrides_dir.path(),
+            DownloadSide::Both,
+            false,
+            &Default::default(),
+            false,
+        )?;
+        add_dir_to_zip(
+            &mut zip,
+            overrides_dir.path(),
+            Path::new(overrides_name),
+            file_options,
+        )?;
+    }
+    zip.finish()?;
+
+    println!("Exported mrpack to {}", output.display());
+    Ok(())
+}
+
+/// A copy of the pack with only the files that are applied on exactly `side`
+fn files_only_for_side(modpack_meta: &ModpackMeta, side: DownloadSide) -> ModpackMeta {
+    let mut modpack_meta = modpack_meta.clone();
+    if let Some(files) = &mut modpack_meta.files {
+        for file_targets in files.values_mut() {
+            file_targets.0.retain(|file_meta| file_meta.side == side);
+        }
+        files.retain(|_, file_targets| !file_targets.0.is_empty());
+    }
+    modpack_meta
+}
+
+/// Build the mrpack index for a pack whose mod files have been downloaded to `mods_dir`
+fn mrpack_index(
+    modpack_meta: &ModpackMeta,
+    pack_lock: &PinnedPackMeta,
+    mods_dir: &Path,
+    version_id: &str,
+    loader_version: &str,
+) -> Result<MrpackIndex> {
+    let mut files = vec![];
+    for pinned_mod in pack_lock.get_pinned_mods().values() {
+        let env = MrpackEnv {
+            client: pinned_mod.client_side.to_string().to_ascii_lowercase(),
+            server: pinned_mod.server_side.to_string().to_ascii_lowercase(),
+        };
+        for filesource in pinned_mod.source.iter() {
+            let FileSource::Download { url, filename, .. } = filesource else {
+                continue;
+            };
+            let contents = std::fs::read(mods_dir.join(filename))?;
+            // Launchers verify mrpack files with their sha1 and sha512 hashes
+            let hashes = all_hashes(&contents)
+                .into_iter()
+                .filter(|(algorithm, _)| algorithm == "sha1" || algorithm == "sha512")
+                .collect();
+            files.push(MrpackFile {
+                path: format!("{DEFAULT_MODS_DIR_NAME}/{filename}"),
+                hashes,
+                env: env.clone(),
+                downloads: vec![url.clone()],
+                file_size: contents.len(),
+            });
+        }
+    }
+    let loader_dependency = match modpack_meta.modloader {
+        ModLoader::Forge => "forge",
+        ModLoader::Fabric => "fabric-loader",
+    };
+    Ok(MrpackIndex {
+        format_version: 1,
+        game: "minecraft",
+        version_id: version_id.into(),
+        name: modpack_meta.pack_name.clone(),
+        files,
+        dependencies: BTreeMap::from([
+            ("minecraft", modpack_meta.mc_version.clone()),
+            (loader_dependency, loader_version.into()),
+        ]),
+    })
+}
+
+/// Get the modloader version pinned in the lock, or the latest one if the lock doesn't pin it
+async fn pack_loader_version(
+    modpack_meta: &ModpackMeta,
+    pack_lock: &PinnedPackMeta,
+) -> Result<String> {
+    Ok(match pack_lock.loader_version() {
+        Some(loader_version) => loader_version.to_string(),
+        None => {
+            Loader::new()
+                .get_latest_version(&modpack_meta.modloader, &modpack_meta.mc_version)
+                .await?
+        }
+    })
+}
+
+/// Extract an offline bundle into `pack_dir`, which can then be used like any other pack directory
+///
+/// The bundled mod files are added to `download_cache`, so that installing them doesn't download anything
+pub fn extract_bundle(
+    bundle: &Path,
+    pack_dir: &Path,
+    download_cache: &DownloadCache,
+) -> Result<()> {
+    println!("Extracting offline bundle {}", bundle.display());
+    tar::Archive::new(zstd::Decoder::new(std::fs::File::open(bundle)?)?).unpack(pack_dir)?;
+    let pack_lock_contents = std::fs::read_to_string(pack_dir.join(MODPACK_LOCK_FILENAME))?;
+    let pack_lock: PinnedPackMeta = toml::from_str(&pack_lock_contents)?;
+    pack_lock.cache_mod_files(&pack_dir.join(BUNDLE_MODS_DIR_NAME), download_cache);
+    Ok(())
+}
+
+/// Generate the unix and windows start scripts for a server pack
+fn start_scripts(modloader: &ModLoader, server_jar: Option<&str>) -> (String, String) {
+    let run_command = match (modloader, server_jar) {
+        (ModLoader::Fabric, Some(jar)) => format!("java -Xmx4G -jar {jar} nogui"),
+        // The forge installer needs to be run once to set up the server
+        (ModLoader::Forge, Some(jar)) => {
+            let start_sh = format!(
+                "#!/usr/bin/env sh\nif [ ! -f run.sh ]; then\n    java -jar {jar} --installServer\nfi\nsh ./run.sh nogui \"$@\"\n"
+            );
+            let start_bat = format!(
+                "@echo off\r\nif not exist run.bat (\r\n    java -jar {jar} --installServer\r\n)\r\ncall run.bat nogui %*\r\n"
+            );
+            return (start_sh, start_bat);
+        }
+        (_, None) => "java -Xmx4G -jar server.jar nogui".into(),
+    };
+    (
+        format!("#!/usr/bin/env sh\n{run_command} \"$@\"\n"),
+        format!("@echo off\r\n{run_command} %*\r\n"),
+    )
+}
+
+/// Recursively add the contents of `dir` to the zip under `zip_path`
+fn add_dir_to_zip(
+    zip: &mut ZipWriter<std::fs::File>,
+    dir: &Path,
+    zip_path: &Path,
+    file_options: SimpleFileOptions,
+) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let entry_zip_path = zip_path.join(entry.file_name());
+        // Zip paths always use forward slashes
+        let entry_zip_name = entry_zip_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if entry.file_type()?.is_dir() {
+            zip.add_directory(&entry_zip_name, file_options)?;
+            add_dir_to_zip(zip, &entry.path(), &entry_zip_path, file_options)?;
+        } else {
+            zip.start_file(&entry_zip_name, file_options)?;
+            zip.write_all(&std::fs::read(entry.path())?)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_export_format_from_str() {
+    assert_eq!(
+        "server-pack".parse::<ExportFormat>().unwrap(),
+        ExportFormat::ServerPack
+    );
+    assert_eq!(
+        "bundle".parse::<ExportFormat>().unwrap(),
+        ExportFormat::Bundle
+    );
+    assert_eq!(
+        "mrpack".parse::<ExportFormat>().unwrap(),
+        ExportFormat::Mrpack
+    );
+    assert!("client-pack".parse::<ExportFormat>().is_err());
+    assert_eq!(ExportFormat::ServerPack.to_string(), "server-pack");
+}
+
+#[test]
+fn test_start_scripts() {
+    let (start_sh, start_bat) = start_scripts(&ModLoader::Fabric, Some("fabric-server-launch.jar"));
+    assert!(start_sh.starts_with("#!/usr/bin/env sh\n"));
+    assert!(start_sh.contains("java -Xmx4G -jar fabric-server-launch.jar nogui"));
+    assert!(start_bat.contains("java -Xmx4G -jar fabric-server-launch.jar nogui"));
+
+    let (start_sh, start_bat) = start_scripts(&ModLoader::Forge, Some("forge-installer.jar"));
+    assert!(start_sh.contains("--installServer"));
+    assert!(start_bat.contains("call run.bat"));
+
+    let (start_sh, _) = start_scripts(&ModLoader::Forge, None);
+    assert!(start_sh.contains("server.jar"));
+}
+
+#[tokio::test]
+async fn test_bundle_round_trip() {
+    let pack_dir = tempfile::tempdir().unwrap();
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+    std::fs::create_dir_all(pack_dir.path().join("config")).unwrap();
+    std::fs::write(pack_dir.path().join("config/a.toml"), "a = 1").unwrap();
+    modpack_meta
+        .add_file(
+            &pack_dir.path().join("config"),
+            &crate::file_meta::FileMeta {
+                target_path: "config".into(),
+                side: DownloadSide::Both,
+                apply_policy: crate::file_meta::FileApplyPolicy::Always,
+                sha512: None,
+                manifest: None,
+                templated: false,
+                variants: vec![],
+                priority: 0,
+            },
+            pack_dir.path(),
+        )
+        .unwrap();
+    modpack_meta.save_to_dir(pack_dir.path()).unwrap();
+    PinnedPackMeta::new()
+        .save_to_dir(&pack_dir.path().to_path_buf())
+        .unwrap();
+
+    let bundle_dir = tempfile::tempdir().unwrap();
+    let bundle = bundle_dir.path().join("test.tar.zst");
+    export_bundle(pack_dir.path(), &bundle).await.unwrap();
+
+    let extracted_dir = tempfile::tempdir().unwrap();
+    extract_bundle(&bundle, extracted_dir.path(), &DownloadCache::default()).unwrap();
+    let extracted_meta = ModpackMeta::load_from_directory(extracted_dir.path()).unwrap();
+    assert_eq!(extracted_meta.pack_name, "test");
+    assert_eq!(
+        std::fs::read_to_string(extracted_dir.path().join("config/a.toml")).unwrap(),
+        "a = 1"
+    );
+    assert!(extracted_dir.path().join(MODPACK_LOCK_FILENAME).exists());
+}
+
+#[tokio::test]
+async fn test_export_server_pack() {
+    let pack_dir = tempfile::tempdir().unwrap();
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+    std::fs::create_dir_all(pack_dir.path().join("config")).unwrap();
+    std::fs::write(pack_dir.path().join("config/a.toml"), "a = 1").unwrap();
+    modpack_meta
+        .add_file(
+            &pack_dir.path().join("config"),
+            &crate::file_meta::FileMeta {
+                target_path: "config".into(),
+                side: DownloadSide::Both,
+                apply_policy: crate::file_meta::FileApplyPolicy::Always,
+                sha512: None,
+                manifest: None,
+                templated: false,
+                variants: vec![],
+                priority: 0,
+            },
+            pack_dir.path(),
+        )
+        .unwrap();
+    modpack_meta.save_to_dir(pack_dir.path()).unwrap();
+    PinnedPackMeta::new()
+        .save_to_dir(&pack_dir.path().to_path_buf())
+        .unwrap();
+
+    let output_dir = tempfile::tempdir().unwrap();
+    let output = output_dir.path().join("server.zip");
+    export_server_pack(pack_dir.path(), &output, false)
+        .await
+        .unwrap();
+
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&output).unwrap()).unwrap();
+    let mut names: Vec<&str> = zip.file_names().collect();
+    names.sort();
+    assert_eq!(
+        names,
+        ["config/", "config/a.toml", "mods/", "start.bat", "start.sh"]
+    );
+    let mut config = String::new();
+    std::io::Read::read_to_string(&mut zip.by_name("config/a.toml").unwrap(), &mut config).unwrap();
+    assert_eq!(config, "a = 1");
+}
+
+#[tokio::test]
+async fn test_export_mrpack_overrides_per_side() {
+    let pack_dir = tempfile::tempdir().unwrap();
+    let mut modpack_meta =
+        ModpackMeta::new("test", "1.20.1", ModLoader::Fabric).loader_version("0.16.0");
+    for (filename, side) in [
+        ("both.toml", DownloadSide::Both),
+        ("client.toml", DownloadSide::Client),
+        ("server.toml", DownloadSide::Server),
+    ] {
+        let file_path = pack_dir.path().join(filename);
+        std::fs::write(&file_path, filename).unwrap();
+        modpack_meta
+            .add_file(
+                &file_path,
+                &crate::file_meta::FileMeta {
+                    target_path: format!("config/{filename}"),
+                    side,
+                    apply_policy: crate::file_meta::FileApplyPolicy::Always,
+                    sha512: None,
+                    manifest: None,
+                    templated: false,
+                    variants: vec![],
+                    priority: 0,
+                },
+                pack_dir.path(),
+            )
+            .unwrap();
+    }
+    modpack_meta.save_to_dir(pack_dir.path()).unwrap();
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.pin_loader_version(&modpack_meta).await;
+    pack_lock
+        .save_to_dir(&pack_dir.path().to_path_buf())
+        .unwrap();
+
+    let output_dir = tempfile::tempdir().unwrap();
+    let output = output_dir.path().join("test.mrpack");
+    export_mrpack(pack_dir.path(), &output, "1.0.0")
+        .await
+        .unwrap();
+
+    let zip = zip::ZipArchive::new(std::fs::File::open(&output).unwrap()).unwrap();
+    let mut names: Vec<&str> = zip.file_names().collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "client-overrides/config/",
+            "client-overrides/config/client.toml",
+            MRPACK_INDEX_FILENAME,
+            "overrides/config/",
+            "overrides/config/both.toml",
+            "server-overrides/config/",
+            "server-overrides/config/server.toml",
+        ]
+    );
+}
+
+#[test]
+fn test_mrpack_index() {
+    let pack_lock: PinnedPackMeta = toml::from_str(
+        r#"
+        [mods.sodium]
+        version = "0.5.3"
+        server_side = "Unsupported"
+        client_side = "Optional"
+        source = [{ Download = { url = "https://cdn.modrinth.com/sodium.jar", filename = "sodium.jar", hashes = {} } }]
+
+        [mods.lithium]
+        version = "0.11.2"
+        server_side = "Required"
+        client_side = "Required"
+        source = [{ Download = { url = "https://cdn.modrinth.com/lithium.jar", filename = "lithium.jar", hashes = {} } }]
+        "#,
+    )
+    .unwrap();
+    let mods_dir = tempfile::tempdir().unwrap();
+    std::fs::write(mods_dir.path().join("sodium.jar"), "sodium").unwrap();
+    std::fs::write(mods_dir.path().join("lithium.jar"), "lithium").unwrap();
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+
+    let index = mrpack_index(
+        &modpack_meta,
+        &pack_lock,
+        mods_dir.path(),
+        "1.0.0",
+        "0.16.0",
+    )
+    .unwrap();
+    let index = serde_json::to_value(&index).unwrap();
+    let files = index["files"].as_array().unwrap();
+    let file = |path: &str| files.iter().find(|file| file["path"] == path).unwrap();
+    assert_eq!(file("mods/sodium.jar")["env"]["client"], "optional");
+    assert_eq!(file("mods/sodium.jar")["env"]["server"], "unsupported");
+    assert_eq!(file("mods/lithium.jar")["env"]["client"], "required");
+    assert_eq!(file("mods/sodium.jar")["fileSize"], 6);
+    assert_eq!(
+        file("mods/sodium.jar")["hashes"]["sha1"],
+        crate::providers::hash_hex("sha1", b"sodium").unwrap()
+    );
+    assert!(file("mods/sodium.jar")["hashes"].get("sha256").is_none());
+    assert_eq!(index["dependencies"]["fabric-loader"], "0.16.0");
+    assert_eq!(index["dependencies"]["minecraft"], "1.20.1");
+}
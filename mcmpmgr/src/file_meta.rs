@@ -1,6 +1,7 @@
-use crate::providers::DownloadSide;
+use crate::{providers::DownloadSide, resolver::sha512_hex};
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, path::Path, str::FromStr};
+use std::{collections::BTreeMap, fmt::Display, path::Path, str::FromStr};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash)]
 pub struct FileMeta {
@@ -10,6 +11,170 @@ pub struct FileMeta {
     pub side: DownloadSide,
     /// When to apply the files to the instance
     pub apply_policy: FileApplyPolicy,
+    /// Sha512 hash of the file when it was added to the pack
+    #[serde(default)]
+    pub sha512: Option<String>,
+    /// Map of relative path -> sha512 hash of each file in the folder when it was added to the pack
+    #[serde(default)]
+    pub manifest: Option<BTreeMap<String, String>>,
+    /// Replace `${VAR}` placeholders in the file(s) with the installing profile's variables when applied
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub templated: bool,
+    /// Pack variants the file(s) are only applied for. Applied for every profile if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<String>,
+    /// Files/Folders are applied in order of priority, so higher priority targets overwrite or merge over lower priority ones.
+    /// Targets with the same priority are applied in order of their path in the pack
+    #[serde(default, skip_serializing_if = "is_default_priority")]
+    pub priority: i32,
+}
+
+fn is_default_priority(priority: &i32) -> bool {
+    *priority == 0
+}
+
+/// Every target a file/folder in the pack is applied to
+///
+/// A single target is stored as a table rather than a list, so that packs with one target per file keep their format
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "FileTargetsRepr", into = "FileTargetsRepr")]
+pub struct FileTargets(pub Vec<FileMeta>);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum FileTargetsRepr {
+    Single(FileMeta),
+    Multiple(Vec<FileMeta>),
+}
+
+impl From<FileTargetsRepr> for FileTargets {
+    fn from(value: FileTargetsRepr) -> Self {
+        match value {
+            FileTargetsRepr::Single(file_meta) => Self(vec![file_meta]),
+            FileTargetsRepr::Multiple(file_metas) => Self(file_metas),
+        }
+    }
+}
+
+impl From<FileTargets> for FileTargetsRepr {
+    fn from(value: FileTargets) -> Self {
+        match <[FileMeta; 1]>::try_from(value.0) {
+            Ok([file_meta]) => Self::Single(file_meta),
+            Err(file_metas) => Self::Multiple(file_metas),
+        }
+    }
+}
+
+impl FileTargets {
+    /// Add a target, replacing any existing target with the same target path
+    pub fn add(&mut self, file_meta: FileMeta) {
+        match self
+            .0
+            .iter_mut()
+            .find(|target| target.target_path == file_meta.target_path)
+        {
+            Some(target) => *target = file_meta,
+            None => self.0.push(file_meta),
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, FileMeta> {
+        self.0.iter()
+    }
+}
+
+impl FileMeta {
+    /// Record the hashes of a file or folder in the pack
+    pub fn update_hashes(&mut self, source_path: &Path) -> anyhow::Result<()> {
+        if source_path.is_dir() {
+            self.sha512 = None;
+            self.manifest = Some(hash_folder(source_path)?);
+        } else {
+            self.sha512 = Some(hash_file(source_path)?);
+            self.manifest = None;
+        }
+        Ok(())
+    }
+
+    /// Ensure a file or folder in the pack still matches the hashes recorded when it was added
+    ///
+    /// Files added before hashes were recorded are not verified
+    pub fn verify_hashes(&self, source_path: &Path) -> anyhow::Result<()> {
+        let matches = if let Some(sha512) = &self.sha512 {
+            source_path.is_file() && hash_file(source_path)? == *sha512
+        } else if let Some(manifest) = &self.manifest {
+            source_path.is_dir() && hash_folder(source_path)? == *manifest
+        } else {
+            true
+        };
+        if !matches {
+            anyhow::bail!(
+                "{} does not match the hashes recorded in the modpack. If it was changed intentionally, add it to the pack again with `mcmpmgr file add`",
+                source_path.display()
+            )
+        }
+        Ok(())
+    }
+}
+
+/// Get the sha512 hash of a file
+fn hash_file(path: &Path) -> anyhow::Result<String> {
+    let contents =
+        std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(sha512_hex(&contents))
+}
+
+/// Get the sha512 hashes of every file in a folder, keyed by normalized path relative to the folder
+fn hash_folder(folder: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut manifest = BTreeMap::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(current_folder) = folders.pop() {
+        for entry in std::fs::read_dir(&current_folder)? {
+            let entry_path = entry?.path();
+            if entry_path.is_dir() {
+                folders.push(entry_path);
+            } else {
+                let relative_path = entry_path.strip_prefix(folder)?;
+                manifest.insert(
+                    get_normalized_relative_path(relative_path, folder)?,
+                    hash_file(&entry_path)?,
+                );
+            }
+        }
+    }
+    Ok(manifest)
+}
+
+/// Replace every `${VAR}` placeholder in `contents` with the value of `VAR` from `variables`,
+/// falling back to the `VAR` environment variable
+///
+/// Placeholders without a value are left as they are
+pub fn substitute_variables(contents: &str, variables: &BTreeMap<String, String>) -> String {
+    let mut substituted = String::with_capacity(contents.len());
+    let mut rest = contents;
+    while let Some(start) = rest.find("${") {
+        substituted.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let Some(end) = placeholder.find('}') else {
+            rest = placeholder;
+            break;
+        };
+        let name = &placeholder[2..end];
+        match variables
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+        {
+            Some(value) => substituted.push_str(&value),
+            None => {
+                eprintln!("Warning: No value for template variable '{name}'. Leaving it as is");
+                substituted.push_str(&placeholder[..=end]);
+            }
+        }
+        rest = &placeholder[end + 1..];
+    }
+    substituted.push_str(rest);
+    substituted
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
@@ -103,3 +268,55 @@ pub fn get_normalized_relative_path(
 
     Ok(normalized_path)
 }
+
+#[test]
+fn test_verify_hashes() {
+    let pack_dir = tempfile::tempdir().unwrap();
+    let config_dir = pack_dir.path().join("config");
+    std::fs::create_dir_all(config_dir.join("nested")).unwrap();
+    std::fs::write(config_dir.join("a.toml"), "a = 1").unwrap();
+    std::fs::write(config_dir.join("nested/b.json"), "{}").unwrap();
+
+    let mut file_meta = FileMeta {
+        target_path: "./config/a.toml".into(),
+        side: DownloadSide::Both,
+        apply_policy: FileApplyPolicy::Always,
+        sha512: None,
+        manifest: None,
+        templated: false,
+        variants: vec![],
+        priority: 0,
+    };
+    // Files without recorded hashes are never rejected
+    assert!(file_meta.verify_hashes(&config_dir.join("a.toml")).is_ok());
+
+    file_meta.update_hashes(&config_dir.join("a.toml")).unwrap();
+    assert!(file_meta.verify_hashes(&config_dir.join("a.toml")).is_ok());
+    std::fs::write(config_dir.join("a.toml"), "a = 2").unwrap();
+    assert!(file_meta.verify_hashes(&config_dir.join("a.toml")).is_err());
+
+    file_meta.update_hashes(&config_dir).unwrap();
+    assert_eq!(file_meta.manifest.as_ref().unwrap().len(), 2);
+    assert!(file_meta.verify_hashes(&config_dir).is_ok());
+    std::fs::write(config_dir.join("nested/c.json"), "{}").unwrap();
+    assert!(file_meta.verify_hashes(&config_dir).is_err());
+}
+
+#[test]
+fn test_substitute_variables() {
+    let variables = BTreeMap::from([
+        ("SERVER_NAME".to_string(), "My Server".to_string()),
+        ("PORT".to_string(), "25565".to_string()),
+    ]);
+    assert_eq!(
+        substitute_variables("motd=${SERVER_NAME}\nport=${PORT}\n", &variables),
+        "motd=My Server\nport=25565\n"
+    );
+    assert_eq!(
+        substitute_variables(
+            "${MCMPMGR_TEST_UNSET_VARIABLE} and ${unterminated",
+            &variables
+        ),
+        "${MCMPMGR_TEST_UNSET_VARIABLE} and ${unterminated"
+    );
+}
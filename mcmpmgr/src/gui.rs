@@ -0,0 +1,967 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::mod_meta::{ModMeta, ModProvider};
+use crate::modpack::ModpackMeta;
+use crate::profiles::{self, InstallCache, InstallPreview, Profile};
+use crate::providers::modrinth::{Modrinth, ModrinthSearchResult};
+use crate::providers::DownloadSide;
+use crate::resolver::{DependencyVersions, DownloadProgress, PinnedPackMeta};
+use iced::futures::SinkExt;
+use iced::widget::{
+    button, checkbox, column, container, pick_list, progress_bar, row, scrollable, text,
+    text_input, Column,
+};
+use iced::{executor, Application, Command};
+use iced::{Alignment, Element, Length, Settings, Theme};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Launch the modpack manager GUI
+pub fn run() -> iced::Result {
+    ManagerGUI::run(Settings {
+        window: iced::window::Settings {
+            size: iced::Size {
+                width: 800.0,
+                height: 300.0,
+            },
+            min_size: Some(iced::Size {
+                width: 800.0,
+                height: 300.0,
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+}
+
+#[derive(Default)]
+struct ManagerGUI {
+    theme: Theme,
+    userdata: profiles::Data,
+    userdata_load_error: Option<String>,
+    current_view: ManagerView,
+    profile_edit_settings: ProfileSettings,
+    profile_save_error: Option<String>,
+    current_install_status: ProfileInstallStatus,
+    mod_browser: ModBrowserState,
+    /// Incremented on each pack source edit so that only the latest edit gets validated
+    pack_source_edit_count: usize,
+    settings_save_error: Option<String>,
+}
+
+/// The theme to use when no theme has been saved in the user data
+const DEFAULT_THEME: Theme = Theme::GruvboxDark;
+
+/// How long to wait after the pack source stops changing before validating it
+const PACK_SOURCE_VALIDATION_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+/// The current application view
+enum ManagerView {
+    ProfileSelect,
+    ProfileView { profile: String },
+    AddProfile,
+    EditProfile { profile: String },
+    ModBrowser { profile: String },
+    Settings,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Search and selection state of the mod browser
+struct ModBrowserState {
+    query: String,
+    results: Vec<ModrinthSearchResult>,
+    selected_mod: Option<String>,
+    version: String,
+    side: Option<DownloadSide>,
+    status: Option<String>,
+}
+
+const MOD_SIDES: [DownloadSide; 3] = [
+    DownloadSide::Both,
+    DownloadSide::Client,
+    DownloadSide::Server,
+];
+
+#[derive(Debug, Clone)]
+/// The current application view
+struct ProfileSettings {
+    name: String,
+    instance_dir: Option<PathBuf>,
+    pack_source: String,
+    side: DownloadSide,
+    include_optional: bool,
+    symlink_files: bool,
+    prune_empty_dirs: bool,
+    mods_dir_name: String,
+    // Settings that can't be edited in the GUI yet, kept so that saving doesn't lose them
+    groups: Vec<String>,
+    mod_side_overrides: BTreeMap<String, DownloadSide>,
+    variables: BTreeMap<String, String>,
+}
+
+impl Default for ProfileSettings {
+    fn default() -> Self {
+        Self {
+            name: Default::default(),
+            instance_dir: Default::default(),
+            pack_source: Default::default(),
+            side: DownloadSide::Client,
+            include_optional: false,
+            symlink_files: false,
+            prune_empty_dirs: false,
+            mods_dir_name: Default::default(),
+            groups: Default::default(),
+            mod_side_overrides: Default::default(),
+            variables: Default::default(),
+        }
+    }
+}
+
+impl TryFrom<ProfileSettings> for profiles::Profile {
+    type Error = String;
+    fn try_from(value: ProfileSettings) -> Result<Self, Self::Error> {
+        let instance_dir = value
+            .instance_dir
+            .ok_or(format!("An instance directory is required"))?;
+        let mods_dir_name = match value.mods_dir_name.trim() {
+            "" => None,
+            mods_dir_name => Some(mods_dir_name.to_string()),
+        };
+        if !instance_dir
+            .join(
+                mods_dir_name
+                    .as_deref()
+                    .unwrap_or(profiles::DEFAULT_MODS_DIR_NAME),
+            )
+            .exists()
+        {
+            return Err(format!("Instance folder {} does not seem to contain a mods directory. Are you sure this is a valid instance directory?", instance_dir.display()));
+        }
+        let pack_source = value.pack_source;
+        let mut profile = profiles::Profile::new(
+            &instance_dir,
+            profiles::PackSource::from_str(&pack_source)?,
+            value.side,
+        )
+        .map_err(|e| e.to_string())?;
+        profile.include_optional = value.include_optional;
+        profile.symlink_files = value.symlink_files;
+        profile.prune_empty_dirs = value.prune_empty_dirs;
+        profile.mods_dir_name = mods_dir_name;
+        profile.groups = value.groups;
+        profile.mod_side_overrides = value.mod_side_overrides;
+        profile.variables = value.variables;
+        Ok(profile)
+    }
+}
+
+impl Default for ManagerView {
+    fn default() -> Self {
+        Self::ProfileSelect
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    SwitchView(ManagerView),
+    BrowseInstanceDir,
+    EditProfileName(String),
+    EditPackSource(String),
+    EditModsDirName(String),
+    ValidatePackSource(usize),
+    PackSourceValidated(usize, Result<(), String>),
+    ToggleIncludeOptional(bool),
+    ToggleSymlinkFiles(bool),
+    TogglePruneEmptyDirs(bool),
+    SaveProfile,
+    DeleteProfile(String),
+    PreviewInstall(String),
+    InstallPreviewed(Result<InstallPreview, String>),
+    CancelInstall,
+    InstallProfile(String),
+    InstallProgress(DownloadProgress),
+    ProfileInstalled(ProfileInstallStatus),
+    EditModSearch(String),
+    SearchMods,
+    ModsFound(Result<Vec<ModrinthSearchResult>, String>),
+    SelectMod(String),
+    EditModVersion(String),
+    SelectModSide(DownloadSide),
+    AddMod,
+    ModAdded(Result<String, String>),
+    SelectTheme(Theme),
+}
+
+#[derive(Debug, Clone)]
+enum ProfileInstallStatus {
+    NotStarted,
+    /// Working out what the install would change
+    Previewing,
+    /// Waiting for the user to confirm the previewed changes
+    AwaitingConfirmation(InstallPreview),
+    /// Installing, with the latest mod download progress if any mods have started downloading
+    Installing(Option<DownloadProgress>),
+    Success,
+    Error(String),
+}
+
+impl Default for ProfileInstallStatus {
+    fn default() -> Self {
+        Self::NotStarted
+    }
+}
+
+impl Application for ManagerGUI {
+    type Message = Message;
+    type Executor = executor::Default;
+    type Theme = Theme;
+    type Flags = ();
+
+    fn new(_flags: Self::Flags) -> (Self, Command<Self::Message>) {
+        let mut gui = ManagerGUI {
+            theme: DEFAULT_THEME,
+            ..Default::default()
+        };
+        let loaded_userdata = profiles::Data::load();
+
+        match loaded_userdata {
+            Ok(userdata) => {
+                if let Some(theme_name) = userdata.get_gui_theme() {
+                    gui.theme = Theme::ALL
+                        .iter()
+                        .find(|theme| theme.to_string() == theme_name)
+                        .cloned()
+                        .unwrap_or(DEFAULT_THEME);
+                }
+                gui.userdata = userdata;
+            }
+            Err(err) => {
+                gui.userdata_load_error = Some(err.to_string());
+            }
+        };
+
+        (gui, Command::none())
+    }
+
+    fn title(&self) -> String {
+        format!("Minecraft Modpack Manager v{VERSION}")
+    }
+
+    fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::SwitchView(view) => {
+                self.current_install_status = ProfileInstallStatus::NotStarted;
+                match &view {
+                    ManagerView::Settings => {
+                        self.settings_save_error = None;
+                    }
+                    ManagerView::ModBrowser { .. } => {
+                        self.mod_browser = ModBrowserState::default();
+                    }
+                    ManagerView::AddProfile => {
+                        self.profile_save_error = None;
+                        self.profile_edit_settings = ProfileSettings::default();
+                    }
+                    ManagerView::ProfileSelect => {
+                        let loaded_userdata = profiles::Data::load();
+
+                        match loaded_userdata {
+                            Ok(userdata) => {
+                                self.userdata = userdata;
+                                self.userdata_load_error = None;
+                            }
+                            Err(err) => {
+                                self.userdata_load_error = Some(err.to_string());
+                            }
+                        };
+                    }
+                    ManagerView::EditProfile { profile } => {
+                        let loaded_profile = self.userdata.get_profile(profile);
+                        self.profile_edit_settings.name = profile.trim().into();
+                        if let Some(loaded_profile) = loaded_profile {
+                            self.profile_edit_settings.name = profile.into();
+                            self.profile_edit_settings.instance_dir =
+                                Some(loaded_profile.instance_folder.clone());
+                            self.profile_edit_settings.pack_source =
+                                loaded_profile.pack_source.to_string();
+                            self.profile_edit_settings.side = loaded_profile.side;
+                            self.profile_edit_settings.include_optional =
+                                loaded_profile.include_optional;
+                            self.profile_edit_settings.symlink_files = loaded_profile.symlink_files;
+                            self.profile_edit_settings.prune_empty_dirs =
+                                loaded_profile.prune_empty_dirs;
+                            self.profile_edit_settings.mods_dir_name =
+                                loaded_profile.mods_dir_name.clone().unwrap_or_default();
+                            self.profile_edit_settings.groups = loaded_profile.groups.clone();
+                            self.profile_edit_settings.mod_side_overrides =
+                                loaded_profile.mod_side_overrides.clone();
+                            self.profile_edit_settings.variables = loaded_profile.variables.clone();
+                        } else {
+                            eprintln!("Failed to load existing profile data for {profile}");
+                        }
+                    }
+                    _ => {}
+                };
+                self.current_view = view;
+                Command::none()
+            }
+            Message::BrowseInstanceDir => {
+                self.profile_edit_settings.instance_dir = rfd::FileDialog::new()
+                    .set_title("Select your instance folder")
+                    .pick_folder();
+                Command::none()
+            }
+            Message::EditProfileName(name) => {
+                self.profile_edit_settings.name = name;
+                Command::none()
+            }
+            Message::EditPackSource(pack_source) => {
+                self.profile_edit_settings.pack_source = pack_source;
+                self.pack_source_edit_count += 1;
+                let edit_count = self.pack_source_edit_count;
+                Command::perform(
+                    tokio::time::sleep(PACK_SOURCE_VALIDATION_DELAY),
+                    move |_| Message::ValidatePackSource(edit_count),
+                )
+            }
+            Message::ValidatePackSource(edit_count) => {
+                if edit_count != self.pack_source_edit_count {
+                    // The pack source has been edited again since
+                    return Command::none();
+                }
+                match profiles::PackSource::from_str(&self.profile_edit_settings.pack_source) {
+                    Ok(pack_source) => {
+                        self.profile_save_error = None;
+                        Command::perform(
+                            async move {
+                                tokio::task::spawn_blocking(move || pack_source.check_available())
+                                    .await
+                                    .map_err(|err| err.to_string())?
+                                    .map_err(|err| err.to_string())
+                            },
+                            move |result| Message::PackSourceValidated(edit_count, result),
+                        )
+                    }
+                    Err(err) => {
+                        self.profile_save_error = Some(format!("Invalid modpack source: {err}"));
+                        Command::none()
+                    }
+                }
+            }
+            Message::PackSourceValidated(edit_count, result) => {
+                if edit_count == self.pack_source_edit_count {
+                    self.profile_save_error = result
+                        .err()
+                        .map(|err| format!("Modpack source is unavailable: {err}"));
+                }
+                Command::none()
+            }
+            Message::ToggleIncludeOptional(include_optional) => {
+                self.profile_edit_settings.include_optional = include_optional;
+                Command::none()
+            }
+            Message::EditModsDirName(mods_dir_name) => {
+                self.profile_edit_settings.mods_dir_name = mods_dir_name;
+                Command::none()
+            }
+            Message::ToggleSymlinkFiles(symlink_files) => {
+                self.profile_edit_settings.symlink_files = symlink_files;
+                Command::none()
+            }
+            Message::TogglePruneEmptyDirs(prune_empty_dirs) => {
+                self.profile_edit_settings.prune_empty_dirs = prune_empty_dirs;
+                Command::none()
+            }
+            Message::SaveProfile => {
+                let profile: Result<profiles::Profile, String> =
+                    profiles::Profile::try_from(self.profile_edit_settings.clone());
+
+                if let Ok(profile) = profile {
+                    if self.profile_edit_settings.name.trim().len() == 0 {
+                        self.profile_save_error =
+                            format!("Invalid profile name {}", self.profile_edit_settings.name)
+                                .into();
+                    } else {
+                        let profile_name = self.profile_edit_settings.name.trim().to_string();
+                        let save_result = profiles::Data::update(|userdata| {
+                            userdata.add_profile(&profile_name, profile);
+                            Ok(())
+                        });
+                        match save_result {
+                            Ok(userdata) => {
+                                self.userdata = userdata;
+                                self.current_view = ManagerView::ProfileView {
+                                    profile: profile_name,
+                                }
+                            }
+                            Err(err) => {
+                                self.profile_save_error =
+                                    format!("Unable to save profile: {err:#?}").into();
+                            }
+                        }
+                    }
+                } else if let Err(err) = profile {
+                    self.profile_save_error = err.into();
+                };
+
+                Command::none()
+            }
+            Message::DeleteProfile(name) => {
+                let save_result = profiles::Data::update(|userdata| {
+                    userdata.remove_profile(&name);
+                    Ok(())
+                });
+                match save_result {
+                    Ok(userdata) => {
+                        self.userdata = userdata;
+                        self.current_view = ManagerView::ProfileSelect;
+                    }
+                    Err(err) => self.profile_save_error = Some(err.to_string()),
+                }
+
+                Command::none()
+            }
+            Message::PreviewInstall(name) => {
+                self.current_install_status = ProfileInstallStatus::Previewing;
+                let profile = self.userdata.get_profile(&name).cloned();
+                Command::perform(
+                    async move {
+                        let Some(profile) = profile else {
+                            return Err(format!("Profile '{name}' doesn't exist"));
+                        };
+                        profile
+                            .preview_install(&InstallCache::default())
+                            .await
+                            .map_err(|err| err.to_string())
+                    },
+                    Message::InstallPreviewed,
+                )
+            }
+            Message::InstallPreviewed(result) => {
+                self.current_install_status = match result {
+                    Ok(preview) => ProfileInstallStatus::AwaitingConfirmation(preview),
+                    Err(err) => ProfileInstallStatus::Error(err),
+                };
+                Command::none()
+            }
+            Message::CancelInstall => {
+                self.current_install_status = ProfileInstallStatus::NotStarted;
+                Command::none()
+            }
+            Message::InstallProfile(name) => {
+                self.current_install_status = ProfileInstallStatus::Installing(None);
+                let profile = self.userdata.get_profile(&name).cloned();
+                iced::command::channel(100, move |mut output| async move {
+                    let status = if let Some(profile) = profile {
+                        let (progress_sender, mut progress_receiver) =
+                            tokio::sync::mpsc::unbounded_channel();
+                        let install = tokio::spawn(async move {
+                            // Only held while installing, so the CLI can change profiles while the GUI is open
+                            let _instance_lock = profiles::Data::lock()?;
+                            profile
+                                .install_with_cache(
+                                    &InstallCache::default(),
+                                    Some(&progress_sender),
+                                )
+                                .await
+                        });
+                        // The progress channel closes once the install finishes
+                        while let Some(progress) = progress_receiver.recv().await {
+                            let _ = output.send(Message::InstallProgress(progress)).await;
+                        }
+                        match install.await {
+                            Ok(Ok(())) => ProfileInstallStatus::Success,
+                            Ok(Err(err)) => ProfileInstallStatus::Error(format!("{}", err)),
+                            Err(err) => ProfileInstallStatus::Error(format!("{}", err)),
+                        }
+                    } else {
+                        ProfileInstallStatus::Error(format!("Profile '{}' doesn't exist", name))
+                    };
+                    let _ = output.send(Message::ProfileInstalled(status)).await;
+                })
+            }
+            Message::InstallProgress(progress) => {
+                if let ProfileInstallStatus::Installing(_) = self.current_install_status {
+                    self.current_install_status = ProfileInstallStatus::Installing(Some(progress));
+                }
+
+                Command::none()
+            }
+            Message::ProfileInstalled(result) => {
+                self.current_install_status = result;
+
+                Command::none()
+            }
+            Message::EditModSearch(query) => {
+                self.mod_browser.query = query;
+                Command::none()
+            }
+            Message::SearchMods => {
+                let pack_dir = match self.get_mod_browser_pack_dir() {
+                    Ok(pack_dir) => pack_dir,
+                    Err(err) => {
+                        self.mod_browser.status = Some(err);
+                        return Command::none();
+                    }
+                };
+                self.mod_browser.status = Some("Searching...".into());
+                let query = self.mod_browser.query.clone();
+                Command::perform(
+                    async move {
+                        let modpack_meta = ModpackMeta::load_from_directory(&pack_dir)?;
+                        Modrinth::new().search(&query, &modpack_meta).await
+                    },
+                    |result| Message::ModsFound(result.map_err(|err| err.to_string())),
+                )
+            }
+            Message::ModsFound(result) => {
+                match result {
+                    Ok(results) => {
+                        self.mod_browser.status = if results.is_empty() {
+                            Some("No mods found".into())
+                        } else {
+                            None
+                        };
+                        self.mod_browser.results = results;
+                    }
+                    Err(err) => {
+                        self.mod_browser.status = Some(format!("Failed to search for mods: {err}"));
+                    }
+                };
+                Command::none()
+            }
+            Message::SelectMod(slug) => {
+                self.mod_browser.selected_mod = Some(slug);
+                Command::none()
+            }
+            Message::EditModVersion(version) => {
+                self.mod_browser.version = version;
+                Command::none()
+            }
+            Message::SelectModSide(side) => {
+                self.mod_browser.side = Some(side);
+                Command::none()
+            }
+            Message::AddMod => {
+                let pack_dir = match self.get_mod_browser_pack_dir() {
+                    Ok(pack_dir) => pack_dir,
+                    Err(err) => {
+                        self.mod_browser.status = Some(err);
+                        return Command::none();
+                    }
+                };
+                let Some(slug) = self.mod_browser.selected_mod.clone() else {
+                    return Command::none();
+                };
+                let version = self.mod_browser.version.trim().to_string();
+                let side = self.mod_browser.side;
+                self.mod_browser.status = Some(format!("Adding {slug}..."));
+                Command::perform(
+                    async move {
+                        let mut mod_meta = ModMeta::new(&slug)?.provider(ModProvider::Modrinth);
+                        if !version.is_empty() {
+                            mod_meta = mod_meta.version(&version);
+                        }
+                        if let Some(side) = side {
+                            mod_meta.client_side = Some(side.contains(DownloadSide::Client));
+                            mod_meta.server_side = Some(side.contains(DownloadSide::Server));
+                        }
+                        add_mod_to_pack(&pack_dir, &mod_meta).await?;
+                        Ok(slug)
+                    },
+                    |result: anyhow::Result<String>| {
+                        Message::ModAdded(result.map_err(|err| err.to_string()))
+                    },
+                )
+            }
+            Message::SelectTheme(theme) => {
+                self.theme = theme.clone();
+                let save_result = profiles::Data::update(|userdata| {
+                    userdata.set_gui_theme(&theme.to_string());
+                    Ok(())
+                });
+                self.settings_save_error = match save_result {
+                    Ok(userdata) => {
+                        self.userdata = userdata;
+                        None
+                    }
+                    Err(err) => Some(format!("Unable to save settings: {err}")),
+                };
+                Command::none()
+            }
+            Message::ModAdded(result) => {
+                self.mod_browser.status = Some(match result {
+                    Ok(slug) => format!("Added {slug} to the modpack"),
+                    Err(err) => format!("Failed to add mod: {err}"),
+                });
+                Command::none()
+            }
+        }
+    }
+
+    fn view(&self) -> Element<Message> {
+        let contents = match &self.current_view {
+            ManagerView::ProfileSelect => self.view_profile_select(),
+            ManagerView::ProfileView { profile } => self.view_profile_view(&profile),
+            ManagerView::AddProfile => self.view_profile_edit(ManagerView::ProfileSelect, true),
+            ManagerView::EditProfile { profile } => self.view_profile_edit(
+                ManagerView::ProfileView {
+                    profile: profile.clone(),
+                },
+                false,
+            ),
+            ManagerView::ModBrowser { profile } => self.view_mod_browser(profile),
+            ManagerView::Settings => self.view_settings(),
+        };
+
+        container(contents)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .into()
+    }
+
+    fn theme(&self) -> Theme {
+        self.theme.clone()
+    }
+}
+
+impl ManagerGUI {
+    fn view_settings(&self) -> Element<'_, Message> {
+        let mut settings = column![
+            text("Settings").horizontal_alignment(iced::alignment::Horizontal::Center),
+            row![
+                "Theme",
+                pick_list(Theme::ALL, Some(&self.theme), Message::SelectTheme),
+            ]
+            .spacing(5),
+            button("Back").on_press(Message::SwitchView(ManagerView::ProfileSelect)),
+        ]
+        .align_items(Alignment::Center)
+        .spacing(10)
+        .padding(20);
+
+        if let Some(err) = &self.settings_save_error {
+            settings = settings.push(text(err));
+        }
+
+        settings.into()
+    }
+
+    /// Get the local modpack directory of the profile being browsed in the mod browser
+    fn get_mod_browser_pack_dir(&self) -> Result<PathBuf, String> {
+        let ManagerView::ModBrowser { profile } = &self.current_view else {
+            return Err("The mod browser is not open".into());
+        };
+        match self.userdata.get_profile(profile) {
+            Some(Profile {
+                pack_source: profiles::PackSource::Local { path },
+                ..
+            }) => Ok(path.clone()),
+            Some(_) => Err(format!(
+                "Mods can only be added to local modpack sources. Profile '{profile}' uses a git modpack source"
+            )),
+            None => Err(format!("Profile '{profile}' doesn't exist")),
+        }
+    }
+
+    fn view_mod_browser(&self, profile_name: &str) -> Element<'_, Message> {
+        let mut search_results: Column<Message> = column!();
+        for result in self.mod_browser.results.iter() {
+            let is_selected = self.mod_browser.selected_mod.as_ref() == Some(&result.slug);
+            search_results = search_results.push(
+                button(text(format!(
+                    "{}{} ({} downloads) - {}",
+                    if is_selected { "> " } else { "" },
+                    result.title,
+                    result.downloads,
+                    result.description
+                )))
+                .on_press(Message::SelectMod(result.slug.clone()))
+                .width(Length::Fill),
+            );
+        }
+
+        let mut mod_browser = column![
+            text(format!("Add mods to profile: {profile_name}"))
+                .horizontal_alignment(iced::alignment::Horizontal::Center),
+            row![
+                text_input("Search Modrinth for mods", &self.mod_browser.query)
+                    .on_input(Message::EditModSearch)
+                    .on_submit(Message::SearchMods),
+                button("Search").on_press(Message::SearchMods)
+            ]
+            .spacing(5),
+            search_results.spacing(1),
+            row![
+                "Selected mod",
+                text(self.mod_browser.selected_mod.as_deref().unwrap_or("None")),
+            ]
+            .spacing(5),
+            row![
+                "Version",
+                text_input("Latest compatible version", &self.mod_browser.version)
+                    .on_input(Message::EditModVersion),
+            ]
+            .spacing(5),
+            row![
+                "Side",
+                pick_list(
+                    &MOD_SIDES[..],
+                    self.mod_browser.side,
+                    Message::SelectModSide
+                )
+                .placeholder("Pack default"),
+            ]
+            .spacing(5),
+            row![
+                button("Back").on_press(Message::SwitchView(ManagerView::ProfileView {
+                    profile: profile_name.into()
+                })),
+                button("Add mod").on_press_maybe(
+                    self.mod_browser
+                        .selected_mod
+                        .as_ref()
+                        .map(|_| Message::AddMod)
+                ),
+            ]
+            .spacing(10)
+        ]
+        .align_items(Alignment::Center)
+        .spacing(10)
+        .padding(20);
+
+        if let Some(status) = &self.mod_browser.status {
+            mod_browser = mod_browser.push(text(status));
+        }
+
+        scrollable(mod_browser).into()
+    }
+
+    fn view_profile_select(&self) -> Element<Message> {
+        let mut profile_select = column![text("Profile Select"),];
+
+        let mut profiles_list: Column<Message> = column!();
+        let mut profile_names = self.userdata.get_profile_names();
+        profile_names.sort();
+
+        for profile_name in profile_names.iter() {
+            profiles_list = profiles_list.push(
+                button(text(profile_name))
+                    .on_press(Message::SwitchView(ManagerView::ProfileView {
+                        profile: profile_name.into(),
+                    }))
+                    .width(Length::Fill),
+            );
+        }
+
+        profile_select =
+            profile_select.push(profiles_list.align_items(Alignment::Center).spacing(1));
+        profile_select = profile_select
+            .push(button("Add profile").on_press(Message::SwitchView(ManagerView::AddProfile)));
+        profile_select = profile_select
+            .push(button("Settings").on_press(Message::SwitchView(ManagerView::Settings)));
+
+        scrollable(
+            profile_select
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .padding(10),
+        )
+        .into()
+    }
+
+    fn view_profile_view(&self, profile_name: &str) -> Element<Message> {
+        let mut profile_view = if let Some(profile) = self.userdata.get_profile(profile_name) {
+            column![
+                text(format!("Modpack Profile: {profile_name}"))
+                    .horizontal_alignment(iced::alignment::Horizontal::Center),
+                row![
+                    "Modpack source",
+                    text_input("Modpack source", &profile.pack_source.to_string()),
+                ]
+                .spacing(5),
+                row![
+                    "Instance folder",
+                    text_input(
+                        "Instance folder",
+                        &profile.instance_folder.display().to_string()
+                    ),
+                ]
+                .spacing(20),
+                row!["Mods to download", text(profile.side),].spacing(5),
+                button("Install").on_press(Message::PreviewInstall(profile_name.into())),
+                row![
+                    button("Back").on_press(Message::SwitchView(ManagerView::ProfileSelect)),
+                    button("Edit profile").on_press(Message::SwitchView(
+                        ManagerView::EditProfile {
+                            profile: profile_name.into()
+                        }
+                    )),
+                    button("Delete profile").on_press(Message::DeleteProfile(profile_name.into())),
+                    button("Browse mods").on_press(Message::SwitchView(ManagerView::ModBrowser {
+                        profile: profile_name.into()
+                    }))
+                ]
+                .spacing(5)
+            ]
+        } else {
+            column![
+                text(format!("Unable to load profile: {profile_name}")),
+                button("Back").on_press(Message::SwitchView(ManagerView::ProfileSelect)),
+            ]
+        };
+
+        if let Some(err) = &self.userdata_load_error {
+            profile_view = profile_view.push(text(err));
+        }
+
+        match &self.current_install_status {
+            ProfileInstallStatus::NotStarted => {}
+            ProfileInstallStatus::Previewing => {
+                profile_view = profile_view.push(text("Checking what will change..."));
+            }
+            ProfileInstallStatus::AwaitingConfirmation(preview) => {
+                profile_view = profile_view
+                    .push(scrollable(text(preview.to_string())).height(Length::Fixed(120.0)))
+                    .push(
+                        row![
+                            button("Confirm install")
+                                .on_press(Message::InstallProfile(profile_name.into())),
+                            button("Cancel").on_press(Message::CancelInstall)
+                        ]
+                        .spacing(5),
+                    );
+            }
+            ProfileInstallStatus::Installing(None) => {
+                profile_view = profile_view.push(text("Installing..."));
+            }
+            ProfileInstallStatus::Installing(Some(progress)) => {
+                profile_view = profile_view.push(progress_bar(
+                    0.0..=progress.total as f32,
+                    progress.current as f32,
+                ));
+                profile_view = profile_view.push(text(if progress.current < progress.total {
+                    format!(
+                        "Installing mods ({}/{}): {}",
+                        progress.current + 1,
+                        progress.total,
+                        progress.filename
+                    )
+                } else {
+                    format!("Installed mods ({}/{})", progress.current, progress.total)
+                }));
+            }
+            ProfileInstallStatus::Success => {
+                profile_view = profile_view.push(text("Installed"));
+            }
+            ProfileInstallStatus::Error(err) => {
+                profile_view =
+                    profile_view.push(text(format!("Failed to install profile: {}", err)));
+            }
+        };
+
+        profile_view
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .padding(20)
+            .into()
+    }
+
+    fn view_profile_edit(
+        &self,
+        previous_view: ManagerView,
+        can_edit_name: bool,
+    ) -> Element<Message> {
+        let current_instance_directory_display = match &self.profile_edit_settings.instance_dir {
+            Some(instance_dir) => instance_dir.display().to_string(),
+            None => String::from(""),
+        };
+        let mut profile_editor = column![
+            text("Profile Add/Edit").horizontal_alignment(iced::alignment::Horizontal::Center),
+            row![
+                "Profile name",
+                if can_edit_name {
+                    text_input("Enter your profile name", &self.profile_edit_settings.name)
+                        .on_input(Message::EditProfileName)
+                } else {
+                    text_input("Profile name", &self.profile_edit_settings.name)
+                }
+            ]
+            .spacing(5),
+            row![
+                "Modpack source",
+                text_input(
+                    "Enter a modpack source. E.g git+https://github.com/WarrenHood/SomeModPack",
+                    &self.profile_edit_settings.pack_source
+                )
+                .on_input(Message::EditPackSource)
+            ]
+            .spacing(5),
+            row![
+                "Instance directory",
+                text_input(
+                    "Browse for your MC instance directory (contains your mods folder)",
+                    &current_instance_directory_display
+                ),
+                button("Browse").on_press(Message::BrowseInstanceDir)
+            ]
+            .spacing(5),
+            row![
+                "Mods directory",
+                text_input(
+                    profiles::DEFAULT_MODS_DIR_NAME,
+                    &self.profile_edit_settings.mods_dir_name
+                )
+                .on_input(Message::EditModsDirName)
+            ]
+            .spacing(5),
+            checkbox(
+                "Include optional mods",
+                self.profile_edit_settings.include_optional
+            )
+            .on_toggle(Message::ToggleIncludeOptional),
+            checkbox(
+                "Symlink pack files (local modpack sources only)",
+                self.profile_edit_settings.symlink_files
+            )
+            .on_toggle(Message::ToggleSymlinkFiles),
+            checkbox(
+                "Remove empty directories left in pack folders",
+                self.profile_edit_settings.prune_empty_dirs
+            )
+            .on_toggle(Message::TogglePruneEmptyDirs),
+            row![
+                button("Back").on_press(Message::SwitchView(previous_view)),
+                button("Save").on_press(Message::SaveProfile)
+            ]
+            .spacing(10)
+        ]
+        .align_items(Alignment::Center)
+        .spacing(10)
+        .padding(20);
+
+        if let Some(save_error) = &self.profile_save_error {
+            profile_editor =
+                profile_editor.extend([row!["Save error", text(save_error)].spacing(10).into()]);
+        };
+
+        profile_editor.into()
+    }
+}
+
+/// Add a mod to a local modpack, only saving the modpack once the mod and its dependencies are pinned
+async fn add_mod_to_pack(pack_dir: &Path, mod_meta: &ModMeta) -> anyhow::Result<()> {
+    let mut modpack_meta = ModpackMeta::load_from_directory(pack_dir)?.add_mod(mod_meta)?;
+    let mut pack_lock =
+        PinnedPackMeta::load_from_directory(pack_dir, DependencyVersions::Latest).await?;
+    pack_lock.remove_mod(&mod_meta.name, true, true)?;
+    pack_lock
+        .pin_mod_and_deps(mod_meta, &modpack_meta, DependencyVersions::Latest)
+        .await?;
+    modpack_meta.record_project_id(&mod_meta.name, &pack_lock);
+    crate::transaction::save_pack_and_lock(pack_dir, &modpack_meta, &pack_lock)
+}
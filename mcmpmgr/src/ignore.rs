@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// File listing files in the mods folder that mcmpmgr should never delete, using gitignore-style patterns
+pub const IGNORE_FILENAME: &str = ".mcmpmgrignore";
+
+#[derive(Debug, Clone)]
+struct IgnorePattern {
+    pattern: Vec<char>,
+    /// A `!pattern`, which un-ignores files matched by earlier patterns
+    negated: bool,
+}
+
+/// Gitignore-style patterns matching the names of files to leave alone
+///
+/// Patterns support `*`, `?` and `[...]` classes. Like gitignore, the last pattern matching a file wins,
+/// so `!pattern` can un-ignore files matched by earlier patterns
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    pub fn parse(contents: &str) -> Self {
+        let mut rules = Self::default();
+        rules.add_patterns(contents);
+        rules
+    }
+
+    fn add_patterns(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, line),
+            };
+            // Only files are ever deleted, so directory patterns can't match anything
+            if pattern.ends_with('/') {
+                continue;
+            }
+            // The mods folder is flat, so patterns are only matched against file names
+            let pattern = pattern.trim_start_matches('/');
+            self.patterns.push(IgnorePattern {
+                pattern: pattern.chars().collect(),
+                negated,
+            });
+        }
+    }
+
+    /// Add the patterns from the ignore file in a directory, if it has one.
+    /// They take precedence over the patterns already added
+    pub fn add_dir(&mut self, dir: &Path) -> Result<()> {
+        let ignore_file_path = dir.join(IGNORE_FILENAME);
+        if ignore_file_path.is_file() {
+            let contents = std::fs::read_to_string(&ignore_file_path)
+                .with_context(|| format!("Failed to read {}", ignore_file_path.display()))?;
+            self.add_patterns(&contents);
+        }
+        Ok(())
+    }
+
+    /// Whether a file should be left alone. The ignore file itself always is
+    pub fn is_ignored(&self, file_name: &str) -> bool {
+        if file_name == IGNORE_FILENAME {
+            return true;
+        }
+        let file_name: Vec<char> = file_name.chars().collect();
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| glob_match(&pattern.pattern, &file_name))
+            .is_some_and(|pattern| !pattern.negated)
+    }
+}
+
+/// Match a name against a glob pattern supporting `*`, `?`, `[...]` classes and `\` escapes
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob_match(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob_match(rest, &name[1..]),
+        Some(('[', rest)) if rest.contains(&']') => {
+            let class_end = rest
+                .iter()
+                .skip(1)
+                .position(|c| *c == ']')
+                .map_or(0, |i| i + 1);
+            let (class, rest) = (&rest[..class_end], &rest[class_end + 1..]);
+            let (negated, class) = match class.split_first() {
+                Some(('!' | '^', class)) => (true, class),
+                _ => (false, class),
+            };
+            name.first()
+                .is_some_and(|c| class_matches(class, *c) != negated)
+                && glob_match(rest, &name[1..])
+        }
+        Some(('\\', [escaped, rest @ ..])) => {
+            name.first() == Some(escaped) && glob_match(rest, &name[1..])
+        }
+        Some((c, rest)) => name.first() == Some(c) && glob_match(rest, &name[1..]),
+    }
+}
+
+/// Whether a character is in a glob class, eg. `a-z0-9_`
+fn class_matches(class: &[char], c: char) -> bool {
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            if (class[i]..=class[i + 2]).contains(&c) {
+                return true;
+            }
+            i += 3;
+        } else {
+            if class[i] == c {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+#[test]
+fn test_ignore_rules() {
+    let rules = IgnoreRules::parse(
+        "# Client performance mods added by hand\n\
+         perf-*.jar\n\
+         !perf-broken.jar\n\
+         /optifine-1.20.?.jar\n\
+         [ab]ddon-[0-9].jar\n\
+         \\#literal.jar\n\
+         config/\n",
+    );
+    assert!(rules.is_ignored("perf-sodium.jar"));
+    assert!(!rules.is_ignored("perf-broken.jar"));
+    assert!(rules.is_ignored("optifine-1.20.1.jar"));
+    assert!(!rules.is_ignored("optifine-1.20.10.jar"));
+    assert!(rules.is_ignored("addon-1.jar"));
+    assert!(rules.is_ignored("bddon-9.jar"));
+    assert!(!rules.is_ignored("cddon-1.jar"));
+    assert!(!rules.is_ignored("addon-x.jar"));
+    assert!(rules.is_ignored("#literal.jar"));
+    assert!(!rules.is_ignored("config"));
+    assert!(!rules.is_ignored("sodium.jar"));
+    assert!(rules.is_ignored(IGNORE_FILENAME));
+
+    // Later patterns win
+    let mut rules = IgnoreRules::parse("*.jar");
+    assert!(rules.is_ignored("sodium.jar"));
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join(IGNORE_FILENAME), "!sodium.jar").unwrap();
+    rules.add_dir(dir.path()).unwrap();
+    assert!(!rules.is_ignored("sodium.jar"));
+    assert!(rules.is_ignored("lithium.jar"));
+    assert!(!IgnoreRules::default().is_ignored("lithium.jar"));
+}
@@ -1,7 +1,16 @@
+pub mod error;
+pub mod export;
 pub mod file_merge;
 pub mod file_meta;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod ignore;
 pub mod mod_meta;
 pub mod modpack;
 pub mod profiles;
 pub mod providers;
 pub mod resolver;
+#[cfg(test)]
+mod test_server;
+pub mod transaction;
+pub mod watch;
@@ -1,19 +1,37 @@
+mod completions;
+mod error;
+mod export;
 mod file_merge;
 mod file_meta;
+mod ignore;
 mod mod_meta;
 mod modpack;
 mod profiles;
 mod providers;
 mod resolver;
+mod self_update;
+#[cfg(test)]
+mod test_server;
+mod transaction;
+mod watch;
 
 use anyhow::{Context, Error, Result};
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use error::McmpmgrError;
 use file_meta::{get_normalized_relative_path, FileApplyPolicy, FileMeta};
-use mod_meta::{ModMeta, ModProvider};
-use modpack::ModpackMeta;
-use profiles::{PackSource, Profile};
+use mod_meta::{ModMeta, ModProvider, ProjectKind};
+use modpack::{ModpackMeta, VersionSelection};
+use profiles::{ModSideOverride, PackSource, Profile, TemplateVariable};
 use providers::DownloadSide;
-use std::path::PathBuf;
+use resolver::DependencyVersions;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io::{IsTerminal, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
 /// A Minecraft Modpack Manager
 #[derive(Parser)]
@@ -21,6 +39,15 @@ use std::path::PathBuf;
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// Don't use or update the on-disk cache of Modrinth responses
+    #[arg(long, global = true, action)]
+    no_http_cache: bool,
+    /// Move a malformed modpack.lock or profiles data file aside and regenerate it, instead of failing
+    #[arg(long, global = true, action)]
+    auto_recover: bool,
+    /// Number of worker threads to run with (defaults to $MCMPMGR_THREADS, or the number of CPU cores)
+    #[arg(long, global = true)]
+    threads: Option<NonZeroUsize>,
 }
 
 #[derive(Subcommand)]
@@ -35,9 +62,18 @@ enum Commands {
         /// The modpack's Minecraft version
         #[arg(long, default_value_t = String::from("1.20.1"))]
         mc_version: String,
+        /// Other Minecraft versions mods may list instead of the modpack's Minecraft version (eg. 1.20 for a 1.20.1 pack)
+        #[arg(long)]
+        accept_mc_version: Vec<String>,
         /// The modpack's modloader
         #[arg(long, default_value_t = modpack::ModLoader::Fabric)]
         modloader: modpack::ModLoader,
+        /// The modpack's modloader version (defaults to the latest stable version)
+        #[arg(long)]
+        loader_version: Option<String>,
+        /// Only pin mods (including dependencies) that list the modpack's exact Minecraft version
+        #[arg(long)]
+        strict: bool,
         /// Default providers to download the mods from for the modpack (can be overridden on a per-mod basis)
         #[arg(long)]
         providers: Vec<ModProvider>,
@@ -49,35 +85,98 @@ enum Commands {
         /// The modpack's Minecraft version
         #[arg(long, default_value_t = String::from("1.20.1"))]
         mc_version: String,
+        /// Other Minecraft versions mods may list instead of the modpack's Minecraft version (eg. 1.20 for a 1.20.1 pack)
+        #[arg(long)]
+        accept_mc_version: Vec<String>,
         /// The modpack's modloader
         #[arg(long, default_value_t = modpack::ModLoader::Fabric)]
         modloader: modpack::ModLoader,
+        /// The modpack's modloader version (defaults to the latest stable version)
+        #[arg(long)]
+        loader_version: Option<String>,
+        /// Only pin mods (including dependencies) that list the modpack's exact Minecraft version
+        #[arg(long)]
+        strict: bool,
         /// Default providers to download the mods from for the modpack (can be overridden on a per-mod basis)
         #[arg(long)]
         providers: Vec<ModProvider>,
     },
     /// Add a new mod to the modpack
     Add {
-        /// Name of the mod to add to the project, optionally including a version
-        name: String,
+        /// Name of the mod to add to the project, optionally including a version.
+        /// Pass `-` to read mod names from stdin, one per line
+        #[arg(
+            required_unless_present = "batch_from_modrinth_collection",
+            conflicts_with = "batch_from_modrinth_collection"
+        )]
+        name: Option<String>,
+        /// Add every mod in a Modrinth collection, skipping any that don't support the pack
+        #[arg(long, conflicts_with = "url")]
+        batch_from_modrinth_collection: Option<String>,
         /// Providers to download the mods from
         #[arg(long)]
         providers: Vec<ModProvider>,
         /// URL to download the mod from
         #[arg(long)]
         url: Option<String>,
-        /// Use exact transitive mod dependency versions
-        #[arg(long, short, action)]
+        /// Which versions of the mod's dependencies to pin: `exact` pins the versions the mod declares,
+        /// `latest` pins the latest versions that support the pack
+        #[arg(long, default_value_t = DependencyVersions::Latest)]
+        deps: DependencyVersions,
+        /// Deprecated alias for `--deps exact`
+        #[arg(long, short, action, conflicts_with = "deps")]
         locked: bool,
         /// Minecraft version override
         #[arg(long)]
         mc_version: Option<String>,
+        /// Use the minecraft version an already pinned mod was resolved for as the minecraft version override,
+        /// eg. to keep an addon on the same minecraft version as the mod it extends
+        #[arg(long, conflicts_with = "mc_version")]
+        version_from: Option<String>,
         /// Modloader override
         #[arg(long, short)]
         modloader: Option<modpack::ModLoader>,
         /// Side override
         #[arg(long, short)]
         side: Option<DownloadSide>,
+        /// Install the mod on the client side (only the client side unless --server is also passed)
+        #[arg(long, action, conflicts_with = "side")]
+        client: bool,
+        /// Install the mod on the server side (only the server side unless --client is also passed)
+        #[arg(long, action, conflicts_with = "side")]
+        server: bool,
+        /// Resolve the mod and its dependencies and print the resulting pins, without writing anything
+        #[arg(long, action)]
+        resolve_only: bool,
+        /// Only add the mod to modpack.toml without pinning it. When adding many mods, pass this to each
+        /// and run `mcmpmgr update` once at the end to pin them all together
+        #[arg(long, action, conflicts_with = "resolve_only")]
+        no_install: bool,
+        /// Check that the mod and all of its required dependencies resolve for the pack's minecraft version and
+        /// modloader first, and don't add it if any of them don't
+        #[arg(long, action)]
+        test_compat: bool,
+        /// Print a json trace of every provider attempt made while resolving
+        #[arg(long, action)]
+        trace: bool,
+        /// Add the mod to an optional mod group, so it is only installed when the group is enabled
+        #[arg(long)]
+        group: Option<String>,
+        /// Allow beta and alpha versions of the mod to be pinned even when a release is available
+        #[arg(long, action)]
+        include_prerelease: bool,
+        /// Kind of project (mod, resourcepack, shaderpack or datapack), used to pick the pack's default providers for it
+        #[arg(long, default_value = "mod")]
+        kind: ProjectKind,
+        /// Notes about the mod, eg. why it is in the pack
+        #[arg(long)]
+        note: Option<String>,
+        /// Pin the oldest compatible versions of the mod and its dependencies, instead of following the pack's version_selection
+        #[arg(long, action)]
+        oldest: bool,
+        /// Load the mod after this mod, for packs that set prefix_load_order. Can be given more than once
+        #[arg(long)]
+        after: Vec<String>,
     },
     /// Remove a mod from the modpack
     Remove {
@@ -86,12 +185,60 @@ enum Commands {
         /// Forcefully remove the mod without checking if anything depends on it
         #[arg(long, short, action)]
         force: bool,
+        /// Only remove the named mod, leaving any dependencies that are no longer needed pinned until the next update
+        #[arg(long, action)]
+        no_prune: bool,
+        /// Also forbid the mod, so it can't be pulled back in as a dependency
+        #[arg(long, action)]
+        and_forbid: bool,
+    },
+    /// Allow a mod in the modpack. Once any mod is allowed, only allowed mods (and dependencies) can be added
+    Allow {
+        /// Name of the mod to allow
+        name: String,
+        /// Remove the mod from the allowed mods instead
+        #[arg(long, action)]
+        remove: bool,
+    },
+    /// Pin a dependency at a specific version, overriding the version any mods depending on it ask for
+    PinDep {
+        /// Name of the dependency to override
+        name: String,
+        /// Version to pin the dependency at
+        #[arg(required_unless_present = "remove")]
+        version: Option<String>,
+        /// Remove the override instead
+        #[arg(long, action, conflicts_with = "version")]
+        remove: bool,
+    },
+    /// Show or change the notes about a mod in the modpack
+    Note {
+        /// Name of the mod
+        name: String,
+        /// New notes for the mod. The current notes are printed if not given
+        note: Option<String>,
+        /// Remove the notes instead
+        #[arg(long, action, conflicts_with = "note")]
+        clear: bool,
+    },
+    /// List the mods in the modpack, along with any notes about them and their source urls
+    List,
+    /// Open the source code or homepage of a pinned mod in the browser
+    Open {
+        /// Name of the mod to open
+        name: String,
     },
     /// Forbid a mod from the modpack
     Forbid {
         /// Name of the mod to remove and forbid from the modpack
         name: String,
     },
+    /// Add the mods in a CurseForge instance's mods folder to the pack, identifying them by their CurseForge fingerprints.
+    /// Needs the pack to use the CurseForge provider, and $CURSEFORGE_API_KEY to be set
+    ImportCurseforge {
+        /// Mods folder of the CurseForge instance
+        mods_dir: PathBuf,
+    },
     /// Download the mods in the pack to a specified folder
     Download {
         /// Mods directory
@@ -105,17 +252,146 @@ enum Commands {
         /// Download mods from a local modpack
         #[arg(long)]
         path: Option<PathBuf>,
+        /// Install mods from an offline bundle, without downloading anything
+        #[arg(long, conflicts_with_all = ["git", "path"])]
+        bundle: Option<PathBuf>,
+        /// Also download mods that are only optional for the side
+        #[arg(long, action)]
+        include_optional: bool,
+        /// Also download the mods in an optional mod group. Can be given more than once
+        #[arg(long)]
+        group: Vec<String>,
+        /// Base url of a mirror to download mod files from in place of their pinned urls,
+        /// eg. an internal CDN. Files are still verified against their pinned hashes
+        #[arg(long)]
+        mirror: Option<String>,
+        /// Delete files in the mods folder that aren't pinned, even if the pack doesn't set manage_mods_exclusively
+        #[arg(long, action, conflicts_with = "no_prune")]
+        prune: bool,
+        /// Leave files in the mods folder that aren't pinned, even if the pack sets manage_mods_exclusively
+        #[arg(long, action)]
+        no_prune: bool,
+        /// Only download this mod and its dependencies, without deleting anything. Can be given more than once
+        #[arg(long)]
+        only: Vec<String>,
     },
     /// Update all mods to the latest possible version
     Update {
-        /// Use exact transitive mod dependency versions
-        #[arg(long, short, action)]
+        /// Which versions of mods' dependencies to pin: `exact` pins the versions the mods declare,
+        /// `latest` pins the latest versions that support the pack
+        #[arg(long, default_value_t = DependencyVersions::Latest)]
+        deps: DependencyVersions,
+        /// Deprecated alias for `--deps exact`
+        #[arg(long, short, action, conflicts_with = "deps")]
         locked: bool,
+        /// Print a json trace of every provider attempt made while resolving
+        #[arg(long, action)]
+        trace: bool,
+        /// Keep the pinned versions of the modpack's own mods, and only update their dependencies
+        #[arg(long, action)]
+        upgrade_deps_only: bool,
+        /// Print a markdown changelog of the updated mods, or write it to the given file
+        #[arg(long)]
+        changelog: Option<Option<PathBuf>>,
+        /// Pin the oldest compatible versions of mods, instead of following the pack's version_selection
+        #[arg(long, action)]
+        oldest: bool,
+    },
+    /// Report any mods that modpack.toml and the lock file disagree on, eg. after `add --no-install`.
+    /// Fails if there are any, until `mcmpmgr update` pins them
+    Status,
+    /// List pinned mods that have newer versions available, without updating anything
+    Outdated {
+        /// Output the results as json
+        #[arg(long, action)]
+        json: bool,
+        /// Print a markdown changelog of the available updates, or write it to the given file
+        #[arg(long)]
+        changelog: Option<Option<PathBuf>>,
+    },
+    /// Check that every download url in the lock file is still live, without changing anything
+    VerifyLock {
+        /// Output the results as json
+        #[arg(long, action)]
+        json: bool,
+        /// Look for new urls for the same mod versions and update the lock file with them
+        #[arg(long, action)]
+        repair: bool,
+    },
+    /// Rewrite the lock file in the current directory, without resolving anything again
+    Lock {
+        /// Remove duplicate files and stale entries from the lock file and write it in its canonical form
+        #[arg(long, action, required = true)]
+        normalize: bool,
+    },
+    /// Export the modpack in the current directory
+    Export {
+        /// Output file (defaults to `<pack name>-server.zip`, `<pack name>-bundle.tar.zst` or `<pack name>.mrpack`)
+        output: Option<PathBuf>,
+        /// Format to export the modpack as
+        #[arg(long, default_value_t = export::ExportFormat::ServerPack)]
+        format: export::ExportFormat,
+        /// Also download the modloader's server installer into the export
+        #[arg(long, action)]
+        server_installer: bool,
+        /// Version of the pack to put in an mrpack export
+        #[arg(long, default_value = "1.0.0")]
+        pack_version: String,
+    },
+    /// Install the modpack in the current directory to an instance, and install it again whenever the pack changes
+    Watch {
+        /// Instance directory (containing a mods folder)
+        instance_directory: PathBuf,
+        /// Side to install the pack for
+        #[arg(long, default_value_t = DownloadSide::Client)]
+        side: DownloadSide,
+        /// Also install mods that are only optional for the side
+        #[arg(long, action)]
+        include_optional: bool,
+        /// Symlink pack files into the instance instead of copying them
+        #[arg(long, action)]
+        symlink: bool,
     },
     /// Manage local files in the modpack
     File(FileArgs),
+    /// Manage the modpack's default providers. Lists them if no subcommand is given
+    Providers(ProvidersArgs),
     /// Manage mcmpmgr profiles
     Profile(ProfileArgs),
+    /// Delete files from the download cache that are no longer pinned
+    Prune {
+        /// Delete cached mod files that none of the packs pin
+        #[arg(long, action, required = true)]
+        cache: bool,
+        /// Pack directories whose pinned mod files should be kept (defaults to the packs of every profile)
+        #[arg(long = "pack")]
+        packs: Vec<PathBuf>,
+        /// Keep cached files that were used in the last N days, even if they aren't pinned
+        #[arg(long)]
+        keep_days: Option<u64>,
+    },
+    /// Print a shell completion script for mcmpmgr
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    /// Print values for completion scripts to complete with
+    #[command(name = completions::COMPLETE_COMMAND, hide = true)]
+    Complete {
+        /// The values to print
+        values: completions::DynamicValues,
+    },
+    /// Check GitHub for a newer release of mcmpmgr. Nothing is printed if GitHub can't be reached
+    SelfUpdate,
+    /// Print the paths mcmpmgr reads from and the environment variables it uses
+    Config {
+        /// Print as json instead of toml
+        #[arg(long, action)]
+        json: bool,
+    },
+    /// Launch the modpack manager GUI
+    #[cfg(feature = "gui")]
+    Gui,
 }
 
 #[derive(Debug, Args)]
@@ -133,7 +409,8 @@ enum FileCommands {
     Add {
         /// Local path to file/folder to include in the pack (must be in the pack root)
         local_path: PathBuf,
-        /// Target path to copy the file/folder to relative to the MC instance directory
+        /// Target path to copy the file/folder to relative to the MC instance directory.
+        /// Adding the file/folder again with another target path copies it to both
         #[arg(short, long)]
         target_path: Option<String>,
         /// Side to copy the file/folder to
@@ -142,6 +419,15 @@ enum FileCommands {
         /// File apply policy - whether to always apply the file or just apply it once (if the file doesn't exist), or merge (mergeretain or mergeoverwrite)
         #[arg(long, default_value_t = FileApplyPolicy::MergeOverwrite)]
         apply_policy: FileApplyPolicy,
+        /// Replace `${VAR}` placeholders in the file(s) with the installing profile's variables
+        #[arg(long, action)]
+        templated: bool,
+        /// Only apply the file/folder for profiles installing this variant of the pack. Can be given more than once
+        #[arg(long)]
+        variant: Vec<String>,
+        /// Files/Folders with a higher priority are applied later, so they override lower priority ones targeting the same paths
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        priority: i32,
     },
     /// Show metadata about a file in the pack
     Show {
@@ -152,6 +438,32 @@ enum FileCommands {
     Remove {
         /// local path to file/folder to remove
         local_path: PathBuf,
+        /// Only remove the file/folder's target at this path, keeping any other targets
+        #[arg(short, long)]
+        target_path: Option<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct ProvidersArgs {
+    #[command(subcommand)]
+    command: Option<ProvidersCommands>,
+}
+
+#[derive(Debug, Subcommand)]
+enum ProvidersCommands {
+    /// List the modpack's default providers, in the order they are tried
+    List,
+    /// Add a default provider, tried after the existing ones
+    Add {
+        /// Provider to add
+        provider: ModProvider,
+    },
+    /// Remove a default provider
+    Remove {
+        /// Provider to remove
+        provider: ModProvider,
     },
 }
 
@@ -173,17 +485,65 @@ enum ProfileCommands {
         /// Side to download the profile for. (Client, Server, or Both)
         #[arg(long, default_value_t = DownloadSide::Server)]
         side: DownloadSide,
-        /// A local file path to a modpack directory or a git repo url prefixed with 'git+'
+        /// A local file path to a modpack directory, a path relative to the packs root prefixed with 'rel+'
+        /// ($MCMPMGR_PACKS_ROOT, or the config directory), a git repo url prefixed with 'git+',
+        /// or an offline bundle prefixed with 'bundle+'
         #[arg(long, short)]
         pack_source: PackSource,
         /// Instance directory (containing a mods folder)
         #[arg(long, short)]
         instance_directory: PathBuf,
+        /// Also install mods that are only optional for the side
+        #[arg(long, action)]
+        include_optional: bool,
+        /// Symlink pack files into the instance instead of copying them. Only works for local pack sources
+        #[arg(long, action)]
+        symlink: bool,
+        /// Also install the mods in an optional mod group. Can be given more than once
+        #[arg(long)]
+        group: Vec<String>,
+        /// Install a mod on a different side for this profile, eg. `--mod-side debug-mod=both`. Can be given more than once
+        #[arg(long)]
+        mod_side: Vec<ModSideOverride>,
+        /// Name of the instance's mods directory, if it isn't `mods`
+        #[arg(long)]
+        mods_dir_name: Option<String>,
+        /// Set a variable for the pack's templated files, eg. `--var SERVER_NAME=Survival`. Can be given more than once
+        #[arg(long)]
+        var: Vec<TemplateVariable>,
+        /// Remove directories left empty inside the pack's folders after applying pack files
+        #[arg(long, action)]
+        prune_empty_dirs: bool,
+        /// Install a variant of the pack, eg. `lite`, with its groups and the files tagged for it
+        #[arg(long)]
+        variant: Option<String>,
     },
-    /// Install a profile
+    /// Install one or more profiles
     Install {
-        /// Name of the profile to install
-        name: String,
+        /// Names of the profiles to install
+        #[arg(required_unless_present = "all")]
+        names: Vec<String>,
+        /// Install all profiles
+        #[arg(long, action, conflicts_with = "names")]
+        all: bool,
+        /// Install the profiles concurrently
+        #[arg(long, action)]
+        parallel_profiles: bool,
+        /// Only print what installing the profiles would change, without changing their instances
+        #[arg(long, action)]
+        dry_run: bool,
+        /// Only install this mod and its dependencies, without deleting any mods. Can be given more than once
+        #[arg(long)]
+        only: Vec<String>,
+    },
+    /// Check whether profiles' git pack sources have new commits or their mods have updates, without installing anything
+    CheckUpdates {
+        /// Names of the profiles to check
+        #[arg(required_unless_present = "all")]
+        names: Vec<String>,
+        /// Check all profiles
+        #[arg(long, action, conflicts_with = "names")]
+        all: bool,
     },
     /// Show information about a profile
     Show {
@@ -195,18 +555,332 @@ enum ProfileCommands {
         /// Profile to remove
         name: String,
     },
+    /// Copy a profile to a new name
+    Copy {
+        /// Name of the profile to copy
+        source: String,
+        /// Name of the new profile
+        destination: String,
+    },
+}
+
+/// Exit code when a batch command succeeds for some items but fails for others
+const EXIT_PARTIAL_FAILURE: u8 = 2;
+
+/// An error that should make mcmpmgr exit with a specific exit code
+#[derive(Debug)]
+struct ExitCodeError {
+    code: u8,
+    message: String,
+}
+
+impl ExitCodeError {
+    fn partial_failure(message: String) -> Self {
+        Self {
+            code: EXIT_PARTIAL_FAILURE,
+            message,
+        }
+    }
 }
 
-#[tokio::main(flavor = "multi_thread")]
-async fn main() -> anyhow::Result<()> {
+impl std::fmt::Display for ExitCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ExitCodeError {}
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    providers::http_cache::set_enabled(!cli.no_http_cache);
+    transaction::set_auto_recover(cli.auto_recover);
+
+    // The GUI starts its own async runtime, so it needs to be launched outside of ours
+    #[cfg(feature = "gui")]
+    if let Some(Commands::Gui) = cli.command {
+        return match mcmpmgr::gui::run() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Error: {e:?}");
+                ExitCode::FAILURE
+            }
+        };
+    }
 
+    let worker_threads = match worker_threads(cli.threads, std::env::var(THREADS_ENV).ok()) {
+        Ok(worker_threads) => worker_threads,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(worker_threads) = worker_threads {
+        runtime.worker_threads(worker_threads);
+    }
+    let runtime = runtime
+        .enable_all()
+        .build()
+        .expect("Tokio runtime should be buildable");
+
+    match runtime.block_on(run(cli)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            if let Some(McmpmgrError::HashMismatch { .. }) = McmpmgrError::find(&e) {
+                eprintln!(
+                    "The downloaded file doesn't match the lock. It may have been corrupted in transit or changed since it was pinned"
+                );
+            }
+            // Anything that isn't explicitly a partial failure is a total failure
+            ExitCode::from(e.downcast_ref::<ExitCodeError>().map_or(1, |e| e.code))
+        }
+    }
+}
+
+/// Get the dependency versions to pin, honouring the deprecated `--locked` flag
+fn resolve_dependency_versions(deps: DependencyVersions, locked: bool) -> DependencyVersions {
+    if locked {
+        eprintln!("Warning: --locked is deprecated. Use --deps exact instead");
+        DependencyVersions::Exact
+    } else {
+        deps
+    }
+}
+
+/// Print the provider attempts recorded while resolving mods as json
+fn print_trace(pack_lock: &resolver::PinnedPackMeta) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&pack_lock.take_trace())?);
+    Ok(())
+}
+
+/// Print a markdown changelog, or write it to a file if one is given
+fn output_changelog(
+    changelogs: &[resolver::ModChangelog],
+    changelog_path: Option<PathBuf>,
+) -> Result<()> {
+    let changelog = resolver::changelog_markdown(changelogs);
+    match changelog_path {
+        Some(changelog_path) => {
+            std::fs::write(&changelog_path, changelog)?;
+            println!("Wrote changelog to {}", changelog_path.display());
+        }
+        None => print!("{changelog}"),
+    }
+    Ok(())
+}
+
+/// Read mod names to add, one per line. Blank lines and `#` comments are skipped
+fn read_mod_names(reader: impl std::io::BufRead) -> Result<Vec<String>> {
+    let mut mod_names = vec![];
+    for line in reader.lines() {
+        let line = line.context("Failed to read mod names")?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            mod_names.push(line.to_string());
+        }
+    }
+    Ok(mod_names)
+}
+
+/// Ask the user which of the suggested mods they meant, when a mod can't be found
+///
+/// Outside of a terminal, or if `interactive` is unset, the suggestions are only printed and the add fails
+fn pick_suggested_mod(
+    name: &str,
+    suggestions: &[providers::modrinth::ModrinthSearchResult],
+    interactive: bool,
+) -> Result<String> {
+    if suggestions.is_empty() {
+        anyhow::bail!("No mod called {name} was found on Modrinth")
+    }
+    println!("No mod called {name} was found on Modrinth. Did you mean:");
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        println!(
+            "  {}) {} ({}): {}",
+            i + 1,
+            suggestion.slug,
+            suggestion.title,
+            suggestion.description
+        );
+    }
+    if !interactive || !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        anyhow::bail!(
+            "No mod called {name} was found. Add one of the mods above by its name instead"
+        )
+    }
+
+    print!(
+        "Pick a mod to add (1-{}), or press enter to cancel: ",
+        suggestions.len()
+    );
+    std::io::stdout().flush()?;
+    let mut choice = String::new();
+    std::io::stdin().read_line(&mut choice)?;
+    let choice = choice.trim();
+    if choice.is_empty() {
+        anyhow::bail!("Cancelled adding {name}")
+    }
+    match choice.parse::<usize>() {
+        Ok(i) if (1..=suggestions.len()).contains(&i) => Ok(suggestions[i - 1].slug.clone()),
+        _ => anyhow::bail!(
+            "Invalid choice {choice}. Expected a number from 1 to {}",
+            suggestions.len()
+        ),
+    }
+}
+
+/// Environment variable to set the number of worker threads when `--threads` isn't given
+const THREADS_ENV: &str = "MCMPMGR_THREADS";
+
+/// Get the number of worker threads to run with, if it is set by `--threads` or `$MCMPMGR_THREADS`
+fn worker_threads(
+    threads: Option<NonZeroUsize>,
+    env_threads: Option<String>,
+) -> Result<Option<usize>> {
+    if let Some(threads) = threads {
+        return Ok(Some(threads.get()));
+    }
+    env_threads
+        .map(|env_threads| {
+            env_threads
+                .trim()
+                .parse::<NonZeroUsize>()
+                .map(NonZeroUsize::get)
+                .with_context(|| {
+                    format!("Invalid {THREADS_ENV} '{env_threads}'. Expected a number of threads above 0")
+                })
+        })
+        .transpose()
+}
+
+/// Environment variables that change where mcmpmgr reads from or how it connects
+const CONFIG_ENV_VARS: &[&str] = &[
+    profiles::PACKS_ROOT_ENV,
+    THREADS_ENV,
+    providers::modrinth::MODRINTH_API_URL_ENV,
+    "HTTP_PROXY",
+    "http_proxy",
+    "HTTPS_PROXY",
+    "https_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+    "NO_PROXY",
+    "no_proxy",
+];
+
+/// The resolved paths and environment mcmpmgr runs with
+#[derive(Debug, Serialize)]
+struct ConfigInfo {
+    version: String,
+    config_dir: PathBuf,
+    data_file: PathBuf,
+    data_file_exists: bool,
+    /// Unset if the http cache is disabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_cache_dir: Option<PathBuf>,
+    /// Unset if there is no config directory to keep downloaded mod files in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    download_cache_dir: Option<PathBuf>,
+    packs_root: PathBuf,
+    current_dir: PathBuf,
+    /// Name of the modpack in the current directory, unset if it isn't a valid modpack
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current_pack: Option<String>,
+    /// Map of environment variable -> value, for the ones in [`CONFIG_ENV_VARS`] that are set
+    env: BTreeMap<String, String>,
+}
+
+impl ConfigInfo {
+    fn resolve() -> Result<Self> {
+        let data_file = profiles::Data::get_data_file_path()?;
+        let env = CONFIG_ENV_VARS
+            .iter()
+            .filter_map(|var| Some((var.to_string(), redact_password(&std::env::var(var).ok()?))))
+            .collect();
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION").into(),
+            config_dir: profiles::Data::get_config_folder_path()?,
+            data_file_exists: data_file.exists(),
+            data_file,
+            http_cache_dir: providers::http_cache::HttpCache::from_config_dir()
+                .map(|http_cache| http_cache.dir().to_path_buf()),
+            download_cache_dir: resolver::DownloadCache::from_config_dir()
+                .dir()
+                .map(Path::to_path_buf),
+            packs_root: PackSource::packs_root()?,
+            current_dir: std::env::current_dir()?,
+            current_pack: ModpackMeta::load_from_current_directory()
+                .ok()
+                .map(|modpack_meta| modpack_meta.pack_name),
+            env,
+        })
+    }
+}
+
+/// Hide the password in a url (eg. a proxy with credentials), leaving anything else untouched
+fn redact_password(value: &str) -> String {
+    match reqwest::Url::parse(value) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some("***"));
+            url.to_string()
+        }
+        _ => value.into(),
+    }
+}
+
+/// Lock the directory a command changes, so that concurrent runs can't clobber each other's state
+fn acquire_instance_lock(command: &Commands) -> Result<Option<transaction::InstanceLock>> {
+    let lock_dir = match command {
+        Commands::Init { directory, .. } => directory.clone().unwrap_or(std::env::current_dir()?),
+        Commands::Add {
+            resolve_only: false,
+            ..
+        }
+        | Commands::Remove { .. }
+        | Commands::Allow { .. }
+        | Commands::Forbid { .. }
+        | Commands::Note { note: Some(_), .. }
+        | Commands::Note { clear: true, .. }
+        | Commands::PinDep { .. }
+        | Commands::ImportCurseforge { .. }
+        | Commands::Update { .. }
+        | Commands::VerifyLock { repair: true, .. }
+        | Commands::Lock { .. }
+        | Commands::File(FileArgs {
+            command: Some(FileCommands::Add { .. } | FileCommands::Remove { .. }),
+        })
+        | Commands::Providers(ProvidersArgs {
+            command: Some(ProvidersCommands::Add { .. } | ProvidersCommands::Remove { .. }),
+        }) => std::env::current_dir()?,
+        Commands::Profile(ProfileArgs {
+            command:
+                Some(
+                    ProfileCommands::Add { .. }
+                    | ProfileCommands::Install { .. }
+                    | ProfileCommands::Remove { .. }
+                    | ProfileCommands::Copy { .. },
+                ),
+        })
+        | Commands::Prune { .. } => return Ok(Some(profiles::Data::lock()?)),
+        _ => return Ok(None),
+    };
+    Ok(Some(transaction::InstanceLock::acquire(&lock_dir)?))
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
     if let Some(command) = cli.command {
+        // Held until the command finishes
+        let _instance_lock = acquire_instance_lock(&command)?;
         match command {
             Commands::Init {
                 directory,
                 mc_version,
+                accept_mc_version,
                 modloader,
+                loader_version,
+                strict,
                 name,
                 providers,
             } => {
@@ -230,19 +904,29 @@ async fn main() -> anyhow::Result<()> {
                     dir.display()
                 );
                 let mut mc_modpack_meta: ModpackMeta =
-                    ModpackMeta::new(&pack_name, &mc_version, modloader);
+                    ModpackMeta::new(&pack_name, &mc_version, modloader).strict_mc_version(strict);
+                if let Some(loader_version) = loader_version {
+                    mc_modpack_meta = mc_modpack_meta.loader_version(&loader_version);
+                }
+                for mc_version in accept_mc_version.iter() {
+                    mc_modpack_meta = mc_modpack_meta.accept_mc_version(mc_version);
+                }
                 for provider in providers.into_iter() {
                     mc_modpack_meta = mc_modpack_meta.provider(provider);
                 }
                 mc_modpack_meta.init_project(&dir)?;
                 let modpack_lock =
-                    resolver::PinnedPackMeta::load_from_directory(&dir, true).await?;
+                    resolver::PinnedPackMeta::load_from_directory(&dir, DependencyVersions::Latest)
+                        .await?;
                 modpack_lock.save_to_dir(&dir)?;
             }
             Commands::New {
                 name,
                 mc_version,
+                accept_mc_version,
                 modloader,
+                loader_version,
+                strict,
                 providers,
             } => {
                 let dir = std::env::current_dir()?.join(PathBuf::from(&name));
@@ -252,186 +936,821 @@ async fn main() -> anyhow::Result<()> {
                     dir.display()
                 );
                 std::fs::create_dir_all(&dir)?;
+                let _new_dir_lock = transaction::InstanceLock::acquire(&dir)?;
                 let mut mc_modpack_meta: ModpackMeta =
-                    ModpackMeta::new(&name, &mc_version, modloader);
+                    ModpackMeta::new(&name, &mc_version, modloader).strict_mc_version(strict);
+                if let Some(loader_version) = loader_version {
+                    mc_modpack_meta = mc_modpack_meta.loader_version(&loader_version);
+                }
+                for mc_version in accept_mc_version.iter() {
+                    mc_modpack_meta = mc_modpack_meta.accept_mc_version(mc_version);
+                }
                 for provider in providers.into_iter() {
                     mc_modpack_meta = mc_modpack_meta.provider(provider);
                 }
                 mc_modpack_meta.init_project(&dir)?;
 
                 let modpack_lock =
-                    resolver::PinnedPackMeta::load_from_directory(&dir, true).await?;
+                    resolver::PinnedPackMeta::load_from_directory(&dir, DependencyVersions::Latest)
+                        .await?;
                 modpack_lock.save_to_dir(&dir)?;
             }
             Commands::Add {
                 name,
+                batch_from_modrinth_collection,
                 providers,
                 url,
+                deps,
                 locked,
                 mc_version,
+                version_from,
                 modloader,
                 side,
+                client,
+                server,
+                resolve_only,
+                no_install,
+                test_compat,
+                trace,
+                group,
+                include_prerelease,
+                kind,
+                note,
+                oldest,
+                after,
             } => {
+                let dependency_versions = resolve_dependency_versions(deps, locked);
                 let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
-                let old_modpack_meta = modpack_meta.clone();
-
-                let mut mod_meta = ModMeta::new(&name)?;
 
-                if let Some(modloader) = modloader {
-                    mod_meta = mod_meta.modloader(modloader);
+                // Everything is resolved in memory first, so nothing is written unless the mods can be pinned
+                let mut modpack_lock =
+                    resolver::PinnedPackMeta::load_from_current_directory(dependency_versions)
+                        .await?;
+                let old_pins = modpack_lock.get_pinned_mods().clone();
+                if trace {
+                    modpack_lock.enable_trace();
                 }
-
-                if let Some(mc_version) = mc_version {
-                    mod_meta = mod_meta.mc_version(&mc_version);
+                if oldest {
+                    modpack_lock.set_version_selection(VersionSelection::Oldest);
                 }
+                let mc_version = match version_from {
+                    Some(version_from) => {
+                        let version_from = modpack_meta.canonical_mod_name(&version_from);
+                        let game_version = modpack_lock
+                            .pinned_game_version(&version_from, &modpack_meta)
+                            .await?;
+                        println!("Using minecraft version {game_version} from {version_from}");
+                        Some(game_version)
+                    }
+                    None => mc_version,
+                };
 
-                if let Some(url) = url {
-                    mod_meta = mod_meta.url(&url);
-                }
-                if let Some(side) = side {
-                    match side {
-                        DownloadSide::Both => {
-                            mod_meta.server_side = Some(true);
-                            mod_meta.client_side = Some(true);
-                        }
-                        DownloadSide::Server => {
-                            mod_meta.server_side = Some(true);
-                            mod_meta.client_side = Some(false);
-                        }
-                        DownloadSide::Client => {
-                            mod_meta.server_side = Some(false);
-                            mod_meta.client_side = Some(true);
+                let from_collection = batch_from_modrinth_collection.is_some();
+                let from_stdin = name.as_deref() == Some("-");
+                let mod_names = match (name, batch_from_modrinth_collection) {
+                    (Some(_), _) if from_stdin => {
+                        let mod_names = read_mod_names(std::io::stdin().lock())?;
+                        if mod_names.is_empty() {
+                            anyhow::bail!("No mod names were given on stdin")
                         }
+                        mod_names
                     }
-                }
-                for provider in providers.into_iter() {
-                    mod_meta = mod_meta.provider(provider);
-                }
-                modpack_meta = modpack_meta.add_mod(&mod_meta)?;
-                modpack_meta.save_current_dir_project()?;
-
-                let revert_modpack_meta = |e| -> ! {
-                    let revert_result = old_modpack_meta.save_current_dir_project();
-                    if let Err(result) = revert_result {
-                        panic!("Failed to revert modpack meta: {}", result);
+                    (Some(name), _) => vec![name],
+                    (None, Some(collection_id)) => {
+                        let mod_names = providers::modrinth::Modrinth::new()
+                            .get_collection_mods(&collection_id)
+                            .await?;
+                        println!(
+                            "Found {} mod(s) in Modrinth collection {collection_id}",
+                            mod_names.len()
+                        );
+                        mod_names
                     }
-                    panic!("Reverted modpack meta:\n{}", e);
+                    (None, None) => unreachable!("Either a mod name or a collection is required"),
                 };
 
-                match resolver::PinnedPackMeta::load_from_current_directory(!locked).await {
-                    Ok(mut modpack_lock) => {
-                        let remove_result =
-                            modpack_lock.remove_mod(&mod_meta.name, &modpack_meta, true);
-                        if let Err(e) = remove_result {
-                            revert_modpack_meta(e);
+                // Mods in a batch that can't be added are skipped rather than failing the rest
+                let batch = from_collection || from_stdin;
+                let mut mod_metas = vec![];
+                let mut skipped_mods = vec![];
+                for name in mod_names.iter() {
+                    let mut mod_meta = match ModMeta::new(name) {
+                        Ok(mod_meta) => mod_meta,
+                        Err(e) if batch => {
+                            skipped_mods.push((name.clone(), e));
+                            continue;
                         }
+                        Err(e) => return Err(e),
+                    };
+                    mod_meta.name = modpack_meta.canonical_mod_name(&mod_meta.name);
 
-                        let pin_result = modpack_lock
-                            .pin_mod_and_deps(&mod_meta, &modpack_meta, !locked)
-                            .await;
-                        if let Err(e) = pin_result {
-                            revert_modpack_meta(e);
-                        }
+                    if let Some(modloader) = &modloader {
+                        mod_meta = mod_meta.modloader(modloader.clone());
+                    }
 
-                        if let Err(e) = modpack_lock.save_current_dir_lock() {
-                            revert_modpack_meta(e);
+                    if let Some(mc_version) = &mc_version {
+                        mod_meta = mod_meta.mc_version(mc_version);
+                    }
+
+                    if let Some(url) = &url {
+                        mod_meta = mod_meta.url(url);
+                    }
+                    if let Some(side) = side {
+                        match side {
+                            DownloadSide::Both => {
+                                mod_meta.server_side = Some(true);
+                                mod_meta.client_side = Some(true);
+                            }
+                            DownloadSide::Server => {
+                                mod_meta.server_side = Some(true);
+                                mod_meta.client_side = Some(false);
+                            }
+                            DownloadSide::Client => {
+                                mod_meta.server_side = Some(false);
+                                mod_meta.client_side = Some(true);
+                            }
                         }
                     }
-                    Err(e) => {
-                        revert_modpack_meta(e);
+                    if client || server {
+                        mod_meta.client_side = Some(client);
+                        mod_meta.server_side = Some(server);
+                    }
+                    for provider in providers.iter() {
+                        mod_meta = mod_meta.provider(provider.clone());
+                    }
+                    mod_meta = mod_meta.include_prerelease(include_prerelease).kind(kind);
+                    for load_after in after.iter() {
+                        mod_meta =
+                            mod_meta.load_after(&modpack_meta.canonical_mod_name(load_after));
+                    }
+                    if let Some(note) = &note {
+                        mod_meta = mod_meta.notes(note);
+                    }
+                    if !from_collection && !modpack_meta.mods.contains_key(&mod_meta.name) {
+                        // If Modrinth can't be reached, resolving the mod reports the problem instead
+                        if let Ok(Some(suggestions)) =
+                            modpack_lock.suggest_mods(&mod_meta, &modpack_meta).await
+                        {
+                            // Stdin can't be prompted on once the mod names have been read from it
+                            let picked_name =
+                                pick_suggested_mod(&mod_meta.name, &suggestions, !from_stdin)?;
+                            mod_meta.name = modpack_meta.canonical_mod_name(&picked_name);
+                        }
                     }
-                };
-            }
-            Commands::Remove { name, force } => {
-                let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
-                let old_modpack_meta = modpack_meta.clone();
 
-                modpack_meta = modpack_meta.remove_mod(&name);
-                modpack_meta.save_current_dir_project()?;
+                    if batch || test_compat {
+                        let compatible = match modpack_meta.clone().add_mod(&mod_meta) {
+                            Ok(_) if test_compat => {
+                                modpack_lock
+                                    .check_compat(&mod_meta, &modpack_meta, dependency_versions)
+                                    .await
+                            }
+                            Ok(_) => modpack_lock
+                                .resolve_mod(&mod_meta, &modpack_meta)
+                                .await
+                                .map(|_| ()),
+                            Err(e) => Err(e),
+                        };
+                        match compatible {
+                            Err(e) if batch => {
+                                skipped_mods.push((name.clone(), e));
+                                continue;
+                            }
+                            Err(e) => {
+                                return Err(e.context(format!(
+                                    "Not adding {name}, since it isn't compatible with the pack"
+                                )))
+                            }
+                            Ok(()) => {}
+                        }
+                    }
+                    modpack_meta = modpack_meta.add_mod(&mod_meta)?;
+                    if let Some(group) = &group {
+                        modpack_meta.add_mod_to_group(group, &mod_meta.name);
+                    }
+                    mod_metas.push(mod_meta);
+                }
 
-                let revert_modpack_meta = |e| -> ! {
-                    let revert_result = old_modpack_meta.save_current_dir_project();
-                    if let Err(result) = revert_result {
-                        panic!("Failed to revert modpack meta: {}", result);
+                if !skipped_mods.is_empty() {
+                    println!(
+                        "Skipped {} mod(s) that couldn't be added to the pack:",
+                        skipped_mods.len()
+                    );
+                    for (name, e) in skipped_mods.iter() {
+                        println!("  {name}: {e}");
+                    }
+                    if mod_metas.is_empty() {
+                        anyhow::bail!("None of the mods could be added to the pack")
                     }
-                    panic!("Reverted modpack meta:\n{}", e);
+                }
+                // Some mods being skipped still adds the rest, but is reported as a partial failure
+                let add_result = if skipped_mods.is_empty() {
+                    Ok(())
+                } else {
+                    Err(ExitCodeError::partial_failure(format!(
+                        "{} of {} mods couldn't be added to the pack",
+                        skipped_mods.len(),
+                        mod_names.len()
+                    ))
+                    .into())
                 };
+                if no_install {
+                    modpack_meta.save_current_dir_project()?;
+                    println!("Run `mcmpmgr update` to pin the added mod(s)");
+                    return add_result;
+                }
 
-                match resolver::PinnedPackMeta::load_from_current_directory(true).await {
-                    Ok(mut modpack_lock) => {
-                        let remove_result = modpack_lock.remove_mod(&name, &modpack_meta, force);
-                        if let Err(e) = remove_result {
-                            revert_modpack_meta(e);
-                        }
+                let pin_result = async {
+                    for mod_meta in mod_metas.iter() {
+                        modpack_lock.remove_mod(&mod_meta.name, true, true)?;
+                        modpack_lock
+                            .pin_mod_and_deps(mod_meta, &modpack_meta, dependency_versions)
+                            .await?;
+                    }
+                    Ok::<(), Error>(())
+                }
+                .await;
+                if trace {
+                    print_trace(&modpack_lock)?;
+                }
+                pin_result?;
 
-                        if let Err(e) = modpack_lock.save_current_dir_lock() {
-                            revert_modpack_meta(e);
+                for mod_meta in mod_metas.iter() {
+                    modpack_meta.record_project_id(&mod_meta.name, &modpack_lock);
+                    if let Some(existing_name) =
+                        modpack_meta.find_same_project(&mod_meta.name, &modpack_lock)
+                    {
+                        println!(
+                            "Warning: Not adding {} since it is the same project as {existing_name}, which is already in the modpack",
+                            mod_meta.name
+                        );
+                        modpack_meta = modpack_meta.remove_mod(&mod_meta.name);
+                        modpack_lock.remove_mod(&mod_meta.name, true, true)?;
+                    }
+                }
+
+                if resolve_only {
+                    println!("Resolved pins (nothing was written):");
+                    for (name, pinned_mod) in modpack_lock.get_pinned_mods() {
+                        match old_pins.get(name) {
+                            Some(old_pin) if old_pin.version == pinned_mod.version => {}
+                            Some(old_pin) => println!(
+                                "  ~ {name}: {} -> {}",
+                                old_pin.version, pinned_mod.version
+                            ),
+                            None => println!("  + {name}@{}", pinned_mod.version),
                         }
                     }
-                    Err(e) => {
-                        revert_modpack_meta(e);
+                    for name in old_pins.keys() {
+                        if !modpack_lock.get_pinned_mods().contains_key(name) {
+                            println!("  - {name}");
+                        }
                     }
-                };
+                    return add_result;
+                }
+                transaction::save_pack_and_lock(
+                    &std::env::current_dir()?,
+                    &modpack_meta,
+                    &modpack_lock,
+                )?;
+                add_result?;
             }
-            Commands::Forbid { name } => {
+            Commands::Remove {
+                name,
+                force,
+                no_prune,
+                and_forbid,
+            } => {
                 let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
-                let old_modpack_meta = modpack_meta.clone();
-
-                modpack_meta.forbid_mod(&name);
+                let name = modpack_meta.canonical_mod_name(&name);
+                if and_forbid {
+                    modpack_meta.forbid_mod(&name);
+                }
                 modpack_meta = modpack_meta.remove_mod(&name);
-                modpack_meta.save_current_dir_project()?;
 
-                let revert_modpack_meta = |e| -> ! {
-                    let revert_result = old_modpack_meta.save_current_dir_project();
-                    if let Err(result) = revert_result {
-                        panic!("Failed to revert modpack meta: {}", result);
+                let mut modpack_lock = resolver::PinnedPackMeta::load_from_current_directory(
+                    DependencyVersions::Latest,
+                )
+                .await?;
+                modpack_lock.remove_mod(&name, force, !no_prune)?;
+                // Saved together, so the mod is never forbidden without also being removed
+                transaction::save_pack_and_lock(
+                    &std::env::current_dir()?,
+                    &modpack_meta,
+                    &modpack_lock,
+                )?;
+            }
+            Commands::Allow { name, remove } => {
+                let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
+                if remove {
+                    modpack_meta.disallow_mod(&name);
+                } else {
+                    modpack_meta.allow_mod(&name);
+                }
+                modpack_meta.save_current_dir_project()?;
+            }
+            Commands::PinDep {
+                name,
+                version,
+                remove,
+            } => {
+                let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
+                let version = match version {
+                    Some(version) if !remove => version,
+                    _ => {
+                        modpack_meta.remove_dependency_override(&name);
+                        modpack_meta.save_current_dir_project()?;
+                        return Ok(());
                     }
-                    panic!("Reverted modpack meta:\n{}", e);
                 };
+                modpack_meta.override_dependency(&name, &version);
 
-                match resolver::PinnedPackMeta::load_from_current_directory(true).await {
-                    Ok(mut modpack_lock) => {
-                        let remove_result = modpack_lock.remove_mod(&name, &modpack_meta, true);
-                        if let Err(e) = remove_result {
-                            revert_modpack_meta(e);
-                        }
-
-                        if let Err(e) = modpack_lock.save_current_dir_lock() {
-                            revert_modpack_meta(e);
+                // Re-pin the dependency straight away if it's already pinned at another version
+                let mut modpack_lock = resolver::PinnedPackMeta::load_from_current_directory(
+                    DependencyVersions::Latest,
+                )
+                .await?;
+                let pinned_version = modpack_lock
+                    .get_pinned_mods()
+                    .get(&name)
+                    .map(|pinned_mod| pinned_mod.version.clone());
+                if pinned_version.is_some_and(|pinned_version| pinned_version != version) {
+                    modpack_lock
+                        .pin_mod_and_deps(
+                            &ModMeta::new(&name)?.version(&version),
+                            &modpack_meta,
+                            DependencyVersions::Exact,
+                        )
+                        .await?;
+                }
+                transaction::save_pack_and_lock(
+                    &std::env::current_dir()?,
+                    &modpack_meta,
+                    &modpack_lock,
+                )?;
+            }
+            Commands::Note { name, note, clear } => {
+                let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
+                if note.is_some() || clear {
+                    modpack_meta.set_mod_notes(&name, note)?;
+                    modpack_meta.save_current_dir_project()?;
+                } else {
+                    match modpack_meta.mods.get(&name) {
+                        Some(mod_meta) => {
+                            println!("{}", mod_meta.notes.as_deref().unwrap_or_default())
                         }
+                        None => anyhow::bail!("Mod {name} is not in the modpack"),
                     }
-                    Err(e) => {
-                        revert_modpack_meta(e);
+                }
+            }
+            Commands::List => {
+                let modpack_meta = ModpackMeta::load_from_current_directory()?;
+                // Listing shouldn't pin the whole pack just to show source urls
+                let pack_lock = if Path::new(resolver::MODPACK_LOCK_FILENAME).is_file() {
+                    Some(
+                        resolver::PinnedPackMeta::load_from_current_directory(
+                            DependencyVersions::Latest,
+                        )
+                        .await?,
+                    )
+                } else {
+                    None
+                };
+                for mod_meta in modpack_meta.iter_mods() {
+                    println!("{}@{}", mod_meta.name, mod_meta.version);
+                    let source_url = pack_lock
+                        .as_ref()
+                        .and_then(|pack_lock| pack_lock.get_pinned_mods().get(&mod_meta.name))
+                        .and_then(|pinned_mod| pinned_mod.source_url.as_ref());
+                    if let Some(source_url) = source_url {
+                        println!("    Source: {source_url}");
+                    }
+                    if let Some(notes) = &mod_meta.notes {
+                        for line in notes.lines() {
+                            println!("    {line}");
+                        }
                     }
+                }
+            }
+            Commands::Open { name } => {
+                let modpack_meta = ModpackMeta::load_from_current_directory()?;
+                let pack_lock = resolver::PinnedPackMeta::load_from_current_directory(
+                    DependencyVersions::Latest,
+                )
+                .await?;
+                let name = modpack_meta.canonical_mod_name(&name);
+                let Some(pinned_mod) = pack_lock.get_pinned_mods().get(&name) else {
+                    anyhow::bail!("Mod {name} is not pinned")
                 };
+                let Some(source_url) = &pinned_mod.source_url else {
+                    anyhow::bail!(
+                        "No source url is pinned for {name}. Run `mcmpmgr update` to pin one if its provider has it"
+                    )
+                };
+                println!("Opening {source_url}");
+                open::that(source_url).with_context(|| format!("Failed to open {source_url}"))?;
+            }
+            Commands::Forbid { name } => {
+                let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
+                let name = modpack_meta.canonical_mod_name(&name);
+                modpack_meta.forbid_mod(&name);
+                modpack_meta = modpack_meta.remove_mod(&name);
+
+                let mut modpack_lock = resolver::PinnedPackMeta::load_from_current_directory(
+                    DependencyVersions::Latest,
+                )
+                .await?;
+                modpack_lock.remove_mod(&name, true, true)?;
+                transaction::save_pack_and_lock(
+                    &std::env::current_dir()?,
+                    &modpack_meta,
+                    &modpack_lock,
+                )?;
+            }
+            Commands::ImportCurseforge { mods_dir } => {
+                let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
+                let mut modpack_lock = resolver::PinnedPackMeta::load_from_current_directory(
+                    DependencyVersions::Latest,
+                )
+                .await?;
+                let imported_mods = modpack_lock
+                    .pin_curseforge_files(
+                        &mods_dir,
+                        &modpack_meta,
+                        &providers::curseforge::CurseForge::new(),
+                    )
+                    .await?;
+                if imported_mods.is_empty() {
+                    anyhow::bail!(
+                        "None of the mods in {} could be imported",
+                        mods_dir.display()
+                    )
+                }
+                for mod_meta in imported_mods.iter() {
+                    modpack_meta = modpack_meta.add_mod(mod_meta)?;
+                }
+                transaction::save_pack_and_lock(
+                    &std::env::current_dir()?,
+                    &modpack_meta,
+                    &modpack_lock,
+                )?;
+                println!("Imported {} mods from CurseForge", imported_mods.len());
             }
             Commands::Download {
                 mods_dir,
                 side,
                 git,
                 path,
+                bundle,
+                include_optional,
+                group,
+                mirror,
+                prune,
+                no_prune,
+                only,
             } => {
                 let mut pack_dir: Option<tempfile::TempDir> = None;
-                let pack_lock = if let Some(git_url) = git {
-                    let (lock_meta, repo_dir) =
-                        resolver::PinnedPackMeta::load_from_git_repo(&git_url, true).await?;
+                let download_cache = resolver::DownloadCache::from_config_dir();
+                let (mut pack_lock, modpack_meta) = if let Some(bundle) = bundle {
+                    let bundle_dir = tempfile::tempdir()?;
+                    export::extract_bundle(&bundle, bundle_dir.path(), &download_cache)?;
+                    let pack_lock = resolver::PinnedPackMeta::load_from_directory(
+                        bundle_dir.path(),
+                        DependencyVersions::Latest,
+                    )
+                    .await?;
+                    let modpack_meta = ModpackMeta::load_from_directory(bundle_dir.path())?;
+                    // Hold on to the extracted bundle until pack_dir is dropped
+                    let _ = pack_dir.insert(bundle_dir);
+                    (pack_lock, modpack_meta)
+                } else if let Some(git_url) = git {
+                    let (lock_meta, repo_dir) = resolver::PinnedPackMeta::load_from_git_repo(
+                        &git_url,
+                        DependencyVersions::Latest,
+                    )
+                    .await?;
+                    let modpack_meta = ModpackMeta::load_from_directory(repo_dir.path())?;
                     // Hold on to the repo directory until pack_dir is dropped
                     let _ = pack_dir.insert(repo_dir);
-                    lock_meta
+                    (lock_meta, modpack_meta)
                 } else if let Some(local_path) = path {
-                    resolver::PinnedPackMeta::load_from_directory(&local_path, true).await?
+                    (
+                        resolver::PinnedPackMeta::load_from_directory(
+                            &local_path,
+                            DependencyVersions::Latest,
+                        )
+                        .await?,
+                        ModpackMeta::load_from_directory(&local_path)?,
+                    )
                 } else {
-                    resolver::PinnedPackMeta::load_from_current_directory(true).await?
+                    (
+                        resolver::PinnedPackMeta::load_from_current_directory(
+                            DependencyVersions::Latest,
+                        )
+                        .await?,
+                        ModpackMeta::load_from_current_directory()?,
+                    )
                 };
+                pack_lock.select_groups(&modpack_meta, &group)?;
+                if !only.is_empty() {
+                    pack_lock.select_only(&modpack_meta, &only)?;
+                }
+                if let Some(signing_key) = &modpack_meta.signing_key {
+                    pack_lock.set_signing_key(signing_key)?;
+                }
+                if let Some(mirror) = mirror {
+                    pack_lock.set_mirror(&mirror)?;
+                }
+                if modpack_meta.prefix_load_order {
+                    pack_lock.prefix_load_order()?;
+                }
+                let prune = (prune || modpack_meta.manage_mods_exclusively) && !no_prune;
+                pack_lock.set_keep_unpinned(!prune);
 
-                pack_lock.download_mods(&mods_dir, side).await?;
+                pack_lock
+                    .download_mods_with_cache(
+                        &mods_dir,
+                        side,
+                        include_optional,
+                        &download_cache,
+                        None,
+                    )
+                    .await?;
                 println!("Mods updated");
             }
-            Commands::Update { locked } => {
-                let mut pack_lock = resolver::PinnedPackMeta::new();
+            Commands::Update {
+                deps,
+                locked,
+                trace,
+                upgrade_deps_only,
+                changelog,
+                oldest,
+            } => {
+                let dependency_versions = resolve_dependency_versions(deps, locked);
                 let modpack_meta = ModpackMeta::load_from_current_directory()?;
-                pack_lock.init(&modpack_meta, !locked).await?;
+                // The lock from before updating, to get the changelogs of mods that are updated
+                let previous_lock = if changelog.is_some()
+                    && std::env::current_dir()?
+                        .join(resolver::MODPACK_LOCK_FILENAME)
+                        .exists()
+                {
+                    Some(
+                        resolver::PinnedPackMeta::load_from_current_directory(dependency_versions)
+                            .await?,
+                    )
+                } else {
+                    None
+                };
+                let mut pack_lock = if upgrade_deps_only {
+                    resolver::PinnedPackMeta::load_from_current_directory(dependency_versions)
+                        .await?
+                } else {
+                    resolver::PinnedPackMeta::new()
+                };
+                if trace {
+                    pack_lock.enable_trace();
+                }
+                if oldest {
+                    pack_lock.set_version_selection(VersionSelection::Oldest);
+                }
+                let init_result = if upgrade_deps_only {
+                    pack_lock
+                        .upgrade_deps(&modpack_meta, dependency_versions)
+                        .await
+                } else {
+                    pack_lock.init(&modpack_meta, dependency_versions).await
+                };
+                if trace {
+                    print_trace(&pack_lock)?;
+                }
+                init_result?;
                 pack_lock.save_current_dir_lock()?;
+                if let Some(changelog_path) = changelog {
+                    let version_changes = previous_lock
+                        .map(|previous_lock| pack_lock.get_version_changes(&previous_lock))
+                        .unwrap_or_default();
+                    let changelogs = pack_lock
+                        .get_changelogs(&modpack_meta, version_changes)
+                        .await;
+                    output_changelog(&changelogs, changelog_path)?;
+                }
+            }
+            Commands::Status => {
+                let modpack_meta = ModpackMeta::load_from_current_directory()?;
+                // A missing lock is reported rather than resolved, since this shouldn't change anything
+                let pack_lock = if std::env::current_dir()?
+                    .join(resolver::MODPACK_LOCK_FILENAME)
+                    .exists()
+                {
+                    resolver::PinnedPackMeta::load_from_current_directory(
+                        DependencyVersions::Latest,
+                    )
+                    .await?
+                } else {
+                    resolver::PinnedPackMeta::new()
+                };
+                let drift = pack_lock.get_drift(&modpack_meta);
+                if !drift.is_empty() {
+                    for drifted_mod in drift.iter() {
+                        println!("{drifted_mod}");
+                    }
+                    anyhow::bail!(
+                        "{} is out of date with {}. Run `mcmpmgr update` to pin the modpack's mods",
+                        resolver::MODPACK_LOCK_FILENAME,
+                        modpack::MODPACK_FILENAME
+                    )
+                }
+                println!(
+                    "{} is up to date with {}",
+                    resolver::MODPACK_LOCK_FILENAME,
+                    modpack::MODPACK_FILENAME
+                );
+            }
+            Commands::Outdated { json, changelog } => {
+                let modpack_meta = ModpackMeta::load_from_current_directory()?;
+                let pack_lock = resolver::PinnedPackMeta::load_from_current_directory(
+                    DependencyVersions::Latest,
+                )
+                .await?;
+                let outdated_mods = pack_lock.get_outdated_mods(&modpack_meta).await;
+
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&outdated_mods)?);
+                } else {
+                    let name_width = outdated_mods
+                        .iter()
+                        .map(|m| m.name.len())
+                        .chain(["Mod".len()])
+                        .max()
+                        .unwrap_or_default();
+                    let version_width = outdated_mods
+                        .iter()
+                        .map(|m| m.current_version.len())
+                        .chain(["Current".len()])
+                        .max()
+                        .unwrap_or_default();
+                    let update_width = outdated_mods
+                        .iter()
+                        .map(|m| m.update_version.as_deref().unwrap_or("Unknown").len())
+                        .chain(["Update".len()])
+                        .max()
+                        .unwrap_or_default();
+                    println!(
+                        "  {:name_width$}  {:version_width$}  {:update_width$}  Latest",
+                        "Mod", "Current", "Update"
+                    );
+                    for outdated_mod in outdated_mods.iter() {
+                        println!(
+                            "{} {:name_width$}  {:version_width$}  {:update_width$}  {}",
+                            if outdated_mod.outdated { "*" } else { " " },
+                            outdated_mod.name,
+                            outdated_mod.current_version,
+                            outdated_mod.update_version.as_deref().unwrap_or("Unknown"),
+                            outdated_mod.latest_version.as_deref().unwrap_or("Unknown")
+                        );
+                    }
+                    let outdated_count = outdated_mods.iter().filter(|m| m.outdated).count();
+                    println!("{outdated_count} mod(s) marked with * would change on update");
+                    for outdated_mod in outdated_mods.iter() {
+                        if let Some(prerelease_version) = &outdated_mod.prerelease_version {
+                            println!(
+                                "Warning: {} has a newer prerelease {prerelease_version}, which won't be pinned unless it is added with --include-prerelease",
+                                outdated_mod.name
+                            );
+                        }
+                    }
+                }
+                if let Some(changelog_path) = changelog {
+                    let version_changes = outdated_mods
+                        .into_iter()
+                        .filter(|m| m.outdated)
+                        .filter_map(|m| Some((m.name, m.current_version, m.update_version?)))
+                        .collect();
+                    let changelogs = pack_lock
+                        .get_changelogs(&modpack_meta, version_changes)
+                        .await;
+                    output_changelog(&changelogs, changelog_path)?;
+                }
+            }
+            Commands::Lock { normalize: _ } => {
+                if !Path::new(resolver::MODPACK_LOCK_FILENAME).is_file() {
+                    anyhow::bail!(
+                        "There is no {} in the current directory to normalize",
+                        resolver::MODPACK_LOCK_FILENAME
+                    )
+                }
+                let mut modpack_lock = resolver::PinnedPackMeta::load_from_current_directory(
+                    DependencyVersions::Latest,
+                )
+                .await?;
+                for fix in modpack_lock.normalize() {
+                    println!("{fix}");
+                }
+                modpack_lock.save_current_dir_lock()?;
+                println!("Normalized {}", resolver::MODPACK_LOCK_FILENAME);
+            }
+            Commands::VerifyLock { json, repair } => {
+                let mut pack_lock = resolver::PinnedPackMeta::load_from_current_directory(
+                    DependencyVersions::Latest,
+                )
+                .await?;
+                let url_statuses = pack_lock.verify_urls().await;
+
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&url_statuses)?);
+                } else {
+                    for url_status in url_statuses.iter() {
+                        if url_status.dead {
+                            println!(
+                                "Dead: {} ({}) {}",
+                                url_status.name,
+                                url_status.status.unwrap_or_default(),
+                                url_status.url
+                            );
+                        } else if let Some(e) = &url_status.error {
+                            println!("Unreachable: {} {}: {e}", url_status.name, url_status.url);
+                        }
+                    }
+                }
+                let mut dead_count = url_statuses.iter().filter(|s| s.dead).count();
+                if repair && dead_count > 0 {
+                    let modpack_meta = ModpackMeta::load_from_current_directory()?;
+                    let repaired_mods = pack_lock
+                        .repair_dead_urls(&modpack_meta, &url_statuses)
+                        .await;
+                    if !repaired_mods.is_empty() {
+                        pack_lock.save_current_dir_lock()?;
+                    }
+                    dead_count -= url_statuses
+                        .iter()
+                        .filter(|s| s.dead && repaired_mods.contains(&s.name))
+                        .count();
+                }
+                if dead_count > 0 {
+                    anyhow::bail!(
+                        "{dead_count} of {} download urls are dead",
+                        url_statuses.len()
+                    );
+                }
+                let unreachable_count = url_statuses.iter().filter(|s| s.error.is_some()).count();
+                if !json {
+                    println!(
+                        "{} download urls are live, {unreachable_count} could not be checked",
+                        url_statuses.len() - unreachable_count
+                    );
+                }
+            }
+            Commands::Export {
+                output,
+                format,
+                server_installer,
+                pack_version,
+            } => {
+                let modpack_meta = ModpackMeta::load_from_current_directory()?;
+                let output = output.unwrap_or(PathBuf::from(match format {
+                    export::ExportFormat::ServerPack => {
+                        format!("{}-server.zip", modpack_meta.pack_name)
+                    }
+                    export::ExportFormat::Bundle => {
+                        format!("{}-bundle.tar.zst", modpack_meta.pack_name)
+                    }
+                    export::ExportFormat::Mrpack => format!("{}.mrpack", modpack_meta.pack_name),
+                }));
+                match format {
+                    export::ExportFormat::ServerPack => {
+                        export::export_server_pack(
+                            &std::env::current_dir()?,
+                            &output,
+                            server_installer,
+                        )
+                        .await?;
+                    }
+                    export::ExportFormat::Bundle => {
+                        export::export_bundle(&std::env::current_dir()?, &output).await?;
+                    }
+                    export::ExportFormat::Mrpack => {
+                        export::export_mrpack(&std::env::current_dir()?, &output, &pack_version)
+                            .await?;
+                    }
+                }
+            }
+            Commands::Watch {
+                instance_directory,
+                side,
+                include_optional,
+                symlink,
+            } => {
+                let pack_dir = std::env::current_dir()?;
+                let mut profile = Profile::new(
+                    &instance_directory,
+                    PackSource::Local {
+                        path: pack_dir.clone(),
+                    },
+                    side,
+                )?;
+                profile.include_optional = include_optional;
+                profile.symlink_files = symlink;
+                watch::watch_pack(&pack_dir, &profile).await?;
             }
             Commands::File(FileArgs { command }) => {
                 if let Some(command) = command {
@@ -442,6 +1761,9 @@ async fn main() -> anyhow::Result<()> {
                             target_path,
                             side,
                             apply_policy,
+                            templated,
+                            variant,
+                            priority,
                         } => {
                             let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
                             let current_dir = &std::env::current_dir()?;
@@ -454,20 +1776,128 @@ async fn main() -> anyhow::Result<()> {
                                 target_path,
                                 side,
                                 apply_policy,
+                                sha512: None,
+                                manifest: None,
+                                templated,
+                                variants: variant,
+                                priority,
                             };
 
                             modpack_meta.add_file(&local_path, &file_meta, current_dir)?;
                             modpack_meta.save_current_dir_project()?;
                         }
                         FileCommands::Show { local_path } => todo!(),
-                        FileCommands::Remove { local_path } => {
+                        FileCommands::Remove {
+                            local_path,
+                            target_path,
+                        } => {
                             let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
-                            modpack_meta.remove_file(&local_path, &std::env::current_dir()?)?;
+                            modpack_meta.remove_file(
+                                &local_path,
+                                &std::env::current_dir()?,
+                                target_path.as_deref(),
+                            )?;
                             modpack_meta.save_current_dir_project()?;
                         }
                     }
                 }
             }
+            #[cfg(feature = "gui")]
+            Commands::Gui => unreachable!("The GUI is launched before the async runtime starts"),
+            Commands::Completions { shell } => {
+                let mut command = Cli::command();
+                let bin_name = command.get_name().to_string();
+                clap_complete::generate(shell, &mut command, &bin_name, &mut std::io::stdout());
+                if let Some(dynamic_completions) =
+                    completions::dynamic_completions(shell, &bin_name)
+                {
+                    print!("{dynamic_completions}");
+                }
+            }
+            Commands::Complete { values } => {
+                for value in completions::dynamic_values(values) {
+                    println!("{value}");
+                }
+            }
+            Commands::SelfUpdate => {
+                // Being offline isn't worth reporting, since the check is only a convenience
+                match self_update::check_for_update().await {
+                    Ok(Some(update)) => println!(
+                        "mcmpmgr {} is available (current version: {}). Download it from {}",
+                        update.version,
+                        env!("CARGO_PKG_VERSION"),
+                        update.url
+                    ),
+                    Ok(None) => println!("mcmpmgr {} is up to date", env!("CARGO_PKG_VERSION")),
+                    Err(_) => {}
+                }
+            }
+            Commands::Config { json } => {
+                let config_info = ConfigInfo::resolve()?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&config_info)?);
+                } else {
+                    print!("{}", toml::to_string(&config_info)?);
+                }
+            }
+            Commands::Prune {
+                cache: _,
+                packs,
+                keep_days,
+            } => {
+                let mut pack_locks = vec![];
+                if packs.is_empty() {
+                    let userdata = profiles::Data::load()?;
+                    let install_cache = profiles::InstallCache::default();
+                    for profile_name in userdata.get_profile_names() {
+                        let profile = userdata
+                            .get_profile(&profile_name)
+                            .expect("Profile should exist");
+                        // Pruning without every lock could delete files a profile still needs
+                        let pack_lock = profile.load_pack_lock(&install_cache).await.with_context(|| {
+                            format!("Failed to load the pack of profile {profile_name}. Pass --pack to choose which packs to keep files for")
+                        })?;
+                        pack_locks.push(pack_lock);
+                    }
+                } else {
+                    for pack_dir in packs {
+                        pack_locks.push(
+                            resolver::PinnedPackMeta::load_from_directory(
+                                &pack_dir,
+                                DependencyVersions::Latest,
+                            )
+                            .await?,
+                        );
+                    }
+                }
+                let summary =
+                    resolver::DownloadCache::from_config_dir().prune(&pack_locks, keep_days)?;
+                println!(
+                    "Removed {} cached files, reclaiming {:.1} MiB",
+                    summary.removed_files,
+                    summary.reclaimed_bytes as f64 / (1024.0 * 1024.0)
+                );
+            }
+            Commands::Providers(ProvidersArgs { command }) => {
+                let mut modpack_meta = ModpackMeta::load_from_current_directory()?;
+                match command.unwrap_or(ProvidersCommands::List) {
+                    ProvidersCommands::List => {
+                        for provider in modpack_meta.default_providers.iter() {
+                            println!("{provider}");
+                        }
+                    }
+                    ProvidersCommands::Add { provider } => {
+                        modpack_meta = modpack_meta.provider(provider.clone());
+                        modpack_meta.save_current_dir_project()?;
+                        println!("{provider} is a default provider of the modpack");
+                    }
+                    ProvidersCommands::Remove { provider } => {
+                        modpack_meta.remove_provider(&provider)?;
+                        modpack_meta.save_current_dir_project()?;
+                        println!("{provider} is no longer a default provider of the modpack");
+                    }
+                }
+            }
             Commands::Profile(ProfileArgs { command }) => {
                 if let Some(command) = command {
                     match command {
@@ -483,26 +1913,167 @@ async fn main() -> anyhow::Result<()> {
                             side,
                             pack_source,
                             instance_directory,
+                            include_optional,
+                            symlink,
+                            group,
+                            mod_side,
+                            mods_dir_name,
+                            var,
+                            prune_empty_dirs,
+                            variant,
                         } => {
                             let mut userdata = profiles::Data::load()?;
-                            let profile = Profile::new(&instance_directory, pack_source, side)?;
+                            let mut profile = Profile::new(&instance_directory, pack_source, side)?;
+                            profile.include_optional = include_optional;
+                            profile.symlink_files = symlink;
+                            profile.groups = group;
+                            profile.mod_side_overrides =
+                                mod_side.into_iter().map(|o| (o.mod_name, o.side)).collect();
+                            profile.mods_dir_name = mods_dir_name;
+                            profile.prune_empty_dirs = prune_empty_dirs;
+                            profile.variant = variant;
+                            profile.variables =
+                                var.into_iter().map(|v| (v.name, v.value)).collect();
                             userdata.add_profile(&name, profile);
                             userdata.save()?;
                             println!("Saved profile '{name}'");
                         }
-                        ProfileCommands::Install { name } => {
-                            let userdata = profiles::Data::load()?;
-                            let profile = userdata.get_profile(&name);
-
-                            let profile = if let Some(profile) = profile {
-                                profile
+                        ProfileCommands::Install {
+                            names,
+                            all,
+                            parallel_profiles,
+                            dry_run,
+                            only,
+                        } => {
+                            let mut userdata = profiles::Data::load()?;
+                            let names = if all {
+                                userdata.get_profile_names()
                             } else {
-                                anyhow::bail!("Profile '{name}' does not exist")
+                                names
                             };
+                            for name in names.iter() {
+                                if let Some(profile) = userdata.get_profile_mut(name) {
+                                    profile.only_mods = only.clone();
+                                }
+                            }
+
+                            if dry_run {
+                                let install_cache = profiles::InstallCache::default();
+                                for name in names.iter() {
+                                    let Some(profile) = userdata.get_profile(name) else {
+                                        anyhow::bail!("Profile '{name}' does not exist")
+                                    };
+                                    let preview = profile.preview_install(&install_cache).await?;
+                                    println!(
+                                        "Installing profile '{name}' would make these changes:"
+                                    );
+                                    println!("{preview}");
+                                }
+                                return Ok(());
+                            }
 
-                            println!("Installing profile '{name}'...");
-                            profile.install().await?;
-                            println!("Installed profile '{name}' successfully");
+                            let results =
+                                userdata.install_profiles(&names, parallel_profiles).await;
+                            let failed_count = results.values().filter(|r| r.is_err()).count();
+                            if results.len() == 1 {
+                                for (name, result) in results.into_iter() {
+                                    result?;
+                                    println!("Installed profile '{name}' successfully");
+                                }
+                            } else {
+                                println!("Profile install summary:");
+                                for (name, result) in results.iter() {
+                                    match result {
+                                        Ok(_) => println!("- {name}: Installed"),
+                                        Err(e) => println!("- {name}: Failed: {e}"),
+                                    }
+                                }
+                                if failed_count > 0 && failed_count == results.len() {
+                                    anyhow::bail!("All profiles failed to install")
+                                } else if failed_count > 0 {
+                                    return Err(ExitCodeError::partial_failure(format!(
+                                        "{failed_count} of {} profiles failed to install",
+                                        results.len()
+                                    ))
+                                    .into());
+                                }
+                            }
+                        }
+                        ProfileCommands::CheckUpdates { names, all } => {
+                            let userdata = profiles::Data::load()?;
+                            let names = if all {
+                                userdata.get_profile_names()
+                            } else {
+                                names
+                            };
+                            let install_cache = profiles::InstallCache::default();
+                            let mut failed_count = 0;
+                            for name in names.iter() {
+                                let Some(profile) = userdata.get_profile(name) else {
+                                    anyhow::bail!("Profile '{name}' does not exist")
+                                };
+                                let updates = match profile.check_updates(&install_cache).await {
+                                    Ok(updates) => updates,
+                                    Err(e) => {
+                                        println!(
+                                            "Profile '{name}': Failed to check for updates: {e}"
+                                        );
+                                        failed_count += 1;
+                                        continue;
+                                    }
+                                };
+                                println!("Profile '{name}':");
+                                match updates.new_commits {
+                                    Some(profiles::NewPackCommits::Count(0)) => {
+                                        println!("  Pack is up to date with its git repo")
+                                    }
+                                    Some(profiles::NewPackCommits::Count(count)) => {
+                                        println!("  Pack has {count} new commit(s) since it was installed")
+                                    }
+                                    Some(profiles::NewPackCommits::Diverged) => println!(
+                                        "  Pack's git history has changed since it was installed"
+                                    ),
+                                    Some(profiles::NewPackCommits::Unknown) => {
+                                        println!("  Pack hasn't been installed from git yet")
+                                    }
+                                    None => {}
+                                }
+                                if updates.outdated_mods.is_empty() {
+                                    println!("  Mods are up to date");
+                                } else {
+                                    println!(
+                                        "  {} mod(s) have updates:",
+                                        updates.outdated_mods.len()
+                                    );
+                                    for outdated_mod in updates.outdated_mods.iter() {
+                                        println!(
+                                            "    {} {} -> {}",
+                                            outdated_mod.name,
+                                            outdated_mod.current_version,
+                                            outdated_mod
+                                                .update_version
+                                                .as_deref()
+                                                .unwrap_or("Unknown")
+                                        );
+                                    }
+                                }
+                            }
+                            if failed_count > 0 {
+                                return Err(ExitCodeError::partial_failure(format!(
+                                    "{failed_count} of {} profiles couldn't be checked for updates",
+                                    names.len()
+                                ))
+                                .into());
+                            }
+                        }
+                        ProfileCommands::Copy {
+                            source,
+                            destination,
+                        } => {
+                            let mut userdata = profiles::Data::load()?;
+                            userdata.copy_profile(&source, &destination)?;
+                            userdata.save()?;
+                            println!("Copied profile '{source}' to '{destination}'");
                         }
                         ProfileCommands::Remove { name } => {
                             let mut userdata = profiles::Data::load()?;
@@ -525,6 +2096,20 @@ async fn main() -> anyhow::Result<()> {
                             println!("Instance folder   : {}", profile.instance_folder.display());
                             println!("Modpack source    : {}", profile.pack_source);
                             println!("Side              : {}", profile.side);
+                            println!("Include optional  : {}", profile.include_optional);
+                            println!("Symlink files     : {}", profile.symlink_files);
+                            println!("Prune empty dirs  : {}", profile.prune_empty_dirs);
+                            println!("Mods directory    : {}", profile.mods_dir().display());
+                            println!("Mod groups        : {}", profile.groups.join(", "));
+                            if let Some(variant) = &profile.variant {
+                                println!("Variant           : {variant}");
+                            }
+                            for (mod_name, side) in profile.mod_side_overrides.iter() {
+                                println!("Mod side override : {mod_name}={side}");
+                            }
+                            for (name, value) in profile.variables.iter() {
+                                println!("Variable          : {name}={value}");
+                            }
                         }
                     }
                 }
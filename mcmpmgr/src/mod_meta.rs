@@ -1,6 +1,6 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::{borrow::BorrowMut, error::Error};
+use std::{borrow::BorrowMut, collections::BTreeSet};
 
 use crate::modpack::ModLoader;
 
@@ -27,7 +27,51 @@ impl std::str::FromStr for ModProvider {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+impl std::fmt::Display for ModProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModProvider::CurseForge => write!(f, "CurseForge"),
+            ModProvider::Modrinth => write!(f, "Modrinth"),
+            ModProvider::Raw => write!(f, "Raw"),
+        }
+    }
+}
+
+/// The kind of project a mod entry is
+#[derive(
+    Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord,
+)]
+pub enum ProjectKind {
+    #[default]
+    Mod,
+    ResourcePack,
+    ShaderPack,
+    DataPack,
+}
+
+impl ProjectKind {
+    fn is_mod(&self) -> bool {
+        *self == Self::Mod
+    }
+}
+
+impl std::str::FromStr for ProjectKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mod" => Ok(Self::Mod),
+            "resourcepack" => Ok(Self::ResourcePack),
+            "shaderpack" => Ok(Self::ShaderPack),
+            "datapack" => Ok(Self::DataPack),
+            _ => anyhow::bail!(
+                "Invalid project kind: {s}. Expected one of: mod, resourcepack, shaderpack, datapack"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModMeta {
     pub name: String,
     pub version: String,
@@ -37,6 +81,24 @@ pub struct ModMeta {
     pub download_url: Option<String>,
     pub server_side: Option<bool>,
     pub client_side: Option<bool>,
+    /// Stable provider project id, used to look the mod up instead of its name if set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    /// Allow beta and alpha versions to be pinned even when a release is available
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub include_prerelease: bool,
+    /// Kind of project, used to pick the pack's default providers for it
+    #[serde(default, skip_serializing_if = "ProjectKind::is_mod")]
+    pub kind: ProjectKind,
+    /// Notes about the mod, eg. why it is in the pack or pinned at its version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Mods this mod has to be loaded after, for packs that set `prefix_load_order`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub load_after: BTreeSet<String>,
+    /// Fields mcmpmgr doesn't know about (eg. from a newer version), kept so they survive being rewritten
+    #[serde(flatten)]
+    pub extra: toml::Table,
 }
 
 impl PartialEq for ModMeta {
@@ -46,6 +108,14 @@ impl PartialEq for ModMeta {
     }
 }
 
+impl std::hash::Hash for ModMeta {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Consistent with PartialEq
+        self.name.hash(state);
+        self.version.hash(state);
+    }
+}
+
 impl PartialOrd for ModMeta {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         self.name.partial_cmp(&other.name)
@@ -109,6 +179,36 @@ impl ModMeta {
         self.mc_version = Some(mc_version.into());
         self
     }
+
+    pub fn project_id(mut self, project_id: &str) -> Self {
+        self.project_id = Some(project_id.into());
+        self
+    }
+
+    pub fn include_prerelease(mut self, include_prerelease: bool) -> Self {
+        self.include_prerelease = include_prerelease;
+        self
+    }
+
+    pub fn kind(mut self, kind: ProjectKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn notes(mut self, notes: &str) -> Self {
+        self.notes = Some(notes.into());
+        self
+    }
+
+    pub fn load_after(mut self, mod_name: &str) -> Self {
+        self.load_after.insert(mod_name.into());
+        self
+    }
+
+    /// Get the identifier providers should look this mod up by
+    pub fn project_ref(&self) -> &str {
+        self.project_id.as_deref().unwrap_or(&self.name)
+    }
 }
 
 impl Default for ModMeta {
@@ -122,6 +222,58 @@ impl Default for ModMeta {
             loader: None,
             server_side: None,
             client_side: None,
+            project_id: None,
+            include_prerelease: false,
+            kind: ProjectKind::Mod,
+            notes: None,
+            load_after: Default::default(),
+            extra: Default::default(),
         }
     }
 }
+
+#[test]
+fn test_project_ref() {
+    let mod_meta = ModMeta::new("sodium").unwrap();
+    assert_eq!(mod_meta.project_ref(), "sodium");
+    assert!(!toml::to_string(&mod_meta).unwrap().contains("project_id"));
+
+    let mod_meta = mod_meta.project_id("AANobbMI");
+    assert_eq!(mod_meta.project_ref(), "AANobbMI");
+    assert_eq!(mod_meta.name, "sodium");
+}
+
+#[test]
+fn test_mod_provider_round_trip() {
+    // Fails to compile when a provider is added, so that it gets added to this test too
+    let all_providers = |provider: &ModProvider| match provider {
+        ModProvider::CurseForge | ModProvider::Modrinth | ModProvider::Raw => (),
+    };
+    let providers = [
+        ModProvider::CurseForge,
+        ModProvider::Modrinth,
+        ModProvider::Raw,
+    ];
+
+    #[derive(Serialize, Deserialize)]
+    struct Providers {
+        providers: Vec<ModProvider>,
+    }
+    for provider in providers.iter() {
+        all_providers(provider);
+        assert_eq!(
+            provider.to_string().parse::<ModProvider>().unwrap(),
+            *provider
+        );
+    }
+    let serialized = toml::to_string(&Providers {
+        providers: providers.to_vec(),
+    })
+    .unwrap();
+    assert_eq!(
+        serialized,
+        "providers = [\"CurseForge\", \"Modrinth\", \"Raw\"]\n"
+    );
+    let deserialized: Providers = toml::from_str(&serialized).unwrap();
+    assert_eq!(deserialized.providers, providers);
+}
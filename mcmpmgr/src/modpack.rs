@@ -1,8 +1,13 @@
 use crate::{
+    error::McmpmgrError,
     file_merge,
-    file_meta::{get_normalized_relative_path, FileApplyPolicy, FileMeta},
-    mod_meta::{ModMeta, ModProvider},
+    file_meta::{
+        get_normalized_relative_path, substitute_variables, FileApplyPolicy, FileMeta, FileTargets,
+    },
+    mod_meta::{ModMeta, ModProvider, ProjectKind},
     providers::DownloadSide,
+    resolver::PinnedPackMeta,
+    transaction::write_atomic,
 };
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
@@ -13,7 +18,7 @@ use std::{
     str::FromStr,
 };
 
-const MODPACK_FILENAME: &str = "modpack.toml";
+pub(crate) const MODPACK_FILENAME: &str = "modpack.toml";
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ModLoader {
@@ -35,30 +40,171 @@ impl std::str::FromStr for ModLoader {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "Fabric" => Ok(Self::Fabric),
-            "Forge" => Ok(Self::Forge),
-            _ => anyhow::bail!("Invalid mod launcher: {}", s),
+        match s.trim().to_ascii_lowercase().as_str() {
+            "fabric" => Ok(Self::Fabric),
+            "forge" | "minecraftforge" => Ok(Self::Forge),
+            _ => anyhow::bail!(
+                "Invalid mod launcher: {}. Expected one of: fabric, forge",
+                s
+            ),
         }
     }
 }
 
+/// Which of the versions of a mod that support the pack gets pinned
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum VersionSelection {
+    /// The newest compatible version
+    #[default]
+    Newest,
+    /// The oldest compatible version, eg. for servers that would rather avoid bleeding-edge bugs
+    Oldest,
+}
+
+impl VersionSelection {
+    fn is_newest(&self) -> bool {
+        *self == Self::Newest
+    }
+}
+
+/// What applying a pack file/folder would do to the instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChangeAction {
+    Create,
+    Overwrite,
+    Merge,
+}
+
+impl std::fmt::Display for FileChangeAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Create => write!(f, "Create"),
+            Self::Overwrite => write!(f, "Overwrite"),
+            Self::Merge => write!(f, "Merge into"),
+        }
+    }
+}
+
+/// A change that applying a pack file/folder would make to the instance
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    /// Path of the file/folder relative to the instance directory
+    pub target_path: String,
+    pub action: FileChangeAction,
+}
+
+/// Replaces a mod with a different mod on certain minecraft versions
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModOverride {
+    /// Name of the mod to replace
+    pub mod_name: String,
+    /// Minecraft versions to replace the mod on as a version requirement. Eg. ">=1.21"
+    pub mc_versions: String,
+    /// The mod to use instead
+    pub replacement: ModMeta,
+}
+
+impl ModOverride {
+    /// Whether this override applies to a mod on a minecraft version
+    pub fn matches(&self, mod_name: &str, mc_version: &str) -> bool {
+        if self.mod_name != mod_name {
+            return false;
+        }
+        let Ok(version_req) = semver::VersionReq::parse(&self.mc_versions) else {
+            eprintln!(
+                "Ignoring override for {} with invalid minecraft versions '{}'",
+                self.mod_name, self.mc_versions
+            );
+            return false;
+        };
+        parse_mc_version(mc_version).is_some_and(|mc_version| version_req.matches(&mc_version))
+    }
+}
+
+fn manage_mods_exclusively() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+/// Whether a mod version is `*` or a version number, rather than eg. a leftover `name@version`
+fn is_valid_version_constraint(version: &str) -> bool {
+    !version.is_empty() && !version.contains(|c: char| c.is_whitespace() || c == '@')
+}
+
+/// Parse a minecraft version as a semver version. Eg. 1.21 -> 1.21.0
+fn parse_mc_version(mc_version: &str) -> Option<semver::Version> {
+    let mc_version = if mc_version.matches('.').count() == 1 {
+        format!("{mc_version}.0")
+    } else {
+        mc_version.into()
+    };
+    semver::Version::parse(&mc_version).ok()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ModpackMeta {
     /// The name of the modpack
     pub pack_name: String,
     /// The intended minecraft version on which this pack should run
     pub mc_version: String,
+    /// Other minecraft versions mods may list instead of `mc_version`, eg. `1.20` for a `1.20.1` pack
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accepted_mc_versions: Vec<String>,
     /// The default modloader for the modpack
     pub modloader: ModLoader,
+    /// The modloader version the pack needs. The latest stable version is pinned in the lock if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loader_version: Option<String>,
+    /// If set, every pinned mod (including dependencies) must list exactly `mc_version` as supported
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict_mc_version: bool,
+    /// Whether mods without a version constraint are pinned at their newest or oldest compatible version
+    #[serde(default, skip_serializing_if = "VersionSelection::is_newest")]
+    pub version_selection: VersionSelection,
+    /// Prefix the filenames of mods with load order hints when they are installed, for packs that depend on jar ordering
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub prefix_load_order: bool,
+    /// Whether installing the pack deletes files in the mods folder that aren't pinned. Unset for packs players add their own mods to
+    #[serde(default = "manage_mods_exclusively", skip_serializing_if = "is_true")]
+    pub manage_mods_exclusively: bool,
     /// Map of mod name -> mod metadata
     pub mods: BTreeMap<String, ModMeta>,
-    /// Mapping of relative paths to files to copy over from the modpack
-    pub files: Option<BTreeMap<String, FileMeta>>,
+    /// Mapping of relative paths to files to copy over from the modpack -> where to copy them to
+    pub files: Option<BTreeMap<String, FileTargets>>,
     /// Default provider for newly added mods in the modpack
     pub default_providers: Vec<ModProvider>,
+    /// Default providers for particular kinds of projects, used instead of `default_providers` for those kinds
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub kind_default_providers: BTreeMap<ProjectKind, Vec<ModProvider>>,
     /// A set of forbidden mods in the modpack
     pub forbidden_mods: BTreeSet<String>,
+    /// If set, only these mods (including dependencies) may be added to the modpack
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_mods: Option<BTreeSet<String>>,
+    /// The minimum version of mcmpmgr required to use this modpack
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_mcmpmgr_version: Option<String>,
+    /// Mods to swap for other mods on certain minecraft versions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<ModOverride>,
+    /// Map of group name -> mods in the group. Grouped mods are only installed when one of their groups is enabled
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, BTreeSet<String>>,
+    /// Map of variant name -> groups the variant installs. Profiles installing a variant also only get the files tagged for it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variants: BTreeMap<String, BTreeSet<String>>,
+    /// Minisign public key that every downloaded mod file is verified with. Files without a signature in the lock are rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<String>,
+    /// Map of mod name -> version to pin it at whenever it is pulled in as a dependency
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependency_overrides: BTreeMap<String, String>,
+    /// Fields mcmpmgr doesn't know about (eg. from a newer version), kept so they survive being rewritten
+    #[serde(flatten)]
+    pub extra: toml::Table,
 }
 
 impl ModpackMeta {
@@ -78,13 +224,130 @@ impl ModpackMeta {
     pub fn load_from_directory(directory: &Path) -> Result<Self> {
         let modpack_meta_file_path = directory.join(PathBuf::from(MODPACK_FILENAME));
         if !modpack_meta_file_path.exists() {
-            anyhow::bail!(
+            return Err(McmpmgrError::InvalidPack(format!(
                 "Directory '{}' does not seem to be a valid modpack project directory.",
                 directory.display()
-            )
+            ))
+            .into());
         };
         let modpack_contents = std::fs::read_to_string(modpack_meta_file_path)?;
-        Ok(toml::from_str(&modpack_contents)?)
+        let modpack_meta: Self = toml::from_str(&modpack_contents)?;
+        modpack_meta.check_mcmpmgr_version(env!("CARGO_PKG_VERSION"))?;
+        modpack_meta.validate()?;
+        Ok(modpack_meta)
+    }
+
+    /// Check a hand-edited modpack for mistakes that would otherwise only fail once mods are resolved
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = vec![];
+        let mut mod_names: BTreeMap<String, &str> = BTreeMap::new();
+        for (mod_name, mod_meta) in self.mods.iter() {
+            if mod_meta.name != *mod_name {
+                problems.push(format!(
+                    "Mod {mod_name} has a different name ({}) to its entry",
+                    mod_meta.name
+                ));
+            }
+            // Mod names are looked up case insensitively, so these would be the same mod
+            if let Some(other_name) = mod_names.insert(mod_name.to_lowercase(), mod_name) {
+                problems.push(format!("Mods {other_name} and {mod_name} are duplicates"));
+            }
+            if !is_valid_version_constraint(&mod_meta.version) {
+                problems.push(format!(
+                    "Mod {mod_name} has an invalid version '{}'. Expected '*' or a version number",
+                    mod_meta.version
+                ));
+            }
+            if self.forbidden_mods.contains(mod_name) {
+                problems.push(format!(
+                    "Mod {mod_name} is in the modpack, but is forbidden"
+                ));
+            }
+            let providers = mod_meta
+                .providers
+                .as_deref()
+                .unwrap_or(self.default_providers_for(mod_meta.kind));
+            if providers == [ModProvider::Raw] && mod_meta.download_url.is_none() {
+                problems.push(format!(
+                    "Mod {mod_name} can only use the Raw provider, but has no download_url"
+                ));
+            }
+        }
+        if self.default_providers.is_empty() {
+            problems.push("The modpack has no default providers".into());
+        }
+        for (mod_name, version) in self.dependency_overrides.iter() {
+            if !is_valid_version_constraint(version) {
+                problems.push(format!(
+                    "Dependency {mod_name} is pinned at an invalid version '{version}'"
+                ));
+            }
+        }
+        for (variant, groups) in self.variants.iter() {
+            for group in groups
+                .iter()
+                .filter(|group| !self.groups.contains_key(*group))
+            {
+                problems.push(format!(
+                    "Variant {variant} installs group {group}, which doesn't exist"
+                ));
+            }
+        }
+        for variant in self
+            .files
+            .iter()
+            .flat_map(|files| files.values())
+            .flat_map(FileTargets::iter)
+            .flat_map(|file_meta| file_meta.variants.iter())
+            .filter(|variant| !self.variants.contains_key(*variant))
+            .collect::<BTreeSet<_>>()
+        {
+            problems.push(format!(
+                "Files are tagged for variant {variant}, which doesn't exist"
+            ));
+        }
+        if let Some(signing_key) = &self.signing_key {
+            if minisign_verify::PublicKey::from_base64(signing_key.trim()).is_err() {
+                problems.push(format!(
+                    "Signing key '{signing_key}' isn't a valid minisign public key"
+                ));
+            }
+        }
+        for mod_override in self.overrides.iter() {
+            if semver::VersionReq::parse(&mod_override.mc_versions).is_err() {
+                problems.push(format!(
+                    "Override for {} has invalid minecraft versions '{}'. Expected a version requirement, eg. '>=1.21'",
+                    mod_override.mod_name, mod_override.mc_versions
+                ));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(McmpmgrError::InvalidPack(format!(
+                "{MODPACK_FILENAME} is invalid:\n- {}",
+                problems.join("\n- ")
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Ensure the given mcmpmgr version is new enough to use this modpack
+    pub fn check_mcmpmgr_version(&self, mcmpmgr_version: &str) -> Result<()> {
+        if let Some(min_version) = &self.min_mcmpmgr_version {
+            let min_version = semver::Version::parse(min_version).with_context(|| {
+                format!("Invalid min_mcmpmgr_version '{min_version}' in {MODPACK_FILENAME}")
+            })?;
+            let current_version = semver::Version::parse(mcmpmgr_version)?;
+            if current_version < min_version {
+                anyhow::bail!(
+                    "Modpack '{}' requires mcmpmgr {} or newer, but this is mcmpmgr {}. Please update mcmpmgr",
+                    self.pack_name,
+                    min_version,
+                    current_version
+                )
+            }
+        }
+        Ok(())
     }
 
     pub fn load_from_current_directory() -> Result<Self> {
@@ -98,16 +361,200 @@ impl ModpackMeta {
         self
     }
 
+    /// Remove a provider from the default providers. At least one default provider has to be kept
+    pub fn remove_provider(&mut self, provider: &ModProvider) -> Result<()> {
+        if !self.default_providers.contains(provider) {
+            anyhow::bail!("{provider} is not one of the modpack's default providers");
+        }
+        if self.default_providers.len() == 1 {
+            anyhow::bail!(
+                "Cannot remove {provider}, since the modpack needs at least one default provider"
+            );
+        }
+        self.default_providers.retain(|p| p != provider);
+        Ok(())
+    }
+
+    /// Get the default providers to try for a kind of project
+    pub fn default_providers_for(&self, kind: ProjectKind) -> &[ModProvider] {
+        self.kind_default_providers
+            .get(&kind)
+            .unwrap_or(&self.default_providers)
+    }
+
+    pub fn loader_version(mut self, loader_version: &str) -> Self {
+        self.loader_version = Some(loader_version.into());
+        self
+    }
+
+    pub fn accept_mc_version(mut self, mc_version: &str) -> Self {
+        if !self.accepted_mc_versions.iter().any(|v| v == mc_version) {
+            self.accepted_mc_versions.push(mc_version.into());
+        }
+        self
+    }
+
+    /// Get the normalized minecraft versions a mod may list to be accepted for the pack, most preferred first
+    ///
+    /// A mod's own minecraft version override replaces the pack's versions
+    pub fn game_versions(&self, game_version_override: Option<&str>) -> Vec<String> {
+        if let Some(game_version) = game_version_override {
+            return vec![normalize_mc_version(game_version)];
+        }
+        let mut game_versions: Vec<String> = vec![];
+        for game_version in std::iter::once(&self.mc_version).chain(&self.accepted_mc_versions) {
+            let game_version = normalize_mc_version(game_version);
+            if !game_versions.contains(&game_version) {
+                game_versions.push(game_version);
+            }
+        }
+        game_versions
+    }
+
+    pub fn strict_mc_version(mut self, strict_mc_version: bool) -> Self {
+        self.strict_mc_version = strict_mc_version;
+        self
+    }
+
     pub fn add_mod(mut self, mod_meta: &ModMeta) -> Result<Self> {
         if self.forbidden_mods.contains(&mod_meta.name) {
             anyhow::bail!("Cannot add forbidden mod {} to modpack", mod_meta.name)
+        } else if !self.is_mod_allowed(&mod_meta.name) {
+            anyhow::bail!(
+                "Cannot add mod {} to modpack since it is not in the allowed mods",
+                mod_meta.name
+            )
         } else {
-            self.mods
-                .insert(mod_meta.name.to_string(), mod_meta.clone());
+            let mut mod_meta = mod_meta.clone();
+            // Re-adding a mod shouldn't lose the notes kept about it
+            if mod_meta.notes.is_none() {
+                mod_meta.notes = self
+                    .mods
+                    .get(&mod_meta.name)
+                    .and_then(|old_mod_meta| old_mod_meta.notes.clone());
+            }
+            self.mods.insert(mod_meta.name.to_string(), mod_meta);
         }
         Ok(self)
     }
 
+    /// Set or clear the notes about a mod in the modpack
+    pub fn set_mod_notes(&mut self, mod_name: &str, notes: Option<String>) -> Result<()> {
+        let Some(mod_meta) = self.mods.get_mut(mod_name) else {
+            anyhow::bail!("Mod {mod_name} is not in the modpack")
+        };
+        mod_meta.notes = notes;
+        Ok(())
+    }
+
+    /// Force a dependency to be pinned at a version, regardless of the version its dependents ask for
+    pub fn override_dependency(&mut self, mod_name: &str, version: &str) {
+        self.dependency_overrides
+            .insert(mod_name.into(), version.into());
+        println!(
+            "Dependency {} will be pinned at version {}",
+            mod_name, version
+        );
+    }
+
+    pub fn remove_dependency_override(&mut self, mod_name: &str) {
+        self.dependency_overrides.remove(mod_name);
+        println!("Dependency {} is no longer overridden", mod_name);
+    }
+
+    /// Swap a dependency's version for its override, if it has one.
+    /// A warning is printed if this replaces a specific version the dependent mod requires
+    pub fn apply_dependency_override(&self, dep: ModMeta, dependent: &str) -> ModMeta {
+        match self.dependency_overrides.get(&dep.name) {
+            Some(version) if *version != dep.version => {
+                if dep.version != "*" {
+                    eprintln!(
+                        "Warning: {} requires {}@{}, but it is overridden to version {}",
+                        dependent, dep.name, dep.version, version
+                    );
+                }
+                dep.version(version)
+            }
+            _ => dep,
+        }
+    }
+
+    /// Get the mod that should be used in place of a mod, if it is overridden for its minecraft version
+    pub fn get_override(&self, mod_meta: &ModMeta) -> Option<&ModMeta> {
+        let mc_version = mod_meta.mc_version.as_ref().unwrap_or(&self.mc_version);
+        self.overrides
+            .iter()
+            .find(|o| o.matches(&mod_meta.name, mc_version))
+            .map(|o| &o.replacement)
+    }
+
+    /// Record the project id a mod was pinned from, so it can still be found if it is renamed upstream
+    pub fn record_project_id(&mut self, mod_name: &str, pack_lock: &PinnedPackMeta) {
+        let project_id = pack_lock
+            .get_pinned_mods()
+            .get(mod_name)
+            .and_then(|pinned_mod| pinned_mod.project_id.clone());
+        if let Some(mod_meta) = self.mods.get_mut(mod_name) {
+            if mod_meta.project_id.is_none() {
+                mod_meta.project_id = project_id;
+            }
+        }
+    }
+
+    /// Get the name to add a mod to the modpack under. Mod names are case insensitive like Modrinth slugs,
+    /// so the name of a mod already in the modpack is reused, and any other name is lowercased
+    pub fn canonical_mod_name(&self, mod_name: &str) -> String {
+        self.mods
+            .keys()
+            .find(|existing_name| existing_name.eq_ignore_ascii_case(mod_name))
+            .cloned()
+            .unwrap_or_else(|| mod_name.to_lowercase())
+    }
+
+    /// Find another mod in the modpack pinned from the same project as a mod, eg. if the mod was added under an alias
+    pub fn find_same_project(&self, mod_name: &str, pack_lock: &PinnedPackMeta) -> Option<&str> {
+        let project_id = |name: &str| {
+            self.mods
+                .get(name)
+                .and_then(|mod_meta| mod_meta.project_id.clone())
+                .or_else(|| {
+                    pack_lock
+                        .get_pinned_mods()
+                        .get(name)
+                        .and_then(|pinned_mod| pinned_mod.project_id.clone())
+                })
+        };
+        let mod_project_id = project_id(mod_name)?;
+        self.mods
+            .keys()
+            .filter(|name| *name != mod_name)
+            .find(|name| project_id(name).as_ref() == Some(&mod_project_id))
+            .map(String::as_str)
+    }
+
+    /// Check whether a mod passes the allowlist. Every mod is allowed if there is no allowlist
+    pub fn is_mod_allowed(&self, mod_name: &str) -> bool {
+        self.allowed_mods
+            .as_ref()
+            .is_none_or(|allowed_mods| allowed_mods.contains(mod_name))
+    }
+
+    /// Add a mod to the allowlist, creating the allowlist if there isn't one yet
+    pub fn allow_mod(&mut self, mod_name: &str) {
+        self.allowed_mods
+            .get_or_insert_with(Default::default)
+            .insert(mod_name.into());
+        println!("Mod {} has been allowed in the modpack", mod_name);
+    }
+
+    /// Remove a mod from the allowlist
+    pub fn disallow_mod(&mut self, mod_name: &str) {
+        if let Some(allowed_mods) = &mut self.allowed_mods {
+            allowed_mods.remove(mod_name);
+        }
+        println!("Mod {} is no longer allowed in the modpack", mod_name);
+    }
+
     pub fn forbid_mod(&mut self, mod_name: &str) {
         self.forbidden_mods.insert(mod_name.into());
         println!("Mod {} has been forbidden from the modpack", mod_name);
@@ -115,9 +562,70 @@ impl ModpackMeta {
 
     pub fn remove_mod(mut self, mod_name: &str) -> Self {
         self.mods.remove(mod_name);
+        for group_mods in self.groups.values_mut() {
+            group_mods.remove(mod_name);
+        }
+        self.groups.retain(|_, group_mods| !group_mods.is_empty());
         self
     }
 
+    /// Add a mod to a group, creating the group if it doesn't exist yet
+    pub fn add_mod_to_group(&mut self, group_name: &str, mod_name: &str) {
+        self.groups
+            .entry(group_name.into())
+            .or_default()
+            .insert(mod_name.into());
+        println!("Mod {} has been added to group {}", mod_name, group_name);
+    }
+
+    /// Get the grouped mods that shouldn't be installed when only `enabled_groups` are enabled
+    pub fn disabled_group_mods(&self, enabled_groups: &[String]) -> Result<BTreeSet<String>> {
+        for group_name in enabled_groups.iter() {
+            if !self.groups.contains_key(group_name) {
+                anyhow::bail!(
+                    "Modpack '{}' has no group named '{}'",
+                    self.pack_name,
+                    group_name
+                )
+            }
+        }
+        let enabled_mods: BTreeSet<&String> = enabled_groups
+            .iter()
+            .flat_map(|group_name| self.groups[group_name].iter())
+            .collect();
+        Ok(self
+            .groups
+            .values()
+            .flatten()
+            .filter(|mod_name| !enabled_mods.contains(mod_name))
+            .cloned()
+            .collect())
+    }
+
+    /// Get the groups a variant of the pack installs
+    pub fn variant_groups(&self, variant: &str) -> Result<&BTreeSet<String>> {
+        self.variants.get(variant).ok_or(anyhow::format_err!(
+            "Modpack '{}' has no variant named '{}'",
+            self.pack_name,
+            variant
+        ))
+    }
+
+    /// Only keep the file targets that apply to a variant of the pack, or to no variant in particular
+    pub fn select_variant(&mut self, variant: Option<&str>) {
+        let Some(files) = &mut self.files else {
+            return;
+        };
+        for file_targets in files.values_mut() {
+            file_targets.0.retain(|file_meta| {
+                file_meta.variants.is_empty()
+                    || variant
+                        .is_some_and(|variant| file_meta.variants.iter().any(|v| v == variant))
+            });
+        }
+        files.retain(|_, file_targets| !file_targets.0.is_empty());
+    }
+
     /// Add local files or folders to the pack. These should be committed to version control
     pub fn add_file(
         &mut self,
@@ -150,15 +658,19 @@ impl ModpackMeta {
             );
         }
 
-        match &mut self.files {
-            Some(files) => {
-                files.insert(relative_path.clone(), file_meta.clone());
-            }
-            None => {
-                self.files
-                    .insert(BTreeMap::new())
-                    .insert(relative_path.clone(), file_meta.clone());
-            }
+        let mut file_meta = file_meta.clone();
+        file_meta.update_hashes(&full_path)?;
+
+        let file_targets = self
+            .files
+            .get_or_insert_with(BTreeMap::new)
+            .entry(relative_path.clone())
+            .or_default();
+        file_targets.add(file_meta.clone());
+        // Every target is applied from the same file/folder, so they share its hashes
+        for target in file_targets.0.iter_mut() {
+            target.sha512 = file_meta.sha512.clone();
+            target.manifest = file_meta.manifest.clone();
         }
 
         println!(
@@ -169,16 +681,34 @@ impl ModpackMeta {
         Ok(self)
     }
 
-    pub fn remove_file(&mut self, file_path: &PathBuf, pack_root: &Path) -> Result<&mut Self> {
+    /// Remove a file/folder from the pack. Only the target at `target_path` is removed if given
+    pub fn remove_file(
+        &mut self,
+        file_path: &PathBuf,
+        pack_root: &Path,
+        target_path: Option<&str>,
+    ) -> Result<&mut Self> {
         let relative_path = get_normalized_relative_path(&file_path, pack_root)?;
-        if let Some(files) = &mut self.files {
-            let removed = files.remove(&relative_path);
-            if let Some(removed) = removed {
-                println!(
-                    "Removed file '{relative_path}' -> '{}' from modpack...",
-                    removed.target_path
-                );
-            }
+        let Some(files) = &mut self.files else {
+            return Ok(self);
+        };
+        let Some(file_targets) = files.get_mut(&relative_path) else {
+            return Ok(self);
+        };
+        let (removed, kept): (Vec<FileMeta>, Vec<FileMeta>) = std::mem::take(&mut file_targets.0)
+            .into_iter()
+            .partition(|file_meta| {
+                target_path.is_none_or(|target_path| file_meta.target_path == target_path)
+            });
+        file_targets.0 = kept;
+        if file_targets.0.is_empty() {
+            files.remove(&relative_path);
+        }
+        for removed in removed.iter() {
+            println!(
+                "Removed file '{relative_path}' -> '{}' from modpack...",
+                removed.target_path
+            );
         }
         Ok(self)
     }
@@ -192,89 +722,222 @@ impl ModpackMeta {
     /// Ie. If a folder is being applied, any files in that folder not in the modpack will be removed
     ///
     /// Both merge policies will recursively copy files/folders from the src into the destination, while performing merges instead of file copies.
+    ///
+    /// If `symlink` is set, files/folders using the `Always` or `Once` policies are symlinked to the pack instead of copied,
+    /// so that changes to the pack apply to the instance immediately.
+    ///
+    /// Templated files/folders are never symlinked. Their `${VAR}` placeholders are replaced using `variables`
+    /// (or environment variables) before they are written or merged into the instance.
+    ///
+    /// If `prune_empty_dirs` is set, empty directories left inside the pack's folders are removed after they are applied.
+    ///
+    /// Files/Folders are applied in order of priority, then path in the pack, so higher priority targets override lower priority ones
     pub fn install_files(
         &self,
         pack_dir: &Path,
         instance_dir: &Path,
         side: DownloadSide,
+        symlink: bool,
+        variables: &BTreeMap<String, String>,
+        prune_empty_dirs: bool,
     ) -> Result<()> {
         println!(
             "Applying modpack files: {} -> {}...",
             pack_dir.display(),
             instance_dir.display()
         );
-        if let Some(files) = &self.files {
-            for (rel_path, file_meta) in files {
-                let source_path = pack_dir.join(rel_path);
-                let target_path = instance_dir.join(&file_meta.target_path);
-                if !side.contains(file_meta.side) {
-                    println!(
-                        "Skipping apply of {} -> {}. (Applies for side={}, current side={})",
-                        source_path.display(),
-                        target_path.display(),
-                        file_meta.side.to_string(),
-                        side.to_string()
-                    );
-                    continue;
-                }
-                if target_path.exists() && file_meta.apply_policy == FileApplyPolicy::Once {
-                    println!(
-                        "Skipping apply of {} -> {}. (Already applied once)",
+        for (rel_path, file_meta) in self.files_in_apply_order() {
+            let source_path = pack_dir.join(rel_path);
+            let target_path = instance_dir.join(&file_meta.target_path);
+            if !side.contains(file_meta.side) {
+                println!(
+                    "Skipping apply of {} -> {}. (Applies for side={}, current side={})",
+                    source_path.display(),
+                    target_path.display(),
+                    file_meta.side,
+                    side
+                );
+                continue;
+            }
+            if target_path.exists() && file_meta.apply_policy == FileApplyPolicy::Once {
+                println!(
+                    "Skipping apply of {} -> {}. (Already applied once)",
+                    source_path.display(),
+                    target_path.display(),
+                );
+                continue;
+            }
+
+            // Otherwise, this file/folder needs to be applied
+            if symlink
+                && !file_meta.templated
+                && (file_meta.apply_policy == FileApplyPolicy::Always
+                    || file_meta.apply_policy == FileApplyPolicy::Once)
+            {
+                match Self::symlink_files(&source_path, &target_path) {
+                    Ok(()) => continue,
+                    Err(e) => eprintln!(
+                        "Failed to symlink {} -> {}. Copying instead: {e}",
                         source_path.display(),
-                        target_path.display(),
-                    );
-                    continue;
+                        target_path.display()
+                    ),
                 }
+            }
+            if target_path.is_symlink() {
+                // Never copy or merge through a link back into the pack
+                remove_path(&target_path)?;
+            }
 
-                // Otherwise, this file/folder needs to be applied
-                if source_path.is_dir() {
-                    // Sync a folder
-                    if target_path.exists() {
-                        if file_meta.apply_policy == FileApplyPolicy::Always
-                            || file_meta.apply_policy == FileApplyPolicy::Once
-                        {
-                            println!(
-                                "Syncing and overwriting existing directory {} -> {}",
-                                source_path.display(),
-                                target_path.display(),
-                            );
-                            std::fs::remove_dir_all(&target_path)?;
-                        } else {
-                            println!(
-                                "Merging existing directory {} -> {} (policy={})",
-                                source_path.display(),
-                                target_path.display(),
-                                file_meta.apply_policy
-                            );
-                        }
+            file_meta.verify_hashes(&source_path)?;
+            if source_path.is_dir() {
+                // Sync a folder
+                if target_path.exists() {
+                    if file_meta.apply_policy == FileApplyPolicy::Always
+                        || file_meta.apply_policy == FileApplyPolicy::Once
+                    {
+                        println!(
+                            "Syncing and overwriting existing directory {} -> {}",
+                            source_path.display(),
+                            target_path.display(),
+                        );
+                        std::fs::remove_dir_all(&target_path)?;
+                    } else {
+                        println!(
+                            "Merging existing directory {} -> {} (policy={})",
+                            source_path.display(),
+                            target_path.display(),
+                            file_meta.apply_policy
+                        );
                     }
                 }
-                self.copy_files(&source_path, &target_path, file_meta.apply_policy.clone())?;
+            }
+            let variables = file_meta.templated.then_some(variables);
+            self.copy_files(
+                &source_path,
+                &target_path,
+                file_meta.apply_policy.clone(),
+                variables,
+            )?;
+            if prune_empty_dirs && source_path.is_dir() {
+                remove_empty_dirs(&target_path)?;
             }
         }
         Ok(())
     }
 
-    fn copy_files(&self, src: &Path, dst: &Path, apply_policy: FileApplyPolicy) -> Result<()> {
+    /// Every file/folder target paired with its path in the pack, in the order they are applied
+    ///
+    /// Targets are sorted by priority, then by path in the pack, so higher priority targets are applied last and win
+    fn files_in_apply_order(&self) -> Vec<(&String, &FileMeta)> {
+        let Some(files) = &self.files else {
+            return vec![];
+        };
+        let mut files: Vec<(&String, &FileMeta)> = files
+            .iter()
+            .flat_map(|(rel_path, file_targets)| {
+                file_targets
+                    .iter()
+                    .map(move |file_meta| (rel_path, file_meta))
+            })
+            .collect();
+        // The sort is stable, so targets with the same priority stay in path order
+        files.sort_by_key(|(_, file_meta)| file_meta.priority);
+        files
+    }
+
+    /// Work out what `install_files` would do to each pack file/folder, without changing anything
+    ///
+    /// Files/Folders that would be skipped are left out
+    pub fn plan_file_changes(&self, instance_dir: &Path, side: DownloadSide) -> Vec<FileChange> {
+        self.files_in_apply_order()
+            .into_iter()
+            .map(|(_, file_meta)| file_meta)
+            .filter(|file_meta| side.contains(file_meta.side))
+            .filter_map(|file_meta| {
+                let target_path = instance_dir.join(&file_meta.target_path);
+                let action = match (target_path.exists(), &file_meta.apply_policy) {
+                    (false, _) => FileChangeAction::Create,
+                    (true, FileApplyPolicy::Once) => return None,
+                    (true, FileApplyPolicy::Always) => FileChangeAction::Overwrite,
+                    (true, FileApplyPolicy::MergeRetain | FileApplyPolicy::MergeOverwrite) => {
+                        FileChangeAction::Merge
+                    }
+                };
+                Some(FileChange {
+                    target_path: file_meta.target_path.clone(),
+                    action,
+                })
+            })
+            .collect()
+    }
+
+    /// Replace `dst` with a symlink to `src`
+    fn symlink_files(src: &Path, dst: &Path) -> Result<()> {
+        let src = src.canonicalize()?;
+        if dst.is_symlink() && std::fs::read_link(dst)? == src {
+            println!("Already symlinked {} -> {}", src.display(), dst.display());
+            return Ok(());
+        }
+        if dst.exists() || dst.is_symlink() {
+            remove_path(dst)?;
+        }
+        if let Some(parent_dir) = dst.parent() {
+            std::fs::create_dir_all(parent_dir)?;
+        }
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&src, dst)?;
+        #[cfg(windows)]
+        if src.is_dir() {
+            std::os::windows::fs::symlink_dir(&src, dst)?;
+        } else {
+            std::os::windows::fs::symlink_file(&src, dst)?;
+        }
+
+        println!("Symlinked {} -> {}", src.display(), dst.display());
+        Ok(())
+    }
+
+    /// Copy or merge `src` into `dst`, substituting template placeholders in each file if `variables` is given
+    fn copy_files(
+        &self,
+        src: &Path,
+        dst: &Path,
+        apply_policy: FileApplyPolicy,
+        variables: Option<&BTreeMap<String, String>>,
+    ) -> Result<()> {
         if src.is_dir() {
             std::fs::create_dir_all(dst)?;
             for entry in std::fs::read_dir(src)? {
                 let entry = entry?;
                 let src_path = entry.path();
                 let dst_path = dst.join(entry.file_name());
-                self.copy_files(&src_path, &dst_path, apply_policy.clone())?;
+                self.copy_files(&src_path, &dst_path, apply_policy.clone(), variables)?;
             }
         } else {
             let parent_dir = dst.parent();
             if let Some(parent_dir) = parent_dir {
                 std::fs::create_dir_all(parent_dir)?;
             }
+            if let Some(variables) = variables {
+                if apply_policy == FileApplyPolicy::Always || apply_policy == FileApplyPolicy::Once
+                {
+                    println!("Rendering template {} -> {}", src.display(), dst.display());
+                    let contents = std::fs::read_to_string(src)
+                        .with_context(|| format!("Failed to read template {src:?}"))?;
+                    std::fs::write(dst, substitute_variables(&contents, variables))?;
+                    return Ok(());
+                }
+            }
             if apply_policy == FileApplyPolicy::Always || apply_policy == FileApplyPolicy::Once {
                 println!("Syncing file {} -> {}", src.display(), dst.display());
                 std::fs::copy(src, dst)?;
             } else {
                 // Merging files
-                let src_val = std::fs::read_to_string(src)?;
+                let mut src_val = std::fs::read_to_string(src)?;
+                if let Some(variables) = variables {
+                    src_val = substitute_variables(&src_val, variables);
+                }
                 let dst_val = if dst.exists() {
                     Some(std::fs::read_to_string(dst)?)
                 } else {
@@ -315,7 +978,7 @@ impl ModpackMeta {
                     }
                 } else {
                     println!("Syncing file {} -> {}", src.display(), dst.display());
-                    std::fs::copy(src, dst)?;
+                    std::fs::write(dst, &src_val)?;
                 }
             }
         }
@@ -337,15 +1000,36 @@ impl ModpackMeta {
         Ok(())
     }
 
-    pub fn save_to_file(&self, path: &PathBuf) -> Result<()> {
-        std::fs::write(
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        let existing_contents = std::fs::read_to_string(path).ok();
+        write_atomic(
             path,
-            toml::to_string(self).expect("MC Modpack Meta should be serializable"),
+            self.to_toml_string(existing_contents.as_deref())?
+                .as_bytes(),
         )?;
         // println!("Saved modpack metadata to {}", path.display());
         Ok(())
     }
 
+    /// Serialize the modpack metadata, updating `existing_contents` (the current contents of the file) if given
+    ///
+    /// Only the keys that changed are rewritten, so comments, ordering and formatting of hand edited files are kept
+    pub fn to_toml_string(&self, existing_contents: Option<&str>) -> Result<String> {
+        let contents = toml::to_string(self).expect("MC Modpack Meta should be serializable");
+        let Some(mut document) =
+            existing_contents.and_then(|existing| existing.parse::<toml_edit::DocumentMut>().ok())
+        else {
+            return Ok(contents);
+        };
+        let new_document: toml_edit::DocumentMut = contents.parse()?;
+        update_toml_table(document.as_table_mut(), new_document.as_table());
+        Ok(document.to_string())
+    }
+
+    pub fn save_to_dir(&self, dir: &Path) -> Result<()> {
+        self.save_to_file(&dir.join(PathBuf::from(MODPACK_FILENAME)))
+    }
+
     pub fn save_current_dir_project(&self) -> Result<()> {
         let modpack_meta_file_path = std::env::current_dir()?.join(PathBuf::from(MODPACK_FILENAME));
         self.save_to_file(&modpack_meta_file_path)?;
@@ -353,16 +1037,725 @@ impl ModpackMeta {
     }
 }
 
+/// Update a toml table to match `new`, leaving keys that are unchanged as they are
+fn update_toml_table(existing: &mut dyn toml_edit::TableLike, new: &dyn toml_edit::TableLike) {
+    let removed_keys: Vec<String> = existing
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key))
+        .collect();
+    for key in removed_keys.iter() {
+        existing.remove(key);
+    }
+    for (key, new_item) in new.iter() {
+        match existing.get_mut(key) {
+            Some(existing_item) => update_toml_item(existing_item, new_item.clone()),
+            None => {
+                existing.insert(key, without_toml_positions(new_item.clone()));
+            }
+        }
+    }
+}
+
+fn update_toml_item(existing: &mut toml_edit::Item, new: toml_edit::Item) {
+    if let (Some(existing_table), Some(new_table)) =
+        (existing.as_table_like_mut(), new.as_table_like())
+    {
+        update_toml_table(existing_table, new_table);
+        return;
+    }
+    if let (Some(existing_tables), Some(new_tables)) =
+        (existing.as_array_of_tables_mut(), new.as_array_of_tables())
+    {
+        if existing_tables.len() == new_tables.len() {
+            for (existing_table, new_table) in existing_tables.iter_mut().zip(new_tables.iter()) {
+                update_toml_table(existing_table, new_table);
+            }
+            return;
+        }
+    }
+    match (existing, new) {
+        (toml_edit::Item::Value(existing_value), toml_edit::Item::Value(new_value)) => {
+            if toml_value(existing_value) != toml_value(&new_value) {
+                let decor = existing_value.decor().clone();
+                *existing_value = new_value;
+                *existing_value.decor_mut() = decor;
+            }
+        }
+        (existing, new) => *existing = without_toml_positions(new),
+    }
+}
+
+/// Parse a toml_edit value, so that values can be compared regardless of formatting
+fn toml_value(value: &toml_edit::Value) -> Option<toml::Value> {
+    toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()?
+        .remove("value")
+}
+
+/// Copy an item from another document without its tables' positions in that document,
+/// so that the tables are written after their siblings instead
+fn without_toml_positions(item: toml_edit::Item) -> toml_edit::Item {
+    match item {
+        toml_edit::Item::Table(table) => toml_edit::Item::Table(table_without_position(table)),
+        toml_edit::Item::ArrayOfTables(tables) => {
+            toml_edit::Item::ArrayOfTables(tables.into_iter().map(table_without_position).collect())
+        }
+        item => item,
+    }
+}
+
+fn table_without_position(table: toml_edit::Table) -> toml_edit::Table {
+    let mut new_table = toml_edit::Table::new();
+    new_table.set_implicit(table.is_implicit());
+    new_table.set_dotted(table.is_dotted());
+    *new_table.decor_mut() = table.decor().clone();
+    for (key, item) in table.into_iter() {
+        new_table.insert(&key, without_toml_positions(item));
+    }
+    new_table
+}
+
+/// Remove a file, folder, or symlink without following symlinks
+fn remove_path(path: &Path) -> Result<()> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    } else if metadata.is_symlink() && cfg!(windows) && path.is_dir() {
+        // Directory symlinks on windows have to be removed as directories
+        std::fs::remove_dir(path)?;
+    } else {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Normalize a minecraft version to the form mod providers tag versions with
+///
+/// eg. `1.20.0` becomes `1.20`, and `1.20.5 Pre-Release 1` or `1.20.5-pre.1` become `1.20.5-pre1`
+pub fn normalize_mc_version(mc_version: &str) -> String {
+    let mut mc_version = mc_version.trim().to_lowercase();
+    for (alias, suffix) in [
+        (" pre-release ", "-pre"),
+        (" release candidate ", "-rc"),
+        ("-pre.", "-pre"),
+        ("-rc.", "-rc"),
+    ] {
+        mc_version = mc_version.replace(alias, suffix);
+    }
+    match mc_version.strip_suffix(".0") {
+        Some(release) if release.matches('.').count() == 1 => release.into(),
+        _ => mc_version,
+    }
+}
+
+/// Recursively remove empty directories inside `dir`, returning whether `dir` itself is left empty
+///
+/// `dir` itself is kept, and symlinks are never followed, so directories containing any files are left untouched
+fn remove_empty_dirs(dir: &Path) -> Result<bool> {
+    let mut is_empty = true;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+        if entry.file_type()?.is_dir() && remove_empty_dirs(&entry_path)? {
+            println!("Removing empty directory {}", entry_path.display());
+            std::fs::remove_dir(&entry_path)?;
+        } else {
+            is_empty = false;
+        }
+    }
+    Ok(is_empty)
+}
+
 impl std::default::Default for ModpackMeta {
     fn default() -> Self {
         Self {
             pack_name: "my_modpack".into(),
             mc_version: "1.20.1".into(),
+            accepted_mc_versions: Default::default(),
             modloader: ModLoader::Forge,
+            loader_version: None,
+            strict_mc_version: false,
+            version_selection: Default::default(),
+            prefix_load_order: false,
+            manage_mods_exclusively: true,
             mods: Default::default(),
             files: Default::default(),
             default_providers: vec![ModProvider::Modrinth],
+            kind_default_providers: Default::default(),
             forbidden_mods: Default::default(),
+            allowed_mods: None,
+            min_mcmpmgr_version: None,
+            overrides: Default::default(),
+            groups: Default::default(),
+            variants: Default::default(),
+            signing_key: None,
+            dependency_overrides: Default::default(),
+            extra: Default::default(),
         }
     }
 }
+
+#[test]
+fn test_mod_overrides() {
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+    modpack_meta.overrides.push(ModOverride {
+        mod_name: "old-mod".into(),
+        mc_versions: ">=1.21".into(),
+        replacement: ModMeta::new("new-mod").unwrap(),
+    });
+
+    let old_mod = ModMeta::new("old-mod").unwrap();
+    assert!(modpack_meta.get_override(&old_mod).is_none());
+    assert_eq!(
+        modpack_meta
+            .get_override(&old_mod.clone().mc_version("1.21"))
+            .map(|m| m.name.as_str()),
+        Some("new-mod")
+    );
+    assert!(modpack_meta
+        .get_override(&old_mod.clone().mc_version("1.21.1"))
+        .is_some());
+    assert!(modpack_meta
+        .get_override(&ModMeta::new("other-mod").unwrap().mc_version("1.21"))
+        .is_none());
+
+    modpack_meta.mc_version = "1.21.4".into();
+    assert!(modpack_meta.get_override(&old_mod).is_some());
+    // Snapshots can't be compared with version requirements
+    modpack_meta.mc_version = "24w14a".into();
+    assert!(modpack_meta.get_override(&old_mod).is_none());
+}
+
+#[test]
+fn test_game_versions() {
+    assert_eq!(normalize_mc_version("1.20.1"), "1.20.1");
+    assert_eq!(normalize_mc_version("1.21.0"), "1.21");
+    assert_eq!(normalize_mc_version(" 23W45A "), "23w45a");
+    assert_eq!(normalize_mc_version("1.21-rc.1"), "1.21-rc1");
+    assert_eq!(normalize_mc_version("1.20.5 Pre-Release 2"), "1.20.5-pre2");
+
+    let modpack_meta = ModpackMeta::new("test", "1.20.0", ModLoader::Fabric)
+        .accept_mc_version("1.20.1")
+        .accept_mc_version("1.20");
+    assert_eq!(modpack_meta.game_versions(None), vec!["1.20", "1.20.1"]);
+    assert_eq!(modpack_meta.game_versions(Some("1.19.2")), vec!["1.19.2"]);
+}
+
+#[test]
+fn test_mod_aliases() {
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric)
+        .add_mod(&ModMeta::new("sodium").unwrap().project_id("AANobbMI"))
+        .unwrap()
+        .add_mod(&ModMeta::new("Lithium").unwrap())
+        .unwrap();
+    assert_eq!(modpack_meta.canonical_mod_name("Sodium"), "sodium");
+    assert_eq!(modpack_meta.canonical_mod_name("lithium"), "Lithium");
+    assert_eq!(modpack_meta.canonical_mod_name("Fabric-API"), "fabric-api");
+
+    let pack_lock = PinnedPackMeta::new();
+    let modpack_meta = modpack_meta
+        .add_mod(&ModMeta::new("sodium-alias").unwrap().project_id("AANobbMI"))
+        .unwrap();
+    assert_eq!(
+        modpack_meta.find_same_project("sodium-alias", &pack_lock),
+        Some("sodium")
+    );
+    assert_eq!(modpack_meta.find_same_project("Lithium", &pack_lock), None);
+}
+
+#[test]
+fn test_mod_notes() {
+    let mut modpack_meta = ModpackMeta::default()
+        .add_mod(&ModMeta::new("sodium").unwrap().notes("Performance"))
+        .unwrap();
+    assert!(modpack_meta.set_mod_notes("lithium", None).is_err());
+
+    // Notes survive the mod being added again, eg. with a new version
+    modpack_meta = modpack_meta
+        .add_mod(&ModMeta::new("sodium@0.5.8").unwrap())
+        .unwrap();
+    let round_tripped: ModpackMeta =
+        toml::from_str(&toml::to_string(&modpack_meta).unwrap()).unwrap();
+    assert_eq!(
+        round_tripped.mods["sodium"].notes.as_deref(),
+        Some("Performance")
+    );
+
+    modpack_meta.set_mod_notes("sodium", None).unwrap();
+    assert!(!toml::to_string(&modpack_meta).unwrap().contains("notes"));
+}
+
+#[test]
+fn test_unknown_fields_round_trip() {
+    let modpack_toml = r#"
+pack_name = "test"
+mc_version = "1.20.1"
+modloader = "Fabric"
+default_providers = ["Modrinth"]
+forbidden_mods = []
+future_setting = "kept"
+
+[mods.sodium]
+name = "sodium"
+version = "*"
+future_mod_setting = 3
+
+[future_table]
+enabled = true
+"#;
+    let modpack_meta: ModpackMeta = toml::from_str(modpack_toml).unwrap();
+    let modpack_meta = modpack_meta
+        .add_mod(&ModMeta::new("lithium").unwrap())
+        .unwrap();
+    let rewritten: toml::Table = toml::from_str(&toml::to_string(&modpack_meta).unwrap()).unwrap();
+    assert_eq!(rewritten["future_setting"].as_str(), Some("kept"));
+    assert_eq!(rewritten["future_table"]["enabled"].as_bool(), Some(true));
+    assert_eq!(
+        rewritten["mods"]["sodium"]["future_mod_setting"].as_integer(),
+        Some(3)
+    );
+    assert!(rewritten["mods"]["lithium"]
+        .get("future_mod_setting")
+        .is_none());
+}
+
+#[test]
+fn test_save_keeps_formatting() {
+    let modpack_toml = r#"# My pack
+pack_name = "test"
+mc_version = "1.20.1" # Bump with care
+modloader = "Fabric"
+default_providers = ["Modrinth"]
+forbidden_mods = []
+
+# Performance mods
+[mods.sodium]
+name = "sodium"
+version = "*"
+
+# Old mods
+[mods.optifine]
+name = "optifine"
+version = "*"
+"#;
+    let mut modpack_meta: ModpackMeta = toml::from_str(modpack_toml).unwrap();
+    modpack_meta.mc_version = "1.20.4".into();
+    let modpack_meta = modpack_meta
+        .remove_mod("optifine")
+        .add_mod(&ModMeta::new("lithium").unwrap())
+        .unwrap();
+    let contents = modpack_meta.to_toml_string(Some(modpack_toml)).unwrap();
+
+    assert!(contents.starts_with("# My pack\n"));
+    assert!(contents.contains("mc_version = \"1.20.4\" # Bump with care\n"));
+    assert!(contents.contains("# Performance mods\n[mods.sodium]"));
+    assert!(!contents.contains("optifine"));
+    assert!(contents.find("[mods.sodium]") < contents.find("[mods.lithium]"));
+    let saved: ModpackMeta = toml::from_str(&contents).unwrap();
+    assert_eq!(
+        saved.mods.keys().collect::<Vec<_>>(),
+        vec!["lithium", "sodium"]
+    );
+}
+
+#[test]
+fn test_check_mcmpmgr_version() {
+    let mut modpack_meta = ModpackMeta::default();
+    assert!(modpack_meta.check_mcmpmgr_version("0.1.0").is_ok());
+
+    modpack_meta.min_mcmpmgr_version = Some("0.2.0".into());
+    assert!(modpack_meta.check_mcmpmgr_version("0.2.0").is_ok());
+    assert!(modpack_meta.check_mcmpmgr_version("0.3.1").is_ok());
+    assert!(modpack_meta.check_mcmpmgr_version("0.1.9").is_err());
+
+    modpack_meta.min_mcmpmgr_version = Some("not a version".into());
+    assert!(modpack_meta.check_mcmpmgr_version("0.2.0").is_err());
+}
+
+#[test]
+fn test_allowed_mods() {
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+    assert!(modpack_meta.is_mod_allowed("sodium"));
+
+    modpack_meta.allow_mod("sodium");
+    assert!(modpack_meta.is_mod_allowed("sodium"));
+    assert!(!modpack_meta.is_mod_allowed("lithium"));
+    assert!(modpack_meta
+        .clone()
+        .add_mod(&ModMeta::new("lithium").unwrap())
+        .is_err());
+    assert!(modpack_meta
+        .add_mod(&ModMeta::new("sodium").unwrap())
+        .is_ok());
+}
+
+#[test]
+fn test_dependency_overrides() {
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+    let dep = ModMeta::new("cloth-config@11.1.106").unwrap();
+    assert_eq!(
+        modpack_meta
+            .apply_dependency_override(dep.clone(), "some-mod")
+            .version,
+        "11.1.106"
+    );
+
+    modpack_meta.override_dependency("cloth-config", "11.1.118");
+    assert_eq!(
+        modpack_meta
+            .apply_dependency_override(dep, "some-mod")
+            .version,
+        "11.1.118"
+    );
+    let unrelated = ModMeta::new("fabric-api").unwrap();
+    assert_eq!(
+        modpack_meta
+            .apply_dependency_override(unrelated, "some-mod")
+            .version,
+        "*"
+    );
+
+    modpack_meta.remove_dependency_override("cloth-config");
+    assert!(modpack_meta.dependency_overrides.is_empty());
+}
+
+#[test]
+fn test_file_multiple_targets() {
+    let pack_dir = tempfile::tempdir().unwrap();
+    let instance_dir = tempfile::tempdir().unwrap();
+    std::fs::write(pack_dir.path().join("shared.toml"), "a = 1").unwrap();
+    let file_meta = |target_path: &str| FileMeta {
+        target_path: target_path.into(),
+        side: DownloadSide::Both,
+        apply_policy: FileApplyPolicy::Always,
+        sha512: None,
+        manifest: None,
+        templated: false,
+        variants: vec![],
+        priority: 0,
+    };
+
+    let mut modpack_meta = ModpackMeta::default();
+    let shared_path = PathBuf::from("shared.toml");
+    modpack_meta
+        .add_file(
+            &shared_path,
+            &file_meta("config/first.toml"),
+            pack_dir.path(),
+        )
+        .unwrap();
+    // A single target keeps the original format
+    assert!(toml::to_string(&modpack_meta)
+        .unwrap()
+        .contains("[files.\"./shared.toml\"]"));
+    modpack_meta
+        .add_file(
+            &shared_path,
+            &file_meta("config/second.toml"),
+            pack_dir.path(),
+        )
+        .unwrap();
+    // Adding the same target again replaces it
+    modpack_meta
+        .add_file(
+            &shared_path,
+            &file_meta("config/second.toml"),
+            pack_dir.path(),
+        )
+        .unwrap();
+
+    let modpack_meta: ModpackMeta =
+        toml::from_str(&toml::to_string(&modpack_meta).unwrap()).unwrap();
+    assert_eq!(
+        modpack_meta.files.as_ref().unwrap()["./shared.toml"]
+            .0
+            .len(),
+        2
+    );
+    modpack_meta
+        .install_files(
+            pack_dir.path(),
+            instance_dir.path(),
+            DownloadSide::Client,
+            false,
+            &Default::default(),
+            false,
+        )
+        .unwrap();
+    for target_path in ["config/first.toml", "config/second.toml"] {
+        assert_eq!(
+            std::fs::read_to_string(instance_dir.path().join(target_path)).unwrap(),
+            "a = 1"
+        );
+    }
+
+    let mut modpack_meta = modpack_meta;
+    modpack_meta
+        .remove_file(&shared_path, pack_dir.path(), Some("config/first.toml"))
+        .unwrap();
+    let file_targets = &modpack_meta.files.as_ref().unwrap()["./shared.toml"];
+    assert_eq!(file_targets.0.len(), 1);
+    assert_eq!(file_targets.0[0].target_path, "config/second.toml");
+    modpack_meta
+        .remove_file(&shared_path, pack_dir.path(), None)
+        .unwrap();
+    assert!(modpack_meta.files.unwrap().is_empty());
+}
+
+#[test]
+fn test_remove_empty_dirs() {
+    let instance_dir = tempfile::tempdir().unwrap();
+    let config_dir = instance_dir.path().join("config");
+    std::fs::create_dir_all(config_dir.join("dropped/nested")).unwrap();
+    std::fs::create_dir_all(config_dir.join("user")).unwrap();
+    std::fs::write(config_dir.join("user/settings.toml"), "a = 1").unwrap();
+
+    assert!(!remove_empty_dirs(&config_dir).unwrap());
+    assert!(!config_dir.join("dropped").exists());
+    assert!(config_dir.join("user/settings.toml").exists());
+
+    std::fs::remove_dir_all(config_dir.join("user")).unwrap();
+    assert!(remove_empty_dirs(&config_dir).unwrap());
+    assert!(config_dir.exists());
+}
+
+#[test]
+fn test_validate() {
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric)
+        .add_mod(&ModMeta::new("sodium@mc1.20.1-0.5.3").unwrap())
+        .unwrap()
+        .add_mod(&ModMeta::new("lithium").unwrap())
+        .unwrap();
+    modpack_meta.validate().unwrap();
+
+    let pack_dir = tempfile::tempdir().unwrap();
+    let mut invalid_meta = modpack_meta.clone();
+    invalid_meta.forbidden_mods.insert("lithium".into());
+    invalid_meta
+        .mods
+        .insert("Sodium".into(), ModMeta::new("Sodium").unwrap().version(""));
+    invalid_meta.mods.insert(
+        "raw-mod".into(),
+        ModMeta::new("raw-mod").unwrap().provider(ModProvider::Raw),
+    );
+    invalid_meta
+        .save_to_dir(&pack_dir.path().to_path_buf())
+        .unwrap();
+
+    let e = ModpackMeta::load_from_directory(pack_dir.path())
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(e.contains("Mods Sodium and sodium are duplicates"));
+    assert!(e.contains("Mod Sodium has an invalid version ''"));
+    assert!(e.contains("Mod lithium is in the modpack, but is forbidden"));
+    assert!(e.contains("Mod raw-mod can only use the Raw provider"));
+
+    let e = ModpackMeta::load_from_directory(&pack_dir.path().join("missing"))
+        .err()
+        .unwrap();
+    assert!(matches!(
+        McmpmgrError::find(&e),
+        Some(McmpmgrError::InvalidPack(_))
+    ));
+}
+
+#[test]
+fn test_edit_default_providers() {
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric)
+        .provider(ModProvider::Raw)
+        .provider(ModProvider::Raw);
+    assert_eq!(
+        modpack_meta.default_providers,
+        [ModProvider::Modrinth, ModProvider::Raw]
+    );
+
+    modpack_meta
+        .remove_provider(&ModProvider::Modrinth)
+        .unwrap();
+    assert_eq!(modpack_meta.default_providers, [ModProvider::Raw]);
+    assert!(modpack_meta
+        .remove_provider(&ModProvider::Modrinth)
+        .is_err());
+    // The last default provider can't be removed
+    assert!(modpack_meta.remove_provider(&ModProvider::Raw).is_err());
+    assert_eq!(modpack_meta.default_providers, [ModProvider::Raw]);
+}
+
+#[test]
+fn test_kind_default_providers() {
+    let mut modpack_meta =
+        ModpackMeta::new("test", "1.20.1", ModLoader::Fabric).provider(ModProvider::CurseForge);
+    modpack_meta
+        .kind_default_providers
+        .insert(ProjectKind::ResourcePack, vec![ModProvider::Modrinth]);
+
+    assert_eq!(
+        modpack_meta.default_providers_for(ProjectKind::ResourcePack),
+        [ModProvider::Modrinth]
+    );
+    // Kinds without their own providers fall back to the pack's default providers
+    assert_eq!(
+        modpack_meta.default_providers_for(ProjectKind::Mod),
+        [ModProvider::Modrinth, ModProvider::CurseForge]
+    );
+
+    let modpack_meta: ModpackMeta =
+        toml::from_str(&toml::to_string(&modpack_meta).unwrap()).unwrap();
+    assert_eq!(
+        modpack_meta.default_providers_for(ProjectKind::ResourcePack),
+        [ModProvider::Modrinth]
+    );
+}
+
+#[test]
+fn test_modloader_from_str() {
+    for name in ["Fabric", "fabric", "FABRIC", " fAbRiC "] {
+        assert_eq!(ModLoader::from_str(name).unwrap(), ModLoader::Fabric);
+    }
+    for name in ["Forge", "forge", "MinecraftForge"] {
+        assert_eq!(ModLoader::from_str(name).unwrap(), ModLoader::Forge);
+    }
+    for name in ["", "fabrik", "forge2", "quilt"] {
+        assert!(ModLoader::from_str(name).is_err());
+    }
+    // The names written to modpack.toml don't change
+    assert_eq!(ModLoader::Fabric.to_string(), "Fabric");
+    assert_eq!(
+        ModLoader::from_str(&ModLoader::Forge.to_string()).unwrap(),
+        ModLoader::Forge
+    );
+}
+
+#[test]
+fn test_select_variant() {
+    let file_meta = |target_path: &str, variants: &[&str]| FileMeta {
+        target_path: target_path.into(),
+        side: DownloadSide::Both,
+        apply_policy: FileApplyPolicy::Always,
+        sha512: None,
+        manifest: None,
+        templated: false,
+        variants: variants.iter().map(|v| v.to_string()).collect(),
+        priority: 0,
+    };
+    let mut modpack_meta = ModpackMeta::default();
+    modpack_meta.add_mod_to_group("shaders", "iris");
+    modpack_meta
+        .variants
+        .insert("full".into(), BTreeSet::from(["shaders".into()]));
+    modpack_meta.variants.insert("lite".into(), BTreeSet::new());
+    modpack_meta.files = Some(BTreeMap::from([
+        (
+            "./options.txt".into(),
+            FileTargets(vec![file_meta("options.txt", &[])]),
+        ),
+        (
+            "./lite.toml".into(),
+            FileTargets(vec![file_meta("config/perf.toml", &["lite"])]),
+        ),
+        (
+            "./full.toml".into(),
+            FileTargets(vec![
+                file_meta("config/perf.toml", &["full"]),
+                file_meta("config/shaders.toml", &["full"]),
+            ]),
+        ),
+    ]));
+    assert!(modpack_meta.validate().is_ok());
+    assert_eq!(
+        modpack_meta.variant_groups("full").unwrap(),
+        &BTreeSet::from(["shaders".to_string()])
+    );
+    assert!(modpack_meta.variant_groups("ultra").is_err());
+
+    let mut lite = modpack_meta.clone();
+    lite.select_variant(Some("lite"));
+    let lite_files: Vec<&String> = lite.files.as_ref().unwrap().keys().collect();
+    assert_eq!(lite_files, ["./lite.toml", "./options.txt"]);
+
+    // Profiles without a variant only get the untagged files
+    let mut no_variant = modpack_meta.clone();
+    no_variant.select_variant(None);
+    let files: Vec<&String> = no_variant.files.as_ref().unwrap().keys().collect();
+    assert_eq!(files, ["./options.txt"]);
+
+    modpack_meta
+        .variants
+        .insert("broken".into(), BTreeSet::from(["missing".into()]));
+    modpack_meta.files.as_mut().unwrap().insert(
+        "./other.toml".into(),
+        FileTargets(vec![file_meta("other.toml", &["unknown"])]),
+    );
+    let e = modpack_meta.validate().err().unwrap().to_string();
+    assert!(e.contains("Variant broken installs group missing, which doesn't exist"));
+    assert!(e.contains("Files are tagged for variant unknown, which doesn't exist"));
+}
+
+#[test]
+fn test_file_priority() {
+    let pack_dir = tempfile::tempdir().unwrap();
+    let instance_dir = tempfile::tempdir().unwrap();
+    std::fs::write(pack_dir.path().join("a_override.toml"), "a = 2").unwrap();
+    std::fs::write(pack_dir.path().join("b_base.toml"), "a = 1").unwrap();
+    std::fs::write(pack_dir.path().join("c_other.toml"), "b = 1").unwrap();
+    let file_meta = |target_path: &str, priority: i32| FileMeta {
+        target_path: target_path.into(),
+        side: DownloadSide::Both,
+        apply_policy: FileApplyPolicy::Always,
+        sha512: None,
+        manifest: None,
+        templated: false,
+        variants: vec![],
+        priority,
+    };
+    let mut modpack_meta = ModpackMeta::default();
+    for (local_path, target_path, priority) in [
+        ("a_override.toml", "config/mod.toml", 1),
+        ("b_base.toml", "config/mod.toml", 0),
+        ("c_other.toml", "config/other.toml", 0),
+    ] {
+        modpack_meta
+            .add_file(
+                Path::new(local_path),
+                &file_meta(target_path, priority),
+                pack_dir.path(),
+            )
+            .unwrap();
+    }
+
+    // Only non-default priorities are saved
+    let pack_toml = toml::to_string(&modpack_meta).unwrap();
+    assert_eq!(pack_toml.matches("priority = ").count(), 1);
+    let modpack_meta: ModpackMeta = toml::from_str(&pack_toml).unwrap();
+
+    // Same priority keeps path order, and higher priorities are applied last
+    let apply_order: Vec<&str> = modpack_meta
+        .files_in_apply_order()
+        .into_iter()
+        .map(|(rel_path, _)| rel_path.as_str())
+        .collect();
+    assert_eq!(
+        apply_order,
+        ["./b_base.toml", "./c_other.toml", "./a_override.toml"]
+    );
+    modpack_meta
+        .install_files(
+            pack_dir.path(),
+            instance_dir.path(),
+            DownloadSide::Client,
+            false,
+            &Default::default(),
+            false,
+        )
+        .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(instance_dir.path().join("config/mod.toml")).unwrap(),
+        "a = 2"
+    );
+    assert_eq!(
+        std::fs::read_to_string(instance_dir.path().join("config/other.toml")).unwrap(),
+        "b = 1"
+    );
+}
@@ -5,17 +5,41 @@ use std::{
     fmt::Display,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Arc,
 };
 
-use crate::{modpack::ModpackMeta, providers::DownloadSide, resolver::PinnedPackMeta};
+use crate::{
+    export,
+    modpack::{FileChange, ModpackMeta},
+    providers::DownloadSide,
+    resolver::{
+        self, DependencyVersions, DownloadCache, DownloadProgress, ModFileChanges, OutdatedMod,
+        PinnedPackMeta,
+    },
+    transaction::{auto_recover, move_aside_malformed, write_atomic, InstanceLock},
+};
+use tokio::sync::{mpsc::UnboundedSender, OnceCell};
 
 const CONFIG_DIR_NAME: &str = "mcmpmgr";
 const DATA_FILENAME: &str = "data.toml";
+/// Name of the directory mods are installed to within an instance, unless a profile says otherwise
+pub const DEFAULT_MODS_DIR_NAME: &str = "mods";
+/// Environment variable for the directory relative local pack sources are resolved against
+pub(crate) const PACKS_ROOT_ENV: &str = "MCMPMGR_PACKS_ROOT";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PackSource {
-    Git { url: String },
-    Local { path: PathBuf },
+    Git {
+        url: String,
+    },
+    /// A local modpack directory. Relative paths are relative to the packs root, see [`PackSource::local_path`]
+    Local {
+        path: PathBuf,
+    },
+    /// An offline bundle exported with `mcmpmgr export --format bundle`
+    Bundle {
+        path: PathBuf,
+    },
 }
 
 impl FromStr for PackSource {
@@ -25,13 +49,69 @@ impl FromStr for PackSource {
         if s.starts_with("git+") {
             let url = s.trim_start_matches("git+").to_string();
             Ok(PackSource::Git { url })
+        } else if let Some(path) = s.strip_prefix("bundle+") {
+            match PathBuf::from(path).canonicalize() {
+                Ok(path) => Ok(PackSource::Bundle { path }),
+                Err(e) => Err(e.to_string()),
+            }
+        } else if let Some(path) = s.strip_prefix("rel+") {
+            let path = PathBuf::from(path);
+            if path.is_absolute() {
+                Err(format!(
+                    "Pack source {s} should be relative to the packs root"
+                ))
+            } else {
+                Ok(PackSource::Local { path })
+            }
         } else {
             let path = PathBuf::from(s).canonicalize();
             match path {
                 Ok(path) => Ok(PackSource::Local { path }),
-                Err(e) => Err(e.to_string())
+                Err(e) => Err(e.to_string()),
+            }
+        }
+    }
+}
+
+impl PackSource {
+    /// Get the directory of a local pack source
+    ///
+    /// Relative paths are resolved against the packs root, which is `$MCMPMGR_PACKS_ROOT` if set,
+    /// otherwise the config directory. This keeps profiles working when the packs and config are moved together.
+    pub fn local_path(path: &Path) -> Result<PathBuf> {
+        if path.is_absolute() {
+            return Ok(path.to_path_buf());
+        }
+        Ok(Self::packs_root()?.join(path))
+    }
+
+    /// Get the directory relative local pack sources are resolved against
+    pub fn packs_root() -> Result<PathBuf> {
+        match std::env::var_os(PACKS_ROOT_ENV) {
+            Some(packs_root) => Ok(PathBuf::from(packs_root)),
+            None => Data::get_config_folder_path(),
+        }
+    }
+
+    /// Check that the pack source can be installed from, ie. local packs are valid modpack directories
+    /// and git repos can be reached
+    pub fn check_available(&self) -> Result<()> {
+        match self {
+            PackSource::Git { url } => {
+                let mut remote = git2::Remote::create_detached(url.as_str())?;
+                remote.connect(git2::Direction::Fetch)?;
+                remote.disconnect()?;
+            }
+            PackSource::Local { path } => {
+                ModpackMeta::load_from_directory(&Self::local_path(path)?)?;
+            }
+            PackSource::Bundle { path } => {
+                if !path.is_file() {
+                    anyhow::bail!("Bundle {} does not exist", path.display())
+                }
             }
         }
+        Ok(())
     }
 }
 
@@ -39,16 +119,91 @@ impl Display for PackSource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             PackSource::Git { url } => write!(f, "git+{url}"),
+            PackSource::Local { path } if path.is_relative() => write!(f, "rel+{}", path.display()),
             PackSource::Local { path } => write!(f, "{}", path.display()),
+            PackSource::Bundle { path } => write!(f, "bundle+{}", path.display()),
         }
     }
 }
 
+/// File in an instance recording the commit of the git pack source installed in it
+const INSTALLED_COMMIT_FILENAME: &str = ".mcmpmgr-commit";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Profile {
     pub instance_folder: PathBuf,
     pub pack_source: PackSource,
     pub side: DownloadSide,
+    /// Also install mods that are only optional for this profile's side
+    #[serde(default)]
+    pub include_optional: bool,
+    /// Symlink pack files into the instance instead of copying them (local pack sources only)
+    #[serde(default)]
+    pub symlink_files: bool,
+    /// Optional mod groups from the pack to install alongside the ungrouped mods
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Map of mod name -> side to install the mod on for this profile, in place of the side the pack pins it for
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mod_side_overrides: BTreeMap<String, DownloadSide>,
+    /// Name of the instance's mods directory, if it isn't `mods`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mods_dir_name: Option<String>,
+    /// Values for the `${VAR}` placeholders in the pack's templated files
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
+    /// Remove directories left empty inside the pack's folders after applying pack files
+    #[serde(default)]
+    pub prune_empty_dirs: bool,
+    /// Variant of the pack to install, which installs its groups and only the files tagged for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// If not empty, only these mods and their dependencies are installed, and nothing is deleted from the mods folder.
+    /// This is only for a single install, so it is never saved
+    #[serde(skip)]
+    pub only_mods: Vec<String>,
+}
+
+/// A profile-specific side for a mod, written as `<mod name>=<side>`
+#[derive(Debug, Clone)]
+pub struct ModSideOverride {
+    pub mod_name: String,
+    pub side: DownloadSide,
+}
+
+impl FromStr for ModSideOverride {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((mod_name, side)) = s.split_once('=') else {
+            anyhow::bail!("Invalid mod side override {s}. Expected <mod name>=<side>")
+        };
+        Ok(Self {
+            mod_name: mod_name.into(),
+            side: side.parse()?,
+        })
+    }
+}
+
+/// A value for a template variable, written as `<name>=<value>`
+#[derive(Debug, Clone)]
+pub struct TemplateVariable {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for TemplateVariable {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((name, value)) = s.split_once('=') else {
+            anyhow::bail!("Invalid template variable {s}. Expected <name>=<value>")
+        };
+        Ok(Self {
+            name: name.into(),
+            value: value.into(),
+        })
+    }
 }
 
 impl Profile {
@@ -61,42 +216,302 @@ impl Profile {
             instance_folder: instance_folder.canonicalize()?,
             pack_source,
             side,
+            include_optional: false,
+            symlink_files: false,
+            groups: vec![],
+            mod_side_overrides: Default::default(),
+            mods_dir_name: None,
+            variables: Default::default(),
+            prune_empty_dirs: false,
+            variant: None,
+            only_mods: vec![],
         })
     }
 
+    /// Get the directory in the instance that mods are installed to
+    pub fn mods_dir(&self) -> PathBuf {
+        self.instance_folder.join(
+            self.mods_dir_name
+                .as_deref()
+                .unwrap_or(DEFAULT_MODS_DIR_NAME),
+        )
+    }
+
     pub async fn install(&self) -> Result<()> {
-        let (pack_lock, pack_directory, _temp_dir) = match &self.pack_source {
-            PackSource::Git { url } => {
-                let (pack_lock, packdir) = PinnedPackMeta::load_from_git_repo(&url, true).await?;
-                let pack_path = packdir.path().to_path_buf();
-                (pack_lock, pack_path, Some(packdir))
+        self.install_with_cache(&InstallCache::default(), None)
+            .await
+    }
+
+    /// Install the profile, reusing any pack checkouts and mod downloads in the install cache
+    ///
+    /// Mod download progress is sent to `progress` if given
+    pub async fn install_with_cache(
+        &self,
+        install_cache: &InstallCache,
+        progress: Option<&UnboundedSender<DownloadProgress>>,
+    ) -> Result<()> {
+        let symlink = match &self.pack_source {
+            PackSource::Local { .. } => self.symlink_files,
+            pack_source => {
+                if self.symlink_files {
+                    eprintln!("Pack files can only be symlinked from local pack sources. Copying files from {pack_source} instead");
+                }
+                false
             }
-            PackSource::Local { path } => (
-                PinnedPackMeta::load_from_directory(&path, true).await?,
-                path.to_path_buf(),
-                None,
-            ),
         };
-        let modpack_meta = ModpackMeta::load_from_directory(&pack_directory)?;
-        modpack_meta.install_files(&pack_directory, &self.instance_folder, self.side)?;
+        let (pack_directory, modpack_meta, pack_lock) = self.load_pack(install_cache).await?;
+        modpack_meta.install_files(
+            &pack_directory,
+            &self.instance_folder,
+            self.side,
+            symlink,
+            &self.variables,
+            self.prune_empty_dirs,
+        )?;
 
         pack_lock
-            .download_mods(&self.instance_folder.join("mods"), self.side)
+            .download_mods_with_cache(
+                &self.mods_dir(),
+                self.side,
+                self.include_optional,
+                &install_cache.downloads,
+                progress,
+            )
             .await?;
+        if let PackSource::Git { .. } = &self.pack_source {
+            write_atomic(
+                &self.instance_folder.join(INSTALLED_COMMIT_FILENAME),
+                resolver::git_head_commit(&pack_directory)?.as_bytes(),
+            )?;
+        }
         Ok(())
     }
+
+    /// Check for new commits to the profile's git pack source since it was installed, and for mods with newer versions
+    pub async fn check_updates(&self, install_cache: &InstallCache) -> Result<ProfileUpdates> {
+        let (pack_directory, modpack_meta, pack_lock) = self.load_pack(install_cache).await?;
+        let new_commits = match &self.pack_source {
+            PackSource::Git { .. } => {
+                let installed_commit =
+                    std::fs::read_to_string(self.instance_folder.join(INSTALLED_COMMIT_FILENAME))
+                        .ok();
+                Some(match installed_commit {
+                    Some(installed_commit) => {
+                        match resolver::git_commits_since(&pack_directory, installed_commit.trim())?
+                        {
+                            Some(count) => NewPackCommits::Count(count),
+                            None => NewPackCommits::Diverged,
+                        }
+                    }
+                    None => NewPackCommits::Unknown,
+                })
+            }
+            _ => None,
+        };
+        let outdated_mods = pack_lock
+            .get_outdated_mods(&modpack_meta)
+            .await
+            .into_iter()
+            .filter(|outdated_mod| outdated_mod.outdated)
+            .collect();
+        Ok(ProfileUpdates {
+            new_commits,
+            outdated_mods,
+        })
+    }
+
+    /// Work out what installing the profile would change in the instance, without changing anything
+    pub async fn preview_install(&self, install_cache: &InstallCache) -> Result<InstallPreview> {
+        let (_, modpack_meta, pack_lock) = self.load_pack(install_cache).await?;
+        Ok(InstallPreview {
+            mods: pack_lock.plan_mod_changes(&self.mods_dir(), self.side, self.include_optional)?,
+            files: modpack_meta.plan_file_changes(&self.instance_folder, self.side),
+        })
+    }
+
+    /// Get the directory of the profile's pack, fetching it first if it isn't local
+    async fn pack_directory(&self, install_cache: &InstallCache) -> Result<PathBuf> {
+        Ok(match &self.pack_source {
+            PackSource::Git { url } => install_cache.get_git_pack(url).await?,
+            PackSource::Local { path } => PackSource::local_path(path)?,
+            PackSource::Bundle { path } => install_cache.get_bundle(path).await?,
+        })
+    }
+
+    /// Get the lock of the profile's pack, with every mod in it regardless of the profile's groups
+    pub async fn load_pack_lock(&self, install_cache: &InstallCache) -> Result<PinnedPackMeta> {
+        let pack_directory = self.pack_directory(install_cache).await?;
+        PinnedPackMeta::load_from_directory(&pack_directory, DependencyVersions::Latest).await
+    }
+
+    /// Get the pack directory, metadata and lock for the profile's pack source,
+    /// with the lock set up to install the profile's groups and side overrides
+    async fn load_pack(
+        &self,
+        install_cache: &InstallCache,
+    ) -> Result<(PathBuf, ModpackMeta, PinnedPackMeta)> {
+        let pack_directory = self.pack_directory(install_cache).await?;
+        let mut pack_lock =
+            PinnedPackMeta::load_from_directory(&pack_directory, DependencyVersions::Latest)
+                .await?;
+        let mut modpack_meta = ModpackMeta::load_from_directory(&pack_directory)?;
+        let mut groups = self.groups.clone();
+        if let Some(variant) = &self.variant {
+            groups.extend(modpack_meta.variant_groups(variant)?.iter().cloned());
+        }
+        modpack_meta.select_variant(self.variant.as_deref());
+        pack_lock.select_groups(&modpack_meta, &groups)?;
+        if let Some(signing_key) = &modpack_meta.signing_key {
+            pack_lock.set_signing_key(signing_key)?;
+        }
+        pack_lock.set_side_overrides(&self.mod_side_overrides);
+        pack_lock.set_keep_unpinned(!modpack_meta.manage_mods_exclusively);
+        if !self.only_mods.is_empty() {
+            pack_lock.select_only(&modpack_meta, &self.only_mods)?;
+        }
+        if modpack_meta.prefix_load_order {
+            pack_lock.prefix_load_order()?;
+        }
+        Ok((pack_directory, modpack_meta, pack_lock))
+    }
+}
+
+/// New commits to a profile's git pack source since it was installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewPackCommits {
+    /// The profile hasn't been installed from git yet, so there's nothing to compare with
+    Unknown,
+    /// Number of commits made since the installed commit
+    Count(usize),
+    /// The installed commit is no longer in the pack's history, eg. since it was force pushed over
+    Diverged,
+}
+
+/// Updates available for a profile, without installing anything
+#[derive(Debug)]
+pub struct ProfileUpdates {
+    /// Unset if the pack source isn't a git repo
+    pub new_commits: Option<NewPackCommits>,
+    /// Mods that would be pinned at a newer version if the pack was updated
+    pub outdated_mods: Vec<OutdatedMod>,
+}
+
+/// What installing a profile would change in its instance
+#[derive(Debug, Clone)]
+pub struct InstallPreview {
+    pub mods: ModFileChanges,
+    pub files: Vec<FileChange>,
+}
+
+impl Display for InstallPreview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.mods.to_download.is_empty()
+            && self.mods.to_delete.is_empty()
+            && self.files.is_empty()
+        {
+            return write!(f, "No changes");
+        }
+        for filename in self.mods.to_download.iter() {
+            writeln!(f, "Download mod {filename}")?;
+        }
+        for filename in self.mods.to_delete.iter() {
+            writeln!(f, "Delete mod {filename}")?;
+        }
+        for file_change in self.files.iter() {
+            writeln!(f, "{} {}", file_change.action, file_change.target_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pack directories keyed by where they came from, each filled in at most once
+///
+/// The map is only locked long enough to find the cell for a key, so fetching one pack doesn't block
+/// installs that need a different one
+type PackDirs<K> = Arc<std::sync::Mutex<BTreeMap<K, Arc<OnceCell<tempfile::TempDir>>>>>;
+
+/// Get the cell holding the pack directory for a key, adding an empty one if there isn't one yet
+fn pack_dir_cell<K: Ord + Clone>(
+    pack_dirs: &PackDirs<K>,
+    key: &K,
+) -> Arc<OnceCell<tempfile::TempDir>> {
+    let mut pack_dirs = pack_dirs.lock().unwrap_or_else(|e| e.into_inner());
+    pack_dirs.entry(key.clone()).or_default().clone()
+}
+
+/// State shared between profile installs so that common packs and mods are only fetched once
+#[derive(Debug, Clone)]
+pub struct InstallCache {
+    /// Git pack sources that have already been cloned, keyed by url
+    git_packs: PackDirs<String>,
+    /// Bundle pack sources that have already been extracted, keyed by path
+    bundles: PackDirs<PathBuf>,
+    /// Mod files that have already been downloaded
+    downloads: DownloadCache,
+}
+
+impl Default for InstallCache {
+    fn default() -> Self {
+        Self {
+            git_packs: Default::default(),
+            bundles: Default::default(),
+            downloads: DownloadCache::from_config_dir(),
+        }
+    }
+}
+
+impl InstallCache {
+    /// Get the directory of a cloned git pack, cloning it if it hasn't been cloned yet
+    async fn get_git_pack(&self, git_url: &str) -> Result<PathBuf> {
+        let cell = pack_dir_cell(&self.git_packs, &git_url.to_string());
+        if let Some(pack_dir) = cell.get() {
+            println!("Reusing modpack cloned from git repo {git_url}");
+            return Ok(pack_dir.path().to_path_buf());
+        }
+        let pack_dir = cell
+            .get_or_try_init(|| async {
+                let git_url = git_url.to_string();
+                tokio::task::spawn_blocking(move || resolver::clone_git_repo(&git_url)).await?
+            })
+            .await?;
+        Ok(pack_dir.path().to_path_buf())
+    }
+
+    /// Get the directory of an extracted bundle, extracting it if it hasn't been extracted yet
+    ///
+    /// The bundled mod files are added to the download cache, so nothing needs to be downloaded
+    async fn get_bundle(&self, bundle: &Path) -> Result<PathBuf> {
+        let cell = pack_dir_cell(&self.bundles, &bundle.to_path_buf());
+        let pack_dir = cell
+            .get_or_try_init(|| async {
+                let bundle = bundle.to_path_buf();
+                let downloads = self.downloads.clone();
+                tokio::task::spawn_blocking(move || {
+                    let pack_dir = tempfile::tempdir()?;
+                    export::extract_bundle(&bundle, pack_dir.path(), &downloads)?;
+                    anyhow::Ok(pack_dir)
+                })
+                .await?
+            })
+            .await?;
+        Ok(pack_dir.path().to_path_buf())
+    }
 }
 
 /// User data and configs for the modpack manager
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Data {
     profiles: BTreeMap<String, Profile>,
+    /// Name of the theme to use in the GUI
+    #[serde(default)]
+    gui_theme: Option<String>,
 }
 
 impl Default for Data {
     fn default() -> Self {
         Self {
             profiles: Default::default(),
+            gui_theme: None,
         }
     }
 }
@@ -123,7 +538,79 @@ impl Data {
         self.profiles.remove(profile_name);
     }
 
-    fn get_config_folder_path() -> Result<PathBuf> {
+    /// Save a copy of a profile under a new name
+    pub fn copy_profile(&mut self, profile_name: &str, new_profile_name: &str) -> Result<()> {
+        if self.profiles.contains_key(new_profile_name) {
+            anyhow::bail!("Profile '{new_profile_name}' already exists")
+        }
+        let Some(profile) = self.profiles.get(profile_name) else {
+            anyhow::bail!("Profile '{profile_name}' does not exist")
+        };
+        self.profiles
+            .insert(new_profile_name.into(), profile.clone());
+        Ok(())
+    }
+
+    pub fn get_gui_theme(&self) -> Option<&str> {
+        self.gui_theme.as_deref()
+    }
+
+    pub fn set_gui_theme(&mut self, theme_name: &str) {
+        self.gui_theme = Some(theme_name.into());
+    }
+
+    /// Install several profiles, optionally concurrently, sharing pack checkouts and downloads between them
+    ///
+    /// Returns the install result for each requested profile
+    pub async fn install_profiles(
+        &self,
+        profile_names: &[String],
+        parallel: bool,
+    ) -> BTreeMap<String, Result<()>> {
+        let install_cache = InstallCache::default();
+        let mut results = BTreeMap::new();
+        let mut installs = tokio::task::JoinSet::new();
+
+        for profile_name in profile_names.iter() {
+            let profile = if let Some(profile) = self.get_profile(profile_name) {
+                profile.clone()
+            } else {
+                results.insert(
+                    profile_name.clone(),
+                    Err(anyhow::format_err!(
+                        "Profile '{profile_name}' does not exist"
+                    )),
+                );
+                continue;
+            };
+
+            println!("Installing profile '{profile_name}'...");
+            if parallel {
+                let install_cache = install_cache.clone();
+                let profile_name = profile_name.clone();
+                installs.spawn(async move {
+                    let result = profile.install_with_cache(&install_cache, None).await;
+                    (profile_name, result)
+                });
+            } else {
+                let result = profile.install_with_cache(&install_cache, None).await;
+                results.insert(profile_name.clone(), result);
+            }
+        }
+
+        while let Some(install) = installs.join_next().await {
+            match install {
+                Ok((profile_name, result)) => {
+                    results.insert(profile_name, result);
+                }
+                Err(e) => eprintln!("Profile install task failed: {e}"),
+            }
+        }
+
+        results
+    }
+
+    pub(crate) fn get_config_folder_path() -> Result<PathBuf> {
         let home_dir = home::home_dir()
             .and_then(|home_dir| Some(home_dir.join(format!(".config/{CONFIG_DIR_NAME}"))));
 
@@ -134,6 +621,29 @@ impl Data {
         }
     }
 
+    /// Get the path of the file user profiles are saved to
+    pub fn get_data_file_path() -> Result<PathBuf> {
+        Ok(Self::get_config_folder_path()?.join(DATA_FILENAME))
+    }
+
+    /// Lock the config directory so that other instances can't change the user data until the lock is dropped
+    pub fn lock() -> Result<InstanceLock> {
+        let config_dir = Self::get_config_folder_path()?;
+        std::fs::create_dir_all(&config_dir)?;
+        InstanceLock::acquire(&config_dir)
+    }
+
+    /// Change the saved user data while holding the lock, so that changes made by other instances aren't lost
+    ///
+    /// Returns the user data as it was saved
+    pub fn update(change: impl FnOnce(&mut Self) -> Result<()>) -> Result<Self> {
+        let _instance_lock = Self::lock()?;
+        let mut data = Self::load()?;
+        change(&mut data)?;
+        data.save()?;
+        Ok(data)
+    }
+
     pub fn load() -> Result<Self> {
         let config_dir = Self::get_config_folder_path()?;
         if !config_dir.exists() {
@@ -141,14 +651,78 @@ impl Data {
             std::fs::create_dir_all(&config_dir)?;
         }
 
-        let datafile = config_dir.join(DATA_FILENAME);
+        let datafile = Self::get_data_file_path()?;
 
-        Ok(if !datafile.exists() {
-            Self::default()
-        } else {
-            let data_string = std::fs::read_to_string(datafile)?;
-            toml::from_str(&data_string)?
-        })
+        if !datafile.exists() {
+            return Ok(Self::default());
+        }
+        let data_string = std::fs::read_to_string(&datafile)?;
+        match Self::from_toml_str(&data_string) {
+            Ok(data) => Ok(data),
+            Err(e) if auto_recover() => {
+                let malformed_path = move_aside_malformed(&datafile)?;
+                eprintln!(
+                    "{} is malformed ({}). Moved it to {} and starting with no profiles...",
+                    datafile.display(),
+                    e.message(),
+                    malformed_path.display()
+                );
+                Ok(Self::default())
+            }
+            Err(e) => Err(anyhow::Error::new(e).context(format!(
+                "{} is malformed. Fix it by hand, or rerun with --auto-recover to move it aside and start with no profiles",
+                datafile.display()
+            ))),
+        }
+    }
+
+    /// Parse user data, migrating anything written by older versions of mcmpmgr
+    fn from_toml_str(data_string: &str) -> Result<Self, toml::de::Error> {
+        let mut data: toml::Table = toml::from_str(data_string)?;
+        Self::migrate(&mut data);
+        data.try_into()
+    }
+
+    /// Update user data written by older versions of mcmpmgr to the current format, in place
+    ///
+    /// Profiles used to record the instance's mods folder as `mods_folder`, rather than the instance folder.
+    /// The instance folder is the mods folder's parent, and mods folders not named `mods` are kept as `mods_dir_name`
+    fn migrate(data: &mut toml::Table) {
+        let Some(profiles) = data.get_mut("profiles").and_then(toml::Value::as_table_mut) else {
+            return;
+        };
+        for (_, profile) in profiles.iter_mut() {
+            let Some(profile) = profile.as_table_mut() else {
+                continue;
+            };
+            if profile.contains_key("instance_folder") {
+                continue;
+            }
+            let Some(toml::Value::String(mods_folder)) = profile.remove("mods_folder") else {
+                continue;
+            };
+            let mods_folder = PathBuf::from(mods_folder);
+            let (Some(instance_folder), Some(mods_dir_name)) =
+                (mods_folder.parent(), mods_folder.file_name())
+            else {
+                // There's no parent to use as the instance folder, so the folder is the best guess there is
+                profile.insert(
+                    "instance_folder".into(),
+                    mods_folder.to_string_lossy().to_string().into(),
+                );
+                continue;
+            };
+            profile.insert(
+                "instance_folder".into(),
+                instance_folder.to_string_lossy().to_string().into(),
+            );
+            if mods_dir_name != DEFAULT_MODS_DIR_NAME && !profile.contains_key("mods_dir_name") {
+                profile.insert(
+                    "mods_dir_name".into(),
+                    mods_dir_name.to_string_lossy().to_string().into(),
+                );
+            }
+        }
     }
 
     pub fn save(&self) -> Result<()> {
@@ -158,9 +732,101 @@ impl Data {
             std::fs::create_dir_all(&config_dir)?;
         }
 
-        let datafile = config_dir.join(DATA_FILENAME);
-        std::fs::write(datafile, toml::to_string(self)?)?;
+        let datafile = Self::get_data_file_path()?;
+        write_atomic(&datafile, toml::to_string(self)?.as_bytes())?;
         println!("Saved user profiles configuration");
         Ok(())
     }
 }
+
+#[test]
+fn test_copy_profile() {
+    let instance_dir = tempfile::tempdir().unwrap();
+    let mut profile = Profile::new(
+        instance_dir.path(),
+        PackSource::Git {
+            url: "https://example.com/pack.git".into(),
+        },
+        DownloadSide::Client,
+    )
+    .unwrap();
+    profile.groups = vec!["shaders".into()];
+    let mut userdata = Data::default();
+    userdata.add_profile("survival", profile);
+
+    userdata
+        .copy_profile("survival", "survival-shaders")
+        .unwrap();
+    let copied_profile = userdata.get_profile("survival-shaders").unwrap();
+    assert_eq!(copied_profile.groups, vec!["shaders".to_string()]);
+    assert_eq!(copied_profile.side, DownloadSide::Client);
+
+    assert!(userdata
+        .copy_profile("survival", "survival-shaders")
+        .is_err());
+    assert!(userdata.copy_profile("missing", "other").is_err());
+}
+
+#[test]
+fn test_relative_pack_source() {
+    let pack_source = PackSource::from_str("rel+packs/survival").unwrap();
+    assert!(
+        matches!(&pack_source, PackSource::Local { path } if path == Path::new("packs/survival"))
+    );
+    assert_eq!(pack_source.to_string(), "rel+packs/survival");
+
+    let absolute_path = std::env::temp_dir().canonicalize().unwrap();
+    assert!(PackSource::from_str(&format!("rel+{}", absolute_path.display())).is_err());
+    assert_eq!(
+        PackSource::local_path(&absolute_path).unwrap(),
+        absolute_path
+    );
+}
+
+#[test]
+fn test_migrate_mods_folder() {
+    let data = Data::from_toml_str(
+        r#"
+[profiles.survival]
+mods_folder = "/games/survival/mods"
+side = "Client"
+
+[profiles.survival.pack_source.Git]
+url = "https://example.com/pack.git"
+
+[profiles.server]
+mods_folder = "/games/server/server-mods"
+side = "Server"
+
+[profiles.server.pack_source.Local]
+path = "/packs/server"
+
+[profiles.current]
+instance_folder = "/games/current"
+side = "Both"
+
+[profiles.current.pack_source.Local]
+path = "/packs/current"
+"#,
+    )
+    .unwrap();
+
+    let survival = data.get_profile("survival").unwrap();
+    assert_eq!(survival.instance_folder, PathBuf::from("/games/survival"));
+    assert_eq!(survival.mods_dir_name, None);
+    assert_eq!(survival.mods_dir(), PathBuf::from("/games/survival/mods"));
+    // Mods folders with other names are still installed to
+    let server = data.get_profile("server").unwrap();
+    assert_eq!(server.instance_folder, PathBuf::from("/games/server"));
+    assert_eq!(
+        server.mods_dir(),
+        PathBuf::from("/games/server/server-mods")
+    );
+    let current = data.get_profile("current").unwrap();
+    assert_eq!(current.instance_folder, PathBuf::from("/games/current"));
+
+    // Profiles are saved in the current format
+    let saved = toml::to_string(&data).unwrap();
+    assert!(!saved.contains("mods_folder"));
+    assert!(Data::from_toml_str(&saved).is_ok());
+}
@@ -0,0 +1,378 @@
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{FileRole, FileSource, PinnedMod, ProviderError, ProviderErrorKind, SideSupport};
+
+const CURSEFORGE_API_URL: &str = "https://api.curseforge.com";
+
+/// Environment variable holding the key to use the CurseForge api with. CurseForge rejects requests without one
+pub(crate) const CURSEFORGE_API_KEY_ENV: &str = "CURSEFORGE_API_KEY";
+
+/// Seed CurseForge hashes files with to get their fingerprints
+const FINGERPRINT_SEED: u32 = 1;
+
+pub struct CurseForge {
+    client: reqwest::Client,
+    api_url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CurseForgeResponse<T> {
+    data: T,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct FingerprintMatches {
+    exact_matches: Vec<FingerprintMatch>,
+}
+
+#[derive(Deserialize, Debug)]
+struct FingerprintMatch {
+    file: CurseForgeFile,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CurseForgeFile {
+    mod_id: u64,
+    display_name: String,
+    file_name: String,
+    /// Unset if the mod's author doesn't allow third party downloads of the file
+    download_url: Option<String>,
+    #[serde(default)]
+    hashes: Vec<FileHash>,
+    /// 1 for releases, 2 for betas and 3 for alphas
+    release_type: u8,
+    file_fingerprint: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct FileHash {
+    value: String,
+    /// 1 for sha1 and 2 for md5
+    algo: u8,
+}
+
+#[derive(Deserialize, Debug)]
+struct CurseForgeMod {
+    id: u64,
+    slug: String,
+    #[serde(default)]
+    links: ModLinks,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct ModLinks {
+    website_url: Option<String>,
+    source_url: Option<String>,
+}
+
+/// A file CurseForge recognised by its fingerprint
+pub struct IdentifiedFile {
+    /// Slug of the CurseForge project the file belongs to
+    pub slug: String,
+    pub pinned_mod: PinnedMod,
+}
+
+impl CurseForge {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Use a different CurseForge api, eg. a local test server
+    #[cfg(test)]
+    pub(crate) fn with_api_url(api_url: &str) -> Self {
+        Self {
+            api_url: api_url.into(),
+            api_key: Some("test-key".into()),
+            ..Default::default()
+        }
+    }
+
+    async fn post_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> Result<T> {
+        let Some(api_key) = &self.api_key else {
+            return Err(ProviderError::new(
+                ProviderErrorKind::Auth,
+                anyhow::format_err!(
+                    "Set {CURSEFORGE_API_KEY_ENV} to a CurseForge api key to use the CurseForge api"
+                ),
+            )
+            .into());
+        };
+        let url = format!("{}{path}", self.api_url);
+        let response = self
+            .client
+            .post(&url)
+            .header("x-api-key", api_key)
+            .json(&body)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ProviderError::new(
+                ProviderErrorKind::from_status(status),
+                anyhow::format_err!("Request to {url} failed with status {status}"),
+            )
+            .into());
+        }
+        Ok(response.json::<CurseForgeResponse<T>>().await?.data)
+    }
+
+    /// Look up files by their fingerprints, returning the files CurseForge recognises keyed by fingerprint
+    ///
+    /// Files that CurseForge doesn't allow to be downloaded by third parties are left out, since they can't be pinned
+    pub async fn identify_files(
+        &self,
+        fingerprints: &[u32],
+    ) -> Result<BTreeMap<u32, IdentifiedFile>> {
+        if fingerprints.is_empty() {
+            return Ok(Default::default());
+        }
+        let matches: FingerprintMatches = self
+            .post_json(
+                "/v1/fingerprints",
+                serde_json::json!({ "fingerprints": fingerprints }),
+            )
+            .await?;
+        let mod_ids: BTreeSet<u64> = matches
+            .exact_matches
+            .iter()
+            .map(|fingerprint_match| fingerprint_match.file.mod_id)
+            .collect();
+        if mod_ids.is_empty() {
+            return Ok(Default::default());
+        }
+        let mods: BTreeMap<u64, CurseForgeMod> = self
+            .post_json::<Vec<CurseForgeMod>>("/v1/mods", serde_json::json!({ "modIds": mod_ids }))
+            .await?
+            .into_iter()
+            .map(|curseforge_mod| (curseforge_mod.id, curseforge_mod))
+            .collect();
+
+        let mut identified_files = BTreeMap::new();
+        for file in matches
+            .exact_matches
+            .into_iter()
+            .map(|fingerprint_match| fingerprint_match.file)
+        {
+            let Some(curseforge_mod) = mods.get(&file.mod_id) else {
+                anyhow::bail!(
+                    "CurseForge didn't return the project {} of file {}",
+                    file.mod_id,
+                    file.file_name
+                )
+            };
+            let Some(pinned_mod) = pinned_mod(&file, curseforge_mod) else {
+                eprintln!(
+                    "Cannot pin {} from CurseForge, since its author doesn't allow it to be downloaded by other apps",
+                    file.file_name
+                );
+                continue;
+            };
+            identified_files.insert(
+                file.file_fingerprint,
+                IdentifiedFile {
+                    slug: curseforge_mod.slug.clone(),
+                    pinned_mod,
+                },
+            );
+        }
+        Ok(identified_files)
+    }
+}
+
+impl Default for CurseForge {
+    fn default() -> Self {
+        Self {
+            client: Default::default(),
+            api_url: CURSEFORGE_API_URL.into(),
+            api_key: std::env::var(CURSEFORGE_API_KEY_ENV)
+                .ok()
+                .filter(|api_key| !api_key.is_empty()),
+        }
+    }
+}
+
+/// Pin a CurseForge file, if it can be downloaded
+fn pinned_mod(file: &CurseForgeFile, curseforge_mod: &CurseForgeMod) -> Option<PinnedMod> {
+    let url = file.download_url.clone()?;
+    let hashes = file
+        .hashes
+        .iter()
+        .filter(|hash| hash.algo == 1)
+        .map(|hash| ("sha1".to_string(), hash.value.to_ascii_lowercase()))
+        .collect();
+    Some(PinnedMod {
+        source: vec![FileSource::Download {
+            url,
+            hashes,
+            filename: file.file_name.clone(),
+            role: FileRole::Primary,
+            signature: None,
+        }],
+        version: file.display_name.clone(),
+        deps: None,
+        // CurseForge doesn't say which sides mods are needed on
+        server_side: SideSupport::Required,
+        client_side: SideSupport::Required,
+        project_id: Some(curseforge_mod.id.to_string()),
+        version_type: match file.release_type {
+            1 => Some("release".into()),
+            2 => Some("beta".into()),
+            3 => Some("alpha".into()),
+            _ => None,
+        },
+        load_after: Default::default(),
+        source_url: curseforge_mod
+            .links
+            .source_url
+            .clone()
+            .or_else(|| curseforge_mod.links.website_url.clone()),
+    })
+}
+
+/// Get the CurseForge fingerprint of a file: the murmur2 hash of its contents, ignoring whitespace bytes
+pub fn fingerprint(contents: &[u8]) -> u32 {
+    let contents: Vec<u8> = contents
+        .iter()
+        .copied()
+        .filter(|byte| !matches!(byte, b'\t' | b'\n' | b'\r' | b' '))
+        .collect();
+    murmur2(&contents, FINGERPRINT_SEED)
+}
+
+/// 32 bit MurmurHash2
+fn murmur2(data: &[u8], seed: u32) -> u32 {
+    const M: u32 = 0x5bd1e995;
+    let mut h = seed ^ data.len() as u32;
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in chunks.by_ref() {
+        let mut k = u32::from_le_bytes(chunk.try_into().expect("Chunks should be 4 bytes long"));
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M) ^ k;
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u32) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^ (h >> 15)
+}
+
+#[test]
+fn test_murmur2() {
+    let seed = 3242157231;
+    assert_eq!(murmur2(b"", seed), 3632506080);
+    assert_eq!(murmur2(b"a", seed), 455683869);
+    assert_eq!(murmur2(b"ab", seed), 2448092234);
+    assert_eq!(murmur2(b"abc", seed), 2066295634);
+    assert_eq!(murmur2(b"abcd", seed), 2588571162);
+    assert_eq!(murmur2(b"abcde", seed), 2988696942);
+    assert_eq!(murmur2(b"abcdefghijklmnop", seed), 2350868870);
+}
+
+#[test]
+fn test_fingerprint_ignores_whitespace() {
+    assert_eq!(fingerprint(b"abcde"), murmur2(b"abcde", 1));
+    assert_eq!(fingerprint(b" a\tb\r\nc d e\n"), fingerprint(b"abcde"));
+    assert_ne!(fingerprint(b"abcde"), fingerprint(b"abcdf"));
+}
+
+#[tokio::test]
+async fn test_identify_files() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        if !request.contains("x-api-key: test-key") {
+            MockResponse::json_status("403 Forbidden", "{}")
+        } else if request.starts_with("POST /v1/fingerprints ") {
+            MockResponse::json(
+                r#"{"data": {"isCacheBuilt": true, "exactMatches": [
+                    {"id": 238222, "file": {"id": 4712384, "modId": 238222, "displayName": "JEI 15.2.0.27",
+                        "fileName": "jei-1.20.1-forge-15.2.0.27.jar",
+                        "downloadUrl": "https://edge.forgecdn.net/files/4712/384/jei-1.20.1-forge-15.2.0.27.jar",
+                        "hashes": [{"value": "ABCDEF0123", "algo": 1}, {"value": "0f0f", "algo": 2}],
+                        "releaseType": 1, "fileFingerprint": 1234}},
+                    {"id": 32274, "file": {"id": 4567, "modId": 32274, "displayName": "JourneyMap 5.9.7",
+                        "fileName": "journeymap-1.20.1-5.9.7-forge.jar", "downloadUrl": null,
+                        "hashes": [], "releaseType": 2, "fileFingerprint": 5678}}
+                ], "unmatchedFingerprints": [9999]}}"#,
+            )
+        } else if request.starts_with("POST /v1/mods ") {
+            MockResponse::json(
+                r#"{"data": [
+                    {"id": 238222, "slug": "jei", "links": {"websiteUrl": "https://www.curseforge.com/minecraft/mc-mods/jei",
+                        "sourceUrl": "https://github.com/mezz/JustEnoughItems"}},
+                    {"id": 32274, "slug": "journeymap", "links": {}}
+                ]}"#,
+            )
+        } else {
+            MockResponse::json_status("404 Not Found", "{}")
+        }
+    })
+    .await;
+
+    let curseforge = CurseForge::with_api_url(&format!("http://{addr}"));
+    let identified_files = curseforge
+        .identify_files(&[1234, 5678, 9999])
+        .await
+        .unwrap();
+    // Files that can't be downloaded and unrecognised fingerprints are left out
+    assert_eq!(identified_files.keys().collect::<Vec<_>>(), [&1234]);
+    let jei = &identified_files[&1234];
+    assert_eq!(jei.slug, "jei");
+    assert_eq!(jei.pinned_mod.version, "JEI 15.2.0.27");
+    assert_eq!(jei.pinned_mod.project_id.as_deref(), Some("238222"));
+    assert_eq!(jei.pinned_mod.version_type.as_deref(), Some("release"));
+    assert_eq!(
+        jei.pinned_mod.source_url.as_deref(),
+        Some("https://github.com/mezz/JustEnoughItems")
+    );
+    let FileSource::Download {
+        url,
+        hashes,
+        filename,
+        ..
+    } = &jei.pinned_mod.source[0]
+    else {
+        panic!("JEI should be pinned to a download")
+    };
+    assert_eq!(
+        url,
+        "https://edge.forgecdn.net/files/4712/384/jei-1.20.1-forge-15.2.0.27.jar"
+    );
+    assert_eq!(filename, "jei-1.20.1-forge-15.2.0.27.jar");
+    assert_eq!(
+        hashes,
+        &BTreeMap::from([("sha1".to_string(), "abcdef0123".to_string())])
+    );
+
+    // Requests without an api key are never sent
+    let no_key = CurseForge {
+        api_key: None,
+        ..CurseForge::with_api_url(&format!("http://{addr}"))
+    };
+    let Err(e) = no_key.identify_files(&[1234]).await else {
+        panic!("Looking up files without an api key should fail")
+    };
+    assert_eq!(ProviderError::kind_of(&e), ProviderErrorKind::Auth);
+}
@@ -0,0 +1,179 @@
+use anyhow::Result;
+use reqwest::{
+    header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+const HTTP_CACHE_DIR_NAME: &str = "http-cache";
+
+static HTTP_CACHE_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable the on-disk http cache for the rest of the process
+pub fn set_enabled(enabled: bool) {
+    HTTP_CACHE_ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// An on-disk cache of http responses, revalidated with `ETag`/`Last-Modified` on each request
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+}
+
+impl HttpCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Get the http cache in the config directory, unless the cache has been disabled
+    pub fn from_config_dir() -> Option<Self> {
+        if !HTTP_CACHE_ENABLED.load(Ordering::SeqCst) {
+            return None;
+        }
+        crate::profiles::Data::get_config_folder_path()
+            .ok()
+            .map(|config_dir| Self::new(config_dir.join(HTTP_CACHE_DIR_NAME)))
+    }
+
+    /// Get the directory cached responses are stored in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        let mut hasher = Sha1::new();
+        hasher.update(url.as_bytes());
+        self.dir.join(format!("{:x}.json", hasher.finalize()))
+    }
+
+    fn load_entry(&self, url: &str) -> Option<CachedResponse> {
+        let contents = std::fs::read_to_string(self.entry_path(url)).ok()?;
+        serde_json::from_str::<CachedResponse>(&contents)
+            .ok()
+            .filter(|entry| entry.url == url)
+    }
+
+    fn save_entry(&self, entry: &CachedResponse) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        crate::transaction::write_atomic(
+            &self.entry_path(&entry.url),
+            serde_json::to_string(entry)?.as_bytes(),
+        )
+    }
+
+    /// Send a request, skipping the response body download if the cached copy is still current
+    ///
+    /// The response status is returned along with the body, with a revalidated cached copy returned as
+    /// `200 OK`. Only successful responses are cached
+    pub async fn fetch(
+        &self,
+        client: &reqwest::Client,
+        mut request: reqwest::Request,
+    ) -> Result<(StatusCode, String)> {
+        let url = request.url().to_string();
+        let cached = self.load_entry(&url);
+        if let Some(cached) = &cached {
+            let headers = request.headers_mut();
+            if let Some(etag) = cached
+                .etag
+                .as_ref()
+                .and_then(|v| HeaderValue::from_str(v).ok())
+            {
+                headers.insert(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = cached
+                .last_modified
+                .as_ref()
+                .and_then(|v| HeaderValue::from_str(v).ok())
+            {
+                headers.insert(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = client.execute(request).await?;
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                return Ok((StatusCode::OK, cached.body));
+            }
+        }
+
+        let header_string = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(String::from)
+        };
+        let etag = header_string(ETAG);
+        let last_modified = header_string(LAST_MODIFIED);
+        let body = response.text().await?;
+
+        if status.is_success() && (etag.is_some() || last_modified.is_some()) {
+            let entry = CachedResponse {
+                url,
+                etag,
+                last_modified,
+                body,
+            };
+            if let Err(e) = self.save_entry(&entry) {
+                eprintln!("Warning: Failed to cache response for {}: {e}", entry.url);
+            }
+            return Ok((status, entry.body));
+        }
+        Ok((status, body))
+    }
+}
+
+#[tokio::test]
+async fn test_http_cache_revalidates_with_etag() {
+    use crate::test_server::{self, MockResponse};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    let not_modified_count = Arc::new(AtomicUsize::new(0));
+    let server_not_modified_count = not_modified_count.clone();
+    let addr = test_server::serve(move |request| {
+        if request.to_lowercase().contains("if-none-match: \"v1\"") {
+            server_not_modified_count.fetch_add(1, Ordering::SeqCst);
+            MockResponse::new("304 Not Modified", "").header("ETag: \"v1\"")
+        } else {
+            MockResponse::new("200 OK", r#"{"slug": "fabric-api"}"#).header("ETag: \"v1\"")
+        }
+    })
+    .await;
+
+    let cache_dir = tempfile::tempdir().unwrap();
+    let http_cache = HttpCache::new(cache_dir.path().to_path_buf());
+    let client = reqwest::Client::new();
+    let url = format!("http://{addr}/v2/project/fabric-api");
+
+    let (first_status, first) = http_cache
+        .fetch(&client, client.get(&url).build().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(not_modified_count.load(Ordering::SeqCst), 0);
+
+    let (second_status, second) = http_cache
+        .fetch(&client, client.get(&url).build().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(not_modified_count.load(Ordering::SeqCst), 1);
+    assert_eq!(first_status, StatusCode::OK);
+    assert_eq!(second_status, StatusCode::OK);
+    assert_eq!(first, second);
+}
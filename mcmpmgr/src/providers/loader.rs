@@ -0,0 +1,137 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::modpack::ModLoader;
+
+/// Looks up modloader versions and server installers
+#[derive(Default)]
+pub struct Loader {
+    client: reqwest::Client,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FabricVersion {
+    version: String,
+    stable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FabricLoaderVersion {
+    loader: FabricVersion,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ForgePromotions {
+    promos: BTreeMap<String, String>,
+}
+
+/// A downloaded modloader server installer
+pub struct ServerInstaller {
+    /// Filename the installer should be saved as
+    pub filename: String,
+    pub contents: Vec<u8>,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Get the latest stable (or recommended) version of a modloader for a minecraft version
+    pub async fn get_latest_version(
+        &self,
+        modloader: &ModLoader,
+        mc_version: &str,
+    ) -> Result<String> {
+        match modloader {
+            ModLoader::Fabric => {
+                let loader_versions: Vec<FabricLoaderVersion> = self
+                    .client
+                    .get(format!(
+                        "https://meta.fabricmc.net/v2/versions/loader/{mc_version}"
+                    ))
+                    .send()
+                    .await?
+                    .json()
+                    .await?;
+                loader_versions
+                    .into_iter()
+                    .find(|v| v.loader.stable)
+                    .map(|v| v.loader.version)
+                    .ok_or(anyhow::format_err!(
+                        "Cannot find a stable Fabric loader for minecraft {mc_version}"
+                    ))
+            }
+            ModLoader::Forge => {
+                let promotions: ForgePromotions = self
+                    .client
+                    .get("https://files.minecraftforge.net/net/minecraftforge/forge/promotions_slim.json")
+                    .send()
+                    .await?
+                    .json()
+                    .await?;
+                promotions
+                    .promos
+                    .get(&format!("{mc_version}-recommended"))
+                    .or(promotions.promos.get(&format!("{mc_version}-latest")))
+                    .cloned()
+                    .ok_or(anyhow::format_err!(
+                        "Cannot find a Forge version for minecraft {mc_version}"
+                    ))
+            }
+        }
+    }
+
+    /// Download the server installer (or launcher) for a modloader version
+    pub async fn download_server_installer(
+        &self,
+        modloader: &ModLoader,
+        mc_version: &str,
+        loader_version: &str,
+    ) -> Result<ServerInstaller> {
+        let (url, filename) = match modloader {
+            ModLoader::Fabric => {
+                let installer_versions: Vec<FabricVersion> = self
+                    .client
+                    .get("https://meta.fabricmc.net/v2/versions/installer")
+                    .send()
+                    .await?
+                    .json()
+                    .await?;
+                let installer_version = installer_versions
+                    .into_iter()
+                    .find(|v| v.stable)
+                    .ok_or(anyhow::format_err!("Cannot find a stable Fabric installer"))?
+                    .version;
+                (
+                    format!("https://meta.fabricmc.net/v2/versions/loader/{mc_version}/{loader_version}/{installer_version}/server/jar"),
+                    "fabric-server-launch.jar",
+                )
+            }
+            ModLoader::Forge => (
+                format!("https://maven.minecraftforge.net/net/minecraftforge/forge/{mc_version}-{loader_version}/forge-{mc_version}-{loader_version}-installer.jar"),
+                "forge-installer.jar",
+            ),
+        };
+        println!(
+            "Downloading {} server installer from {url}",
+            modloader.to_string()
+        );
+        let contents = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        Ok(ServerInstaller {
+            filename: filename.into(),
+            contents: contents.to_vec(),
+        })
+    }
+}
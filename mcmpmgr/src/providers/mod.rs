@@ -1,26 +1,312 @@
-use crate::mod_meta::ModMeta;
+use crate::{error::McmpmgrError, mod_meta::ModMeta};
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeSet, fmt::Display, path::PathBuf, str::FromStr};
+use sha2::Digest;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+};
 
+pub mod curseforge;
+pub mod http_cache;
+pub mod loader;
 pub mod modrinth;
 pub mod raw;
+pub mod trace;
+
+/// Why a provider couldn't resolve a mod
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ProviderErrorKind {
+    /// The mod, or a version of it matching the constraints, isn't available from this provider
+    NotFound,
+    /// The provider couldn't be reached or failed to respond. Retrying later might work
+    Transient,
+    /// The provider rejected the request's credentials
+    Auth,
+}
+
+impl ProviderErrorKind {
+    /// Classify a failed http response by its status code
+    pub fn from_status(status: reqwest::StatusCode) -> Self {
+        match status {
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE => Self::NotFound,
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Self::Auth,
+            _ => Self::Transient,
+        }
+    }
+}
+
+impl Display for ProviderErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderErrorKind::NotFound => write!(f, "not found"),
+            ProviderErrorKind::Transient => write!(f, "transient"),
+            ProviderErrorKind::Auth => write!(f, "auth"),
+        }
+    }
+}
+
+/// An error from a provider, classified so resolution knows whether trying the next provider makes sense
+#[derive(Debug)]
+pub struct ProviderError {
+    pub kind: ProviderErrorKind,
+    error: anyhow::Error,
+}
+
+impl ProviderError {
+    pub fn new(kind: ProviderErrorKind, error: anyhow::Error) -> Self {
+        Self { kind, error }
+    }
+
+    /// Classify an error by the http or network failure that caused it
+    pub fn classify(error: anyhow::Error) -> Self {
+        let kind = Self::kind_of(&error);
+        Self::new(kind, error)
+    }
+
+    /// Anything that isn't an http or network failure is a failure to find a suitable mod, so it counts as not found
+    pub fn kind_of(error: &anyhow::Error) -> ProviderErrorKind {
+        for cause in error.chain() {
+            if let Some(provider_error) = cause.downcast_ref::<ProviderError>() {
+                return provider_error.kind;
+            }
+            if let Some(reqwest_error) = cause.downcast_ref::<reqwest::Error>() {
+                return match reqwest_error.status() {
+                    Some(status) => ProviderErrorKind::from_status(status),
+                    None => ProviderErrorKind::Transient,
+                };
+            }
+        }
+        ProviderErrorKind::NotFound
+    }
+
+    /// The mcmpmgr error that caused this failure, if there is one
+    pub fn mcmpmgr_error(&self) -> Option<&McmpmgrError> {
+        McmpmgrError::find(&self.error)
+    }
+}
+
+impl Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+/// Hash algorithms that files can be verified with, strongest first
+pub const HASH_ALGORITHMS: [&str; 3] = ["sha512", "sha256", "sha1"];
+
+/// Get the lowercase hex hash of some contents using one of `HASH_ALGORITHMS`
+pub fn hash_hex(algorithm: &str, contents: &[u8]) -> anyhow::Result<String> {
+    let hash = match algorithm {
+        "sha1" => format!("{:x}", sha1::Sha1::digest(contents)),
+        "sha256" => format!("{:x}", sha2::Sha256::digest(contents)),
+        "sha512" => format!("{:x}", sha2::Sha512::digest(contents)),
+        _ => anyhow::bail!(
+            "Unsupported hash algorithm {algorithm}. Expected one of: {}",
+            HASH_ALGORITHMS.join(", ")
+        ),
+    };
+    Ok(hash)
+}
+
+/// Hash some contents with every supported hash algorithm
+pub fn all_hashes(contents: &[u8]) -> BTreeMap<String, String> {
+    HASH_ALGORITHMS
+        .iter()
+        .map(|algorithm| {
+            let hash = hash_hex(algorithm, contents).expect("Hash algorithm should be supported");
+            (algorithm.to_string(), hash)
+        })
+        .collect()
+}
+
+/// Get the strongest supported hash out of a map of algorithm -> hash
+pub fn strongest_hash(hashes: &BTreeMap<String, String>) -> Option<(&'static str, String)> {
+    HASH_ALGORITHMS.iter().find_map(|algorithm| {
+        hashes
+            .get(*algorithm)
+            .map(|hash| (*algorithm, hash.to_ascii_lowercase()))
+    })
+}
+
+/// Check contents against every supported hash in a map of algorithm -> hash.
+/// Hashes from unsupported algorithms are ignored, but at least one supported hash is required
+pub fn verify_hashes(hashes: &BTreeMap<String, String>, contents: &[u8]) -> anyhow::Result<()> {
+    if strongest_hash(hashes).is_none() {
+        anyhow::bail!(
+            "No supported hashes to verify against. Expected one of: {}",
+            HASH_ALGORITHMS.join(", ")
+        )
+    }
+    for (algorithm, expected) in hashes
+        .iter()
+        .filter(|(algorithm, _)| HASH_ALGORITHMS.contains(&algorithm.as_str()))
+    {
+        let actual = hash_hex(algorithm, contents)?;
+        if actual != expected.to_ascii_lowercase() {
+            return Err(McmpmgrError::HashMismatch {
+                algorithm: algorithm.clone(),
+                expected: expected.clone(),
+                actual,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// What a file in a mod version is for
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum FileRole {
+    /// The main file of the mod
+    #[default]
+    Primary,
+    /// A file the mod needs installed alongside it, eg. a separate api jar
+    Companion,
+    /// A file that is only useful for development, eg. a sources or javadoc jar. These are never installed
+    Secondary,
+}
+
+impl FileRole {
+    pub fn is_primary(&self) -> bool {
+        *self == Self::Primary
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(from = "FileSourceRepr")]
 pub enum FileSource {
     Download {
         url: String,
-        sha1: String,
-        sha512: String,
+        /// Map of hash algorithm -> lowercase hex hash of the file
+        hashes: BTreeMap<String, String>,
+        filename: String,
+        #[serde(default, skip_serializing_if = "FileRole::is_primary")]
+        role: FileRole,
+        /// Minisign signature of the file, or a url to download it from. Checked against the pack's signing key
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
+    Local {
+        path: PathBuf,
+        /// Map of hash algorithm -> lowercase hex hash of the file
+        hashes: BTreeMap<String, String>,
+        filename: String,
+        #[serde(default, skip_serializing_if = "FileRole::is_primary")]
+        role: FileRole,
+    },
+}
+
+impl FileSource {
+    /// Map of hash algorithm -> hash of the file
+    pub fn hashes(&self) -> &BTreeMap<String, String> {
+        match self {
+            FileSource::Download { hashes, .. } => hashes,
+            FileSource::Local { hashes, .. } => hashes,
+        }
+    }
+
+    pub fn hashes_mut(&mut self) -> &mut BTreeMap<String, String> {
+        match self {
+            FileSource::Download { hashes, .. } => hashes,
+            FileSource::Local { hashes, .. } => hashes,
+        }
+    }
+
+    /// Name of the file once it is installed in the mods folder
+    pub fn filename(&self) -> &str {
+        match self {
+            FileSource::Download { filename, .. } => filename,
+            FileSource::Local { filename, .. } => filename,
+        }
+    }
+
+    pub fn filename_mut(&mut self) -> &mut String {
+        match self {
+            FileSource::Download { filename, .. } => filename,
+            FileSource::Local { filename, .. } => filename,
+        }
+    }
+}
+
+/// Older lockfiles stored fixed sha1 and sha512 fields instead of a map of hashes
+#[derive(Deserialize)]
+enum FileSourceRepr {
+    Download {
+        url: String,
+        #[serde(default)]
+        hashes: BTreeMap<String, String>,
+        sha1: Option<String>,
+        sha512: Option<String>,
         filename: String,
+        #[serde(default)]
+        role: FileRole,
+        signature: Option<String>,
     },
     Local {
         path: PathBuf,
-        sha1: String,
-        sha512: String,
+        #[serde(default)]
+        hashes: BTreeMap<String, String>,
+        sha1: Option<String>,
+        sha512: Option<String>,
         filename: String,
+        #[serde(default)]
+        role: FileRole,
     },
 }
 
+/// Fold legacy hash fields into a map of hashes, skipping empty ones
+fn migrate_hashes(
+    mut hashes: BTreeMap<String, String>,
+    sha1: Option<String>,
+    sha512: Option<String>,
+) -> BTreeMap<String, String> {
+    for (algorithm, hash) in [("sha1", sha1), ("sha512", sha512)] {
+        if let Some(hash) = hash.filter(|hash| !hash.is_empty()) {
+            hashes.entry(algorithm.into()).or_insert(hash);
+        }
+    }
+    hashes
+}
+
+impl From<FileSourceRepr> for FileSource {
+    fn from(value: FileSourceRepr) -> Self {
+        match value {
+            FileSourceRepr::Download {
+                url,
+                hashes,
+                sha1,
+                sha512,
+                filename,
+                role,
+                signature,
+            } => FileSource::Download {
+                url,
+                hashes: migrate_hashes(hashes, sha1, sha512),
+                filename,
+                role,
+                signature,
+            },
+            FileSourceRepr::Local {
+                path,
+                hashes,
+                sha1,
+                sha512,
+                filename,
+                role,
+            } => FileSource::Local {
+                path,
+                hashes: migrate_hashes(hashes, sha1, sha512),
+                filename,
+                role,
+            },
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, Hash)]
 pub enum DownloadSide {
     Both,
@@ -57,6 +343,73 @@ impl Display for DownloadSide {
     }
 }
 
+/// How a mod supports being installed on a particular side
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, Hash)]
+#[serde(try_from = "SideSupportRepr")]
+pub enum SideSupport {
+    /// The mod must be installed on this side
+    Required,
+    /// The mod can be installed on this side, but doesn't need to be
+    Optional,
+    /// The mod must not be installed on this side
+    Unsupported,
+}
+
+/// Older lockfiles only stored whether or not a mod is needed on a side
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SideSupportRepr {
+    Legacy(bool),
+    Named(String),
+}
+
+impl TryFrom<SideSupportRepr> for SideSupport {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SideSupportRepr) -> Result<Self, Self::Error> {
+        match value {
+            SideSupportRepr::Legacy(supported) => Ok(supported.into()),
+            SideSupportRepr::Named(name) => Self::from_str(&name),
+        }
+    }
+}
+
+impl From<bool> for SideSupport {
+    fn from(required: bool) -> Self {
+        if required {
+            Self::Required
+        } else {
+            Self::Unsupported
+        }
+    }
+}
+
+impl FromStr for SideSupport {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "required" => Ok(SideSupport::Required),
+            "optional" => Ok(SideSupport::Optional),
+            "unsupported" => Ok(SideSupport::Unsupported),
+            _ => anyhow::bail!(
+                "Invalid side support {}. Expected one of: required, optional, unsupported",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for SideSupport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SideSupport::Required => write!(f, "Required"),
+            SideSupport::Optional => write!(f, "Optional"),
+            SideSupport::Unsupported => write!(f, "Unsupported"),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PinnedMod {
     /// Source of the files for the mod
@@ -67,7 +420,209 @@ pub struct PinnedMod {
     // pub deps: Option<BTreeSet<ModMeta>>,
     pub deps: Option<BTreeSet<ModMeta>>,
     /// Server side
-    pub server_side: bool,
+    pub server_side: SideSupport,
     /// Required on client side
-    pub client_side: bool,
+    pub client_side: SideSupport,
+    /// Provider project id the mod was resolved from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    /// Release channel of the pinned version (release, beta or alpha), if the provider has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_type: Option<String>,
+    /// Mods this mod has to be loaded after, from the mod's load order hints in the modpack
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub load_after: BTreeSet<String>,
+    /// Url of the mod's source code or homepage, for looking into a mod when auditing the pack
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+}
+
+impl PinnedMod {
+    /// Use the sides a mod has been explicitly set to install on in place of the provider's sides
+    pub fn with_sides_from(mut self, mod_meta: &ModMeta) -> Self {
+        if let Some(server_side) = mod_meta.server_side {
+            self.server_side = server_side.into();
+        }
+        if let Some(client_side) = mod_meta.client_side {
+            self.client_side = client_side.into();
+        }
+        self
+    }
+
+    /// Use the load order hints of a mod in the modpack
+    pub fn with_load_order_from(mut self, mod_meta: &ModMeta) -> Self {
+        self.load_after = mod_meta.load_after.clone();
+        self
+    }
+
+    /// Whether this mod should be downloaded for a side
+    ///
+    /// Mods that are optional on a side are skipped unless `include_optional` is set
+    pub fn should_download(&self, side: DownloadSide, include_optional: bool) -> bool {
+        let include_side = |side_support: SideSupport| match side_support {
+            SideSupport::Required => true,
+            SideSupport::Optional => include_optional,
+            SideSupport::Unsupported => false,
+        };
+        match side {
+            DownloadSide::Both => include_side(self.client_side) || include_side(self.server_side),
+            DownloadSide::Server => include_side(self.server_side),
+            DownloadSide::Client => include_side(self.client_side),
+        }
+    }
+}
+
+/// Builders for pinned mods in tests
+#[cfg(test)]
+impl PinnedMod {
+    /// A mod pinned at a version, needed on both sides, with no files or dependencies
+    pub(crate) fn test(version: &str) -> Self {
+        Self {
+            source: vec![],
+            version: version.into(),
+            deps: None,
+            server_side: SideSupport::Required,
+            client_side: SideSupport::Required,
+            project_id: None,
+            version_type: None,
+            load_after: Default::default(),
+            source_url: None,
+        }
+    }
+
+    /// Depend on mods, given as mod names with optional versions like `lib@1.0.0`
+    pub(crate) fn deps(mut self, deps: &[&str]) -> Self {
+        self.deps =
+            (!deps.is_empty()).then(|| deps.iter().map(|dep| ModMeta::new(dep).unwrap()).collect());
+        self
+    }
+
+    /// Add a primary file downloaded from a url, named after the last part of the url
+    pub(crate) fn download(mut self, url: &str, hashes: BTreeMap<String, String>) -> Self {
+        self.source.push(FileSource::Download {
+            url: url.into(),
+            hashes,
+            filename: url.rsplit('/').next().unwrap().into(),
+            role: FileRole::Primary,
+            signature: None,
+        });
+        self
+    }
+
+    pub(crate) fn project_id(mut self, project_id: &str) -> Self {
+        self.project_id = Some(project_id.into());
+        self
+    }
+
+    pub(crate) fn load_after(mut self, mod_names: &[&str]) -> Self {
+        self.load_after = mod_names.iter().map(|m| m.to_string()).collect();
+        self
+    }
+
+    pub(crate) fn sides(mut self, client_side: SideSupport, server_side: SideSupport) -> Self {
+        self.client_side = client_side;
+        self.server_side = server_side;
+        self
+    }
+}
+
+#[test]
+fn test_should_download() {
+    let pinned_mod =
+        |client_side, server_side| PinnedMod::test("1.0.0").sides(client_side, server_side);
+    use SideSupport::*;
+
+    // Mods needed everywhere
+    let both = pinned_mod(Required, Required);
+    assert!(both.should_download(DownloadSide::Client, false));
+    assert!(both.should_download(DownloadSide::Server, false));
+    assert!(both.should_download(DownloadSide::Both, false));
+
+    // Server mods with optional client side bits
+    let server_mod = pinned_mod(Optional, Required);
+    assert!(!server_mod.should_download(DownloadSide::Client, false));
+    assert!(server_mod.should_download(DownloadSide::Client, true));
+    assert!(server_mod.should_download(DownloadSide::Server, false));
+    assert!(server_mod.should_download(DownloadSide::Both, false));
+
+    // Client only mods
+    let client_mod = pinned_mod(Required, Unsupported);
+    assert!(client_mod.should_download(DownloadSide::Client, false));
+    assert!(!client_mod.should_download(DownloadSide::Server, false));
+    assert!(!client_mod.should_download(DownloadSide::Server, true));
+    assert!(client_mod.should_download(DownloadSide::Both, false));
+
+    // Mods that work anywhere but aren't needed anywhere
+    let optional_mod = pinned_mod(Optional, Optional);
+    assert!(!optional_mod.should_download(DownloadSide::Client, false));
+    assert!(!optional_mod.should_download(DownloadSide::Server, false));
+    assert!(!optional_mod.should_download(DownloadSide::Both, false));
+    assert!(optional_mod.should_download(DownloadSide::Client, true));
+    assert!(optional_mod.should_download(DownloadSide::Server, true));
+
+    let unsupported_mod = pinned_mod(Unsupported, Unsupported);
+    assert!(!unsupported_mod.should_download(DownloadSide::Both, true));
+}
+
+#[test]
+fn test_side_support_legacy_lock() {
+    #[derive(Deserialize)]
+    struct Sides {
+        client_side: SideSupport,
+        server_side: SideSupport,
+    }
+    let legacy: Sides = toml::from_str("client_side = true\nserver_side = false").unwrap();
+    assert_eq!(legacy.client_side, SideSupport::Required);
+    assert_eq!(legacy.server_side, SideSupport::Unsupported);
+
+    let current: Sides =
+        toml::from_str("client_side = \"Optional\"\nserver_side = \"Required\"").unwrap();
+    assert_eq!(current.client_side, SideSupport::Optional);
+    assert_eq!(current.server_side, SideSupport::Required);
+}
+
+#[test]
+fn test_file_source_legacy_hashes() {
+    #[derive(Serialize, Deserialize)]
+    struct Sources {
+        source: Vec<FileSource>,
+    }
+    let legacy: Sources = toml::from_str(
+        "[[source]]\n[source.Download]\nurl = \"https://example.com/mod.jar\"\nsha1 = \"abc\"\nsha512 = \"def\"\nfilename = \"mod.jar\"",
+    )
+    .unwrap();
+    assert_eq!(
+        legacy.source[0].hashes(),
+        &BTreeMap::from([
+            ("sha1".to_string(), "abc".to_string()),
+            ("sha512".to_string(), "def".to_string())
+        ])
+    );
+
+    let current: Sources = toml::from_str(&toml::to_string(&legacy).unwrap()).unwrap();
+    assert_eq!(current.source[0].hashes(), legacy.source[0].hashes());
+}
+
+#[test]
+fn test_verify_hashes() {
+    let contents = b"mod jar contents";
+    let sha256_only = BTreeMap::from([(
+        "sha256".to_string(),
+        hash_hex("sha256", contents).unwrap().to_ascii_uppercase(),
+    )]);
+    assert!(verify_hashes(&sha256_only, contents).is_ok());
+    assert!(verify_hashes(&sha256_only, b"something else").is_err());
+
+    assert!(verify_hashes(&all_hashes(contents), contents).is_ok());
+    // Every supported hash is checked, not just the strongest
+    let mut bad_sha1 = all_hashes(contents);
+    bad_sha1.insert("sha1".into(), "0".repeat(40));
+    let error = verify_hashes(&bad_sha1, contents).err().unwrap();
+    assert!(matches!(
+        McmpmgrError::find(&error),
+        Some(McmpmgrError::HashMismatch { algorithm, .. }) if algorithm == "sha1"
+    ));
+
+    let unsupported_only = BTreeMap::from([("md5".to_string(), "abc".to_string())]);
+    assert!(verify_hashes(&unsupported_only, contents).is_err());
 }
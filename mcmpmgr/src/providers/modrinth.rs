@@ -1,23 +1,60 @@
 use anyhow::{Error, Result};
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
-use super::PinnedMod;
+use super::{
+    http_cache::HttpCache,
+    trace::{RejectedVersion, ResolutionAttempt, ResolutionTracer},
+    PinnedMod, ProviderError, ProviderErrorKind, SideSupport,
+};
 use crate::{
+    error::McmpmgrError,
     mod_meta::{ModMeta, ModProvider},
-    modpack::{ModLoader, ModpackMeta},
-    providers::FileSource,
+    modpack::{ModLoader, ModpackMeta, VersionSelection},
+    providers::{FileRole, FileSource},
 };
 
+/// Maximum number of ids to send in a single batched request, to keep urls a reasonable length
+const BATCH_SIZE: usize = 100;
+
+const MODRINTH_API_URL: &str = "https://api.modrinth.com";
+
+/// Environment variable to use a different Modrinth api, eg. a self-hosted mirror
+pub(crate) const MODRINTH_API_URL_ENV: &str = "MODRINTH_API_URL";
+
 pub struct Modrinth {
     client: reqwest::Client,
+    api_url: String,
+    /// Response bodies for requests already made in this run, keyed by the full request url
+    cache: Arc<Mutex<BTreeMap<String, String>>>,
+    /// Responses kept between runs
+    http_cache: Option<HttpCache>,
+    tracer: Arc<ResolutionTracer>,
+    /// Used instead of the pack's version selection if set
+    version_selection: Option<VersionSelection>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct ModrinthProject {
+    id: String,
     slug: String,
     client_side: String,
     server_side: String,
+    #[serde(default)]
+    source_url: Option<String>,
+}
+
+impl ModrinthProject {
+    /// Check whether a project reference (as accepted by the Modrinth api) refers to this project
+    ///
+    /// Project ids are matched exactly, but slugs are case insensitive
+    fn is_ref(&self, project_ref: &str) -> bool {
+        self.id == project_ref || self.slug.eq_ignore_ascii_case(project_ref)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -28,17 +65,13 @@ struct VersionDeps {
     version_id: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct VersionHashes {
-    sha1: String,
-    sha512: String,
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 struct VersionFiles {
-    // file_type: String,
+    /// Modrinth's kind of non-primary file, eg. required-resource-pack or sources-jar
+    file_type: Option<String>,
     filename: String,
-    hashes: VersionHashes,
+    /// Map of hash algorithm -> hash
+    hashes: BTreeMap<String, String>,
     primary: bool,
     // size: i64,
     url: String,
@@ -51,12 +84,85 @@ struct ModrinthProjectVersion {
     dependencies: Option<Vec<VersionDeps>>,
     // downloads: i64,
     files: Vec<VersionFiles>,
-    // loaders: Vec<String>,
+    loaders: Vec<String>,
+    game_versions: Vec<String>,
     // name: String,
-    // project_id: String,
+    project_id: String,
     id: String,
     version_number: String,
-    // version_type: String,
+    /// One of release, beta or alpha
+    #[serde(default = "release_version_type")]
+    version_type: String,
+    changelog: Option<String>,
+}
+
+fn release_version_type() -> String {
+    "release".into()
+}
+
+impl ModrinthProjectVersion {
+    /// Check whether the version lists any of the given minecraft versions
+    fn supports_game_version(&self, game_versions: &[String]) -> bool {
+        game_versions.iter().any(|v| self.game_versions.contains(v))
+    }
+}
+
+/// Builders for project versions in tests
+#[cfg(test)]
+impl ModrinthProjectVersion {
+    /// A fabric release for 1.20.1 with no files, dependencies or changelog
+    fn test(version_number: &str) -> Self {
+        Self {
+            date_published: "2024-01-01T00:00:00Z".into(),
+            dependencies: None,
+            files: vec![],
+            loaders: vec!["fabric".into()],
+            game_versions: vec!["1.20.1".into()],
+            project_id: "AAAA".into(),
+            id: version_number.into(),
+            version_number: version_number.into(),
+            version_type: release_version_type(),
+            changelog: None,
+        }
+    }
+
+    /// Set the version type, one of release, beta or alpha
+    fn version_type(mut self, version_type: &str) -> Self {
+        self.version_type = version_type.into();
+        self
+    }
+
+    fn changelog(mut self, changelog: &str) -> Self {
+        self.changelog = Some(changelog.into());
+        self
+    }
+}
+
+/// The changelog of a version of a mod
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionChangelog {
+    pub version: String,
+    pub changelog: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ModrinthCollection {
+    /// Ids of the projects in the collection
+    projects: Vec<String>,
+}
+
+/// A project found by searching Modrinth
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModrinthSearchResult {
+    pub slug: String,
+    pub title: String,
+    pub description: String,
+    pub downloads: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ModrinthSearchResponse {
+    hits: Vec<ModrinthSearchResult>,
 }
 
 impl Modrinth {
@@ -66,16 +172,198 @@ impl Modrinth {
         }
     }
 
+    /// Use a different Modrinth api, eg. a local test server
+    #[cfg(test)]
+    pub(crate) fn with_api_url(api_url: &str) -> Self {
+        Self {
+            api_url: api_url.into(),
+            http_cache: None,
+            ..Default::default()
+        }
+    }
+
+    /// Record resolution attempts to a tracer
+    pub fn set_tracer(&mut self, tracer: Arc<ResolutionTracer>) {
+        self.tracer = tracer;
+    }
+
+    /// Pick mod versions with `version_selection`, whatever the pack's version selection is
+    pub fn set_version_selection(&mut self, version_selection: VersionSelection) {
+        self.version_selection = Some(version_selection);
+    }
+
+    /// Whether to pick the newest or oldest compatible version of mods in a pack
+    fn version_selection(&self, pack_meta: &ModpackMeta) -> VersionSelection {
+        self.version_selection
+            .unwrap_or(pack_meta.version_selection)
+    }
+
     async fn get_project(&self, project_id: &str) -> Result<ModrinthProject> {
-        let project: ModrinthProject = self
+        self.get_json(&format!("/v2/project/{project_id}"), &[])
+            .await
+    }
+
+    /// Get the minecraft versions a version of a project supports
+    pub async fn get_game_versions(
+        &self,
+        project_id: &str,
+        version_number: &str,
+        pack_meta: &ModpackMeta,
+    ) -> Result<Vec<String>> {
+        self.get_project_versions(project_id, pack_meta, true, None, None)
+            .await?
+            .into_iter()
+            .find(|version| version.version_number == version_number)
+            .map(|version| version.game_versions)
+            .ok_or(anyhow::format_err!(
+                "Cannot find version {version_number} of Modrinth project {project_id}"
+            ))
+    }
+
+    /// Check whether a project with a slug or id exists
+    pub async fn project_exists(&self, project_id: &str) -> Result<bool> {
+        match self.get_project(project_id).await {
+            Ok(_) => Ok(true),
+            Err(e)
+                if e.downcast_ref::<ProviderError>()
+                    .is_some_and(|e| e.kind == ProviderErrorKind::NotFound) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get a response from the Modrinth api, reusing the response if the same request was already made in this run
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T> {
+        let request = self
             .client
-            .get(format!("https://api.modrinth.com/v2/project/{project_id}"))
-            .send()
+            .get(format!("{}{path}", self.api_url))
+            .query(query)
+            .build()?;
+        let url = request.url().to_string();
+
+        let cached_body = self
+            .cache
+            .lock()
+            .expect("Modrinth cache lock should not be poisoned")
+            .get(&url)
+            .cloned();
+        let body = match cached_body {
+            Some(body) => {
+                self.tracer.record_request(&url, None);
+                body
+            }
+            None => {
+                let (status, body) = match &self.http_cache {
+                    Some(http_cache) => http_cache.fetch(&self.client, request).await?,
+                    None => {
+                        let response = self.client.execute(request).await?;
+                        (response.status(), response.text().await?)
+                    }
+                };
+                self.tracer.record_request(&url, Some(status.as_u16()));
+                if !status.is_success() {
+                    return Err(ProviderError::new(
+                        ProviderErrorKind::from_status(status),
+                        anyhow::format_err!("Request to {url} failed with status {status}"),
+                    )
+                    .into());
+                }
+                self.cache
+                    .lock()
+                    .expect("Modrinth cache lock should not be poisoned")
+                    .insert(url, body.clone());
+                body
+            }
+        };
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Get several projects in as few requests as possible. Projects that don't exist are left out
+    async fn get_projects(&self, project_ids: &[String]) -> Result<Vec<ModrinthProject>> {
+        let mut projects = vec![];
+        for project_ids in project_ids.chunks(BATCH_SIZE) {
+            let batch: Vec<ModrinthProject> = self
+                .get_json(
+                    "/v2/projects",
+                    &[("ids", serde_json::to_string(project_ids)?)],
+                )
+                .await?;
+            projects.extend(batch);
+        }
+        Ok(projects)
+    }
+
+    /// Get several versions by id in as few requests as possible. Versions that don't exist are left out
+    async fn get_versions(&self, version_ids: &[String]) -> Result<Vec<ModrinthProjectVersion>> {
+        let mut versions = vec![];
+        for version_ids in version_ids.chunks(BATCH_SIZE) {
+            let batch: Vec<ModrinthProjectVersion> = self
+                .get_json(
+                    "/v2/versions",
+                    &[("ids", serde_json::to_string(version_ids)?)],
+                )
+                .await?;
+            versions.extend(batch);
+        }
+        Ok(versions)
+    }
+
+    /// Get the slugs of every project in a collection
+    pub async fn get_collection_mods(&self, collection_id: &str) -> Result<Vec<String>> {
+        let collection: ModrinthCollection = self
+            .get_json(&format!("/v3/collection/{collection_id}"), &[])
+            .await?;
+        Ok(self
+            .get_projects(&collection.projects)
             .await?
-            .json()
+            .into_iter()
+            .map(|project| project.slug)
+            .collect())
+    }
+
+    /// Find the current download for a file by its sha1 or sha512 hash
+    pub async fn get_file_by_hash(&self, algorithm: &str, hash: &str) -> Result<FileSource> {
+        let version: ModrinthProjectVersion = self
+            .get_json(
+                &format!("/v2/version_file/{hash}"),
+                &[("algorithm", algorithm.into())],
+            )
             .await?;
+        version
+            .files
+            .iter()
+            .find(|f| f.hashes.get(algorithm).is_some_and(|h| h == hash))
+            .map(|f| f.file_source())
+            .ok_or(anyhow::format_err!("No file with {algorithm} {hash} found"))
+    }
 
-        Ok(project)
+    /// Search for mods compatible with the modpack's modloader and minecraft version
+    pub async fn search(
+        &self,
+        query: &str,
+        pack_meta: &ModpackMeta,
+    ) -> Result<Vec<ModrinthSearchResult>> {
+        let version_facets: Vec<String> = pack_meta
+            .game_versions(None)
+            .iter()
+            .map(|v| format!("versions:{v}"))
+            .collect();
+        let facets = format!(
+            "[[\"project_type:mod\"],[\"categories:{}\"],{}]",
+            pack_meta.modloader.to_string().to_lowercase(),
+            serde_json::to_string(&version_facets)?
+        );
+        let search_response: ModrinthSearchResponse = self
+            .get_json("/v2/search", &[("query", query.into()), ("facets", facets)])
+            .await?;
+
+        Ok(search_response.hits)
     }
 
     pub async fn get_mod_meta(
@@ -99,19 +387,12 @@ impl Modrinth {
 
         for version in project_versions.iter() {
             if project_version.is_none() || project_version.unwrap_or("*") == version.id {
-                let mut mod_meta = ModMeta::new(&project_slug)?
-                    .provider(ModProvider::Modrinth)
-                    .version(&version.version_number.to_string());
-
-                if let Some(loader) = loader_override {
-                    mod_meta.loader = Some(loader.clone());
-                }
-
-                if let Some(mc_version) = game_version_override {
-                    mod_meta = mod_meta.mc_version(&mc_version);
-                }
-
-                return Ok(mod_meta);
+                return dep_mod_meta(
+                    &project_slug,
+                    &version.version_number,
+                    loader_override,
+                    game_version_override,
+                );
             }
         }
         anyhow::bail!(
@@ -122,21 +403,168 @@ impl Modrinth {
     }
 
     /// Resolve a list of mod candidates in order of newest to oldest
-    pub async fn resolve(&self, mod_meta: &ModMeta, pack_meta: &ModpackMeta) -> Result<PinnedMod> {
+    pub async fn resolve(
+        &self,
+        mod_meta: &ModMeta,
+        pack_meta: &ModpackMeta,
+    ) -> Result<PinnedMod, ProviderError> {
+        self.resolve_batch(&[mod_meta], pack_meta)
+            .await
+            .pop()
+            .expect("There should be a result for each mod")
+    }
+
+    /// Resolve several mods at once, batching the project and dependency lookups across all of them
+    ///
+    /// Results are returned in the same order as `mods`
+    pub async fn resolve_batch(
+        &self,
+        mods: &[&ModMeta],
+        pack_meta: &ModpackMeta,
+    ) -> Vec<Result<PinnedMod, ProviderError>> {
+        let project_refs: Vec<String> = mods.iter().map(|m| m.project_ref().into()).collect();
+        let projects = match self.get_projects(&project_refs).await {
+            Ok(projects) => projects,
+            Err(e) => {
+                let kind = ProviderError::kind_of(&e);
+                return mods
+                    .iter()
+                    .map(|_| {
+                        Err(ProviderError::new(
+                            kind,
+                            anyhow::format_err!("Failed to look up projects: {e}"),
+                        ))
+                    })
+                    .collect();
+            }
+        };
+
+        // Mods can be named by either slug or project id
+        let mut packages = vec![];
+        for mod_meta in mods.iter() {
+            let package = match projects.iter().find(|p| p.is_ref(mod_meta.project_ref())) {
+                Some(project) => self
+                    .get_package(mod_meta, pack_meta)
+                    .await
+                    .map(|package| (project, package)),
+                None => Err(anyhow::format_err!(
+                    "Cannot find Modrinth project {}",
+                    mod_meta.name
+                )),
+            };
+            packages.push(package);
+        }
+
+        let deps = packages
+            .iter()
+            .filter_map(|package| package.as_ref().ok())
+            .flat_map(|(_, package)| required_deps(package));
+        let dep_project_ids: Vec<String> = deps
+            .clone()
+            .map(|dep| dep.project_id.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let dep_version_ids: Vec<String> = deps
+            .filter_map(|dep| dep.version_id.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        // Deps that can't be batched are looked up individually instead
+        let dep_projects: BTreeMap<String, ModrinthProject> = self
+            .get_projects(&dep_project_ids)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| (p.id.clone(), p))
+            .collect();
+        let dep_versions: BTreeMap<String, ModrinthProjectVersion> = self
+            .get_versions(&dep_version_ids)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|v| (v.id.clone(), v))
+            .collect();
+
+        let mut results = vec![];
+        for (mod_meta, package) in mods.iter().zip(packages) {
+            let result = match package {
+                Ok((project, package)) => {
+                    match self
+                        .get_deps_meta(mod_meta, &package, pack_meta, &dep_projects, &dep_versions)
+                        .await
+                    {
+                        Ok(deps_meta) => Ok(pinned_mod(mod_meta, &package, project, deps_meta)),
+                        Err(e) => Err(e),
+                    }
+                }
+                Err(e) => Err(e),
+            }
+            .map_err(ProviderError::classify);
+            self.tracer.complete_attempt(
+                &mod_meta.name,
+                &mod_meta.version,
+                ModProvider::Modrinth,
+                match &result {
+                    Ok(pinned_mod) => Ok(&pinned_mod.version),
+                    Err(e) => Err(e.to_string()),
+                },
+            );
+            results.push(result);
+        }
+        results
+    }
+
+    /// Get the version of a mod that should be pinned, enforcing the modpack's strict minecraft version if set
+    async fn get_package(
+        &self,
+        mod_meta: &ModMeta,
+        pack_meta: &ModpackMeta,
+    ) -> Result<ModrinthProjectVersion> {
+        let package = self.select_package(mod_meta, pack_meta).await?;
+        let game_versions = pack_meta.game_versions(None);
+        if pack_meta.strict_mc_version && !package.supports_game_version(&game_versions) {
+            return Err(McmpmgrError::VersionConflict {
+                name: mod_meta.name.clone(),
+                version: package.version_number,
+                required: game_versions.join(" or "),
+                supported: package.game_versions.join(", "),
+            }
+            .into());
+        }
+        Ok(package)
+    }
+
+    /// Pick the version of a mod matching its version constraint
+    async fn select_package(
+        &self,
+        mod_meta: &ModMeta,
+        pack_meta: &ModpackMeta,
+    ) -> Result<ModrinthProjectVersion> {
         let versions = self
             .get_project_versions(
-                &mod_meta.name,
+                mod_meta.project_ref(),
                 pack_meta,
                 false,
                 mod_meta.loader.clone(),
                 mod_meta.mc_version.clone(),
             )
             .await?;
+        if self.tracer.is_enabled() {
+            self.trace_versions(mod_meta, pack_meta, &versions).await;
+        }
 
-        let package = if mod_meta.version == "*" {
-            let version = versions.first();
+        if mod_meta.version == "*" {
+            let version = select_version(mod_meta, &versions, self.version_selection(pack_meta))
+                .and_then(|selected| versions.into_iter().nth(selected));
             if let Some(version) = version {
-                version
+                if version.version_type != "release" && !mod_meta.include_prerelease {
+                    println!(
+                        "No release of {} is available. Falling back to {} version {}",
+                        mod_meta.name, version.version_type, version.version_number
+                    );
+                }
+                Ok(version)
             } else {
                 anyhow::bail!(
                     "Cannot find package {} for loader={} and mc version={}",
@@ -147,21 +575,128 @@ impl Modrinth {
             }
         } else {
             let version = versions
-                .iter()
+                .into_iter()
                 .filter(|v| v.version_number == mod_meta.version)
                 .nth(0);
 
             if let Some(version) = version {
-                version
+                Ok(version)
             } else {
                 anyhow::bail!("Cannot find package {}@{}", mod_meta.name, mod_meta.version)
             }
+        }
+    }
+
+    /// Record why each version of a mod would not be picked
+    async fn trace_versions(
+        &self,
+        mod_meta: &ModMeta,
+        pack_meta: &ModpackMeta,
+        candidates: &[ModrinthProjectVersion],
+    ) {
+        let loader = mod_meta
+            .loader
+            .clone()
+            .unwrap_or(pack_meta.modloader.clone())
+            .to_string()
+            .to_lowercase();
+        let game_versions = pack_meta.game_versions(mod_meta.mc_version.as_deref());
+        let version_selection = self.version_selection(pack_meta);
+        let selected = select_version(mod_meta, candidates, version_selection)
+            .map(|selected| &candidates[selected]);
+        let not_selected_reason = match version_selection {
+            VersionSelection::Newest => "not the newest compatible version",
+            VersionSelection::Oldest => "not the oldest compatible version",
         };
 
+        let mut attempt =
+            ResolutionAttempt::new(&mod_meta.name, &mod_meta.version, ModProvider::Modrinth);
+        attempt.candidate_versions = candidates.len();
+        // Every version is fetched so that versions for the wrong loader or game version can be explained too
+        let all_versions = self
+            .get_project_versions(mod_meta.project_ref(), pack_meta, true, None, None)
+            .await
+            .unwrap_or_default();
+        for version in all_versions.iter() {
+            if selected.is_some_and(|s| s.id == version.id) {
+                continue;
+            }
+            let mut reasons = vec![];
+            if !version.loaders.contains(&loader) {
+                reasons.push(format!(
+                    "wrong loader (needs {loader}, supports {})",
+                    version.loaders.join(", ")
+                ));
+            }
+            if !version.supports_game_version(&game_versions) {
+                reasons.push(format!(
+                    "wrong game version (needs {})",
+                    game_versions.join(" or ")
+                ));
+            }
+            if !version.files.iter().any(|f| f.primary) {
+                reasons.push("no primary file".into());
+            }
+            if mod_meta.version != "*" && version.version_number != mod_meta.version {
+                reasons.push(format!(
+                    "does not match version constraint {}",
+                    mod_meta.version
+                ));
+            } else if reasons.is_empty() && selected.is_some_and(|s| s.version_type == "release") {
+                if version.version_type == "release" {
+                    reasons.push(not_selected_reason.into());
+                } else {
+                    reasons.push(format!(
+                        "{} version, and releases are preferred",
+                        version.version_type
+                    ));
+                }
+            } else if reasons.is_empty() {
+                reasons.push(not_selected_reason.into());
+            }
+            attempt.rejected_versions.push(RejectedVersion {
+                version: version.version_number.clone(),
+                reasons,
+            });
+        }
+        attempt.requests = self.tracer.take_requests();
+        self.tracer.record_attempt(attempt);
+    }
+
+    /// Get the metadata for the required dependencies of a mod version
+    ///
+    /// Deps found in the prefetched projects and versions are used directly, and anything else is looked up individually
+    async fn get_deps_meta(
+        &self,
+        mod_meta: &ModMeta,
+        package: &ModrinthProjectVersion,
+        pack_meta: &ModpackMeta,
+        dep_projects: &BTreeMap<String, ModrinthProject>,
+        dep_versions: &BTreeMap<String, ModrinthProjectVersion>,
+    ) -> Result<BTreeSet<ModMeta>> {
+        let loader = mod_meta
+            .loader
+            .clone()
+            .unwrap_or(pack_meta.modloader.clone())
+            .to_string()
+            .to_lowercase();
+        let game_versions = pack_meta.game_versions(mod_meta.mc_version.as_deref());
+
         let mut deps_meta = BTreeSet::new();
-        if let Some(deps) = &package.dependencies {
-            for dep in deps.iter().filter(|dep| dep.dependency_type == "required") {
-                deps_meta.insert(
+        for dep in required_deps(package) {
+            let prefetched_version = dep
+                .version_id
+                .as_ref()
+                .and_then(|version_id| dep_versions.get(version_id))
+                .filter(|v| v.loaders.contains(&loader) && v.supports_game_version(&game_versions));
+            let dep_meta = match (dep_projects.get(&dep.project_id), prefetched_version) {
+                (Some(project), Some(version)) => dep_mod_meta(
+                    &project.slug,
+                    &version.version_number,
+                    mod_meta.loader.clone(),
+                    mod_meta.mc_version.clone(),
+                )?,
+                _ => {
                     self.get_mod_meta(
                         &dep.project_id,
                         dep.version_id.as_deref(),
@@ -169,42 +704,39 @@ impl Modrinth {
                         mod_meta.loader.clone(),
                         mod_meta.mc_version.clone(),
                     )
-                    .await?,
-                );
-            }
+                    .await?
+                }
+            };
+            deps_meta.insert(dep_meta);
         }
+        Ok(deps_meta)
+    }
 
-        let project = self.get_project(&mod_meta.name).await?;
-
-        Ok(PinnedMod {
-            source: package
-                .files
-                .iter()
-                .filter(|f| f.primary)
-                .map(|f| FileSource::Download {
-                    url: f.url.clone(),
-                    sha1: f.hashes.sha1.clone(),
-                    sha512: f.hashes.sha512.clone(),
-                    filename: f.filename.clone(),
-                })
-                .collect(),
-            version: package.version_number.clone(),
-            deps: if package
-                .dependencies
-                .as_ref()
-                .is_some_and(|deps| deps.len() > 0)
-            {
-                Some(deps_meta)
-            } else {
-                None
-            },
-            server_side: mod_meta
-                .server_side
-                .unwrap_or(project.server_side != "unsupported"),
-            client_side: mod_meta
-                .client_side
-                .unwrap_or(project.client_side != "unsupported"),
-        })
+    /// Get the changelogs of a project's versions after `from_version`, up to and including `to_version`, newest first
+    pub async fn get_changelogs(
+        &self,
+        project_id: &str,
+        mod_meta: &ModMeta,
+        pack_meta: &ModpackMeta,
+        from_version: &str,
+        to_version: &str,
+    ) -> Result<Vec<VersionChangelog>> {
+        let versions = self
+            .get_project_versions(
+                project_id,
+                pack_meta,
+                false,
+                mod_meta.loader.clone(),
+                mod_meta.mc_version.clone(),
+            )
+            .await?;
+        Ok(versions_between(&versions, from_version, to_version)
+            .iter()
+            .map(|version| VersionChangelog {
+                version: version.version_number.clone(),
+                changelog: version.changelog.clone(),
+            })
+            .collect())
     }
 
     async fn get_project_versions(
@@ -219,25 +751,18 @@ impl Modrinth {
             .unwrap_or(pack_meta.modloader.clone())
             .to_string()
             .to_lowercase();
-        let game_version = game_version_override.unwrap_or(pack_meta.mc_version.clone());
+        let game_versions = pack_meta.game_versions(game_version_override.as_deref());
         let query_vec = if ignore_game_version_and_loader {
-            &vec![]
+            vec![]
         } else {
-            &vec![
+            vec![
                 ("loaders", format!("[\"{}\"]", loader)),
-                ("game_versions", format!("[\"{}\"]", game_version)),
+                ("game_versions", serde_json::to_string(&game_versions)?),
             ]
         };
 
         let mut project_versions: Vec<ModrinthProjectVersion> = self
-            .client
-            .get(format!(
-                "https://api.modrinth.com/v2/project/{mod_id}/version"
-            ))
-            .query(query_vec)
-            .send()
-            .await?
-            .json()
+            .get_json(&format!("/v2/project/{mod_id}/version"), &query_vec)
             .await?;
         project_versions.sort_by_key(|v| v.date_published.clone());
         project_versions.reverse();
@@ -246,10 +771,592 @@ impl Modrinth {
     }
 }
 
+/// Get the Modrinth api url to use, given the value of `$MODRINTH_API_URL`
+fn api_url(env_api_url: Option<String>) -> String {
+    env_api_url
+        .map(|api_url| api_url.trim().trim_end_matches('/').to_string())
+        .filter(|api_url| !api_url.is_empty())
+        .unwrap_or(MODRINTH_API_URL.into())
+}
+
+/// Pick the index of the version to pin out of versions sorted newest first.
+///
+/// Releases are preferred unless the mod includes prereleases, falling back to prereleases if there is no release.
+/// The newest or oldest of those is picked according to `version_selection`
+fn select_version(
+    mod_meta: &ModMeta,
+    versions: &[ModrinthProjectVersion],
+    version_selection: VersionSelection,
+) -> Option<usize> {
+    if mod_meta.version != "*" {
+        return versions
+            .iter()
+            .position(|v| v.version_number == mod_meta.version);
+    }
+    let mut candidates: Vec<usize> = (0..versions.len())
+        .filter(|i| mod_meta.include_prerelease || versions[*i].version_type == "release")
+        .collect();
+    if candidates.is_empty() {
+        candidates = (0..versions.len()).collect();
+    }
+    match version_selection {
+        VersionSelection::Newest => candidates.first().copied(),
+        VersionSelection::Oldest => candidates.last().copied(),
+    }
+}
+
+/// Get the versions after `from_version` up to and including `to_version`, out of versions sorted newest first
+///
+/// Only `to_version` is included if `from_version` isn't available, eg. since it was for another minecraft version
+fn versions_between<'a>(
+    versions: &'a [ModrinthProjectVersion],
+    from_version: &str,
+    to_version: &str,
+) -> &'a [ModrinthProjectVersion] {
+    let Some(to_index) = versions.iter().position(|v| v.version_number == to_version) else {
+        return &[];
+    };
+    let from_index = versions[to_index..]
+        .iter()
+        .position(|v| v.version_number == from_version)
+        .map_or(to_index + 1, |from_index| to_index + from_index);
+    &versions[to_index..from_index]
+}
+
+/// Get the dependencies of a mod version that must be installed alongside it
+fn required_deps(package: &ModrinthProjectVersion) -> impl Iterator<Item = &VersionDeps> + Clone {
+    package
+        .dependencies
+        .iter()
+        .flatten()
+        .filter(|dep| dep.dependency_type == "required")
+}
+
+/// Build the metadata for a dependency, carrying over the dependent mod's loader and minecraft version overrides
+fn dep_mod_meta(
+    project_slug: &str,
+    version_number: &str,
+    loader_override: Option<ModLoader>,
+    game_version_override: Option<String>,
+) -> Result<ModMeta> {
+    let mut mod_meta = ModMeta::new(project_slug)?
+        .provider(ModProvider::Modrinth)
+        .version(version_number);
+
+    if let Some(loader) = loader_override {
+        mod_meta.loader = Some(loader.clone());
+    }
+
+    if let Some(mc_version) = game_version_override {
+        mod_meta = mod_meta.mc_version(&mc_version);
+    }
+
+    Ok(mod_meta)
+}
+
+impl VersionFiles {
+    /// Work out what a file is for. Modrinth doesn't always set a file type, so development jars are
+    /// also recognised by their usual filename suffixes
+    fn role(&self) -> FileRole {
+        if self.primary {
+            return FileRole::Primary;
+        }
+        let development_file = matches!(
+            self.file_type.as_deref(),
+            Some(
+                "sources-jar" | "dev-jar" | "javadoc-jar" | "signature" | "optional-resource-pack"
+            )
+        );
+        let file_stem = self.filename.to_lowercase();
+        let file_stem = file_stem.strip_suffix(".jar").unwrap_or(&file_stem);
+        if development_file
+            || ["-sources", "-javadoc", "-dev"]
+                .iter()
+                .any(|suffix| file_stem.ends_with(suffix))
+        {
+            FileRole::Secondary
+        } else {
+            FileRole::Companion
+        }
+    }
+
+    fn file_source(&self) -> FileSource {
+        FileSource::Download {
+            url: self.url.clone(),
+            hashes: self.hashes.clone(),
+            filename: self.filename.clone(),
+            role: self.role(),
+            signature: None,
+        }
+    }
+}
+
+/// Get the files of a version to install, keeping companion files like api jars but not development files
+fn version_sources(package: &ModrinthProjectVersion) -> Vec<FileSource> {
+    package
+        .files
+        .iter()
+        .filter(|f| f.role() != FileRole::Secondary)
+        .map(VersionFiles::file_source)
+        .collect()
+}
+
+fn pinned_mod(
+    mod_meta: &ModMeta,
+    package: &ModrinthProjectVersion,
+    project: &ModrinthProject,
+    deps_meta: BTreeSet<ModMeta>,
+) -> PinnedMod {
+    PinnedMod {
+        source: version_sources(package),
+        version: package.version_number.clone(),
+        version_type: Some(package.version_type.clone()),
+        deps: if package
+            .dependencies
+            .as_ref()
+            .is_some_and(|deps| !deps.is_empty())
+        {
+            Some(deps_meta)
+        } else {
+            None
+        },
+        server_side: mod_meta.server_side.map_or(
+            // Modrinth uses "unknown" for projects that haven't specified their side support
+            SideSupport::from_str(&project.server_side).unwrap_or(SideSupport::Required),
+            SideSupport::from,
+        ),
+        client_side: mod_meta.client_side.map_or(
+            SideSupport::from_str(&project.client_side).unwrap_or(SideSupport::Required),
+            SideSupport::from,
+        ),
+        project_id: Some(project.id.clone()),
+        load_after: Default::default(),
+        // Projects without a source url still have a page on Modrinth
+        source_url: Some(
+            project
+                .source_url
+                .clone()
+                .unwrap_or_else(|| format!("https://modrinth.com/project/{}", project.slug)),
+        ),
+    }
+}
+
 impl Default for Modrinth {
     fn default() -> Self {
         Self {
             client: Default::default(),
+            api_url: api_url(std::env::var(MODRINTH_API_URL_ENV).ok()),
+            cache: Default::default(),
+            http_cache: HttpCache::from_config_dir(),
+            tracer: Default::default(),
+            version_selection: None,
+        }
+    }
+}
+
+#[test]
+fn test_api_url() {
+    assert_eq!(api_url(None), "https://api.modrinth.com");
+    assert_eq!(api_url(Some("".into())), "https://api.modrinth.com");
+    assert_eq!(
+        api_url(Some("https://modrinth.example.com/".into())),
+        "https://modrinth.example.com"
+    );
+}
+
+#[tokio::test]
+async fn test_repeated_lookups_are_cached() {
+    use crate::test_server::{self, MockResponse};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let request_count = Arc::new(AtomicUsize::new(0));
+    let server_request_count = request_count.clone();
+    let addr = test_server::serve(move |request| {
+        server_request_count.fetch_add(1, Ordering::SeqCst);
+        let body = if request.starts_with("GET /v2/project/fabric-api/version") {
+            "[]"
+        } else {
+            r#"{"id": "P7dR8mSH", "slug": "fabric-api", "client_side": "required", "server_side": "required"}"#
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    let pack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+
+    for _ in 0..3 {
+        assert_eq!(
+            modrinth.get_project("fabric-api").await.unwrap().slug,
+            "fabric-api"
+        );
+    }
+    assert_eq!(request_count.load(Ordering::SeqCst), 1);
+
+    for _ in 0..3 {
+        modrinth
+            .get_project_versions("fabric-api", &pack_meta, false, None, None)
+            .await
+            .unwrap();
+    }
+    assert_eq!(request_count.load(Ordering::SeqCst), 2);
+
+    // A different query is a different request
+    modrinth
+        .get_project_versions("fabric-api", &pack_meta, false, None, Some("1.21".into()))
+        .await
+        .unwrap();
+    assert_eq!(request_count.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_resolve_classifies_errors() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let (status, body) = if request.contains("locked-mod") {
+            ("401 Unauthorized", "{}")
+        } else if request.contains("flaky-mod") {
+            ("503 Service Unavailable", "{}")
+        } else {
+            ("200 OK", "[]")
+        };
+        MockResponse::json_status(status, body)
+    })
+    .await;
+
+    let modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    let pack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+    let kind_of = |mod_name: &str| {
+        let mod_meta = ModMeta::new(mod_name).unwrap();
+        let modrinth = &modrinth;
+        let pack_meta = &pack_meta;
+        async move {
+            modrinth
+                .resolve(&mod_meta, pack_meta)
+                .await
+                .err()
+                .unwrap()
+                .kind
         }
+    };
+
+    assert_eq!(kind_of("missing-mod").await, ProviderErrorKind::NotFound);
+    assert_eq!(kind_of("locked-mod").await, ProviderErrorKind::Auth);
+    assert_eq!(kind_of("flaky-mod").await, ProviderErrorKind::Transient);
+}
+
+#[tokio::test]
+async fn test_strict_mc_version_rejects_wrong_version_deps() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let body = if request.starts_with("GET /v2/projects") {
+            r#"[{"id": "AAAA", "slug": "old-dep", "client_side": "required", "server_side": "required"}]"#
+        } else {
+            r#"[{"date_published": "2023-01-01T00:00:00Z", "dependencies": [], "files": [], "loaders": ["fabric"], "game_versions": ["1.19.2"], "project_id": "AAAA", "id": "BBBB", "version_number": "1.0.0"}]"#
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    // Deps carry over the minecraft version override of the mod depending on them
+    let dep_meta = dep_mod_meta("old-dep", "1.0.0", None, Some("1.19.2".into())).unwrap();
+    let pack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+    assert!(modrinth.resolve(&dep_meta, &pack_meta).await.is_ok());
+
+    let strict_pack_meta = pack_meta.strict_mc_version(true);
+    let error = modrinth
+        .resolve(&dep_meta, &strict_pack_meta)
+        .await
+        .err()
+        .unwrap();
+    assert!(error
+        .to_string()
+        .contains("does not support minecraft version 1.20.1"));
+    assert!(matches!(
+        error.mcmpmgr_error(),
+        Some(McmpmgrError::VersionConflict { required, .. }) if required == "1.20.1"
+    ));
+}
+
+#[test]
+fn test_select_version_prefers_releases() {
+    let version = |version_number: &str, version_type: &str| {
+        ModrinthProjectVersion::test(version_number).version_type(version_type)
+    };
+    let mod_meta = ModMeta::new("test-mod").unwrap();
+
+    let newest = VersionSelection::Newest;
+    let versions = vec![version("2.0.0-beta", "beta"), version("1.0.0", "release")];
+    assert_eq!(select_version(&mod_meta, &versions, newest), Some(1));
+    let prerelease_meta = mod_meta.clone().include_prerelease(true);
+    assert_eq!(select_version(&prerelease_meta, &versions, newest), Some(0));
+
+    // Prereleases are still used if there is no release
+    let versions = vec![version("2.0.0-alpha", "alpha")];
+    assert_eq!(select_version(&mod_meta, &versions, newest), Some(0));
+    assert_eq!(select_version(&mod_meta, &[], newest), None);
+}
+
+#[test]
+fn test_select_oldest_version() {
+    let version = |version_number: &str, version_type: &str| {
+        ModrinthProjectVersion::test(version_number).version_type(version_type)
+    };
+    let mod_meta = ModMeta::new("test-mod").unwrap();
+    let versions = vec![
+        version("3.0.0-beta", "beta"),
+        version("2.0.0", "release"),
+        version("1.0.0", "release"),
+        version("0.1.0-alpha", "alpha"),
+    ];
+    assert_eq!(
+        select_version(&mod_meta, &versions, VersionSelection::Newest),
+        Some(1)
+    );
+    assert_eq!(
+        select_version(&mod_meta, &versions, VersionSelection::Oldest),
+        Some(2)
+    );
+    let prerelease_meta = mod_meta.clone().include_prerelease(true);
+    assert_eq!(
+        select_version(&prerelease_meta, &versions, VersionSelection::Oldest),
+        Some(3)
+    );
+    // Version constraints are followed whatever the selection
+    assert_eq!(
+        select_version(
+            &mod_meta.clone().version("2.0.0"),
+            &versions,
+            VersionSelection::Oldest
+        ),
+        Some(1)
+    );
+    // Prereleases are still used if there is no release
+    let versions = vec![
+        version("2.0.0-beta", "beta"),
+        version("1.0.0-alpha", "alpha"),
+    ];
+    assert_eq!(
+        select_version(&mod_meta, &versions, VersionSelection::Oldest),
+        Some(1)
+    );
+}
+
+#[tokio::test]
+async fn test_resolve_version_selection() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let version = |id: &str, version_number: &str, date_published: &str| {
+            format!(
+                r#"{{"date_published": "{date_published}", "dependencies": [], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "AAAA", "id": "{id}", "version_number": "{version_number}", "version_type": "release"}}"#
+            )
+        };
+        let body = if request.starts_with("GET /v2/projects?") {
+            r#"[{"id": "AAAA", "slug": "the-mod", "client_side": "required", "server_side": "required"}]"#.to_string()
+        } else if request.contains("/version") {
+            format!(
+                "[{}, {}, {}]",
+                version("V2", "2.0.0", "2024-02-01T00:00:00Z"),
+                version("V3", "3.0.0", "2024-03-01T00:00:00Z"),
+                version("V1", "1.0.0", "2024-01-01T00:00:00Z")
+            )
+        } else {
+            "[]".into()
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let mut modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    let mod_meta = ModMeta::new("the-mod").unwrap();
+    let mut pack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+    let pinned_mod = modrinth.resolve(&mod_meta, &pack_meta).await.unwrap();
+    assert_eq!(pinned_mod.version, "3.0.0");
+    pack_meta.version_selection = VersionSelection::Oldest;
+    let pinned_mod = modrinth.resolve(&mod_meta, &pack_meta).await.unwrap();
+    assert_eq!(pinned_mod.version, "1.0.0");
+
+    // The pack's selection can be overridden, eg. by `--oldest`
+    pack_meta.version_selection = VersionSelection::Newest;
+    modrinth.set_version_selection(VersionSelection::Oldest);
+    let pinned_mod = modrinth.resolve(&mod_meta, &pack_meta).await.unwrap();
+    assert_eq!(pinned_mod.version, "1.0.0");
+}
+
+#[test]
+fn test_versions_between() {
+    let versions: Vec<ModrinthProjectVersion> = ["4.0.0", "3.0.0", "2.0.0", "1.0.0"]
+        .iter()
+        .map(|version_number| {
+            ModrinthProjectVersion::test(version_number)
+                .changelog(&format!("Changes in {version_number}"))
+        })
+        .collect();
+    let version_numbers = |from_version: &str, to_version: &str| -> Vec<String> {
+        versions_between(&versions, from_version, to_version)
+            .iter()
+            .map(|v| v.version_number.clone())
+            .collect()
+    };
+
+    assert_eq!(version_numbers("1.0.0", "3.0.0"), ["3.0.0", "2.0.0"]);
+    assert_eq!(version_numbers("3.0.0", "4.0.0"), ["4.0.0"]);
+    assert!(version_numbers("3.0.0", "3.0.0").is_empty());
+    // An old version that isn't listed only gives the new version's changelog
+    assert_eq!(version_numbers("0.1.0", "3.0.0"), ["3.0.0"]);
+    assert!(version_numbers("1.0.0", "5.0.0").is_empty());
+    assert_eq!(
+        versions_between(&versions, "3.0.0", "4.0.0")[0]
+            .changelog
+            .as_deref(),
+        Some("Changes in 4.0.0")
+    );
+}
+
+#[tokio::test]
+async fn test_get_collection_mods() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let body = if request.starts_with("GET /v3/collection/AbCdEfGh") {
+            r#"{"id": "AbCdEfGh", "name": "Farming", "projects": ["AAAA", "BBBB"]}"#
+        } else {
+            r#"[{"id": "AAAA", "slug": "farmers-delight", "client_side": "required", "server_side": "required"}, {"id": "BBBB", "slug": "croptopia", "client_side": "required", "server_side": "required"}]"#
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    assert_eq!(
+        modrinth.get_collection_mods("AbCdEfGh").await.unwrap(),
+        vec!["farmers-delight", "croptopia"]
+    );
+}
+
+#[tokio::test]
+async fn test_resolve_dep_by_id() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let dep_project = r#"{"id": "DDDD", "slug": "the-dep", "client_side": "required", "server_side": "required"}"#;
+        let body = if request.starts_with("GET /v2/projects?") {
+            if request.contains("needs-dep") {
+                r#"[{"id": "NNNN", "slug": "needs-dep", "client_side": "required", "server_side": "required"}]"#.to_string()
+            } else {
+                format!("[{dep_project}]")
+            }
+        } else if request.starts_with("GET /v2/versions?") {
+            // Deps that can't be prefetched are looked up by their project id instead
+            "[]".into()
+        } else if request.starts_with("GET /v2/project/needs-dep/version") {
+            r#"[{"date_published": "2024-01-01T00:00:00Z", "dependencies": [{"dependency_type": "required", "project_id": "DDDD", "version_id": "VVVV"}], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "NNNN", "id": "MMMM", "version_number": "1.0.0"}]"#.into()
+        } else if request.contains("/version") {
+            r#"[{"date_published": "2024-01-01T00:00:00Z", "dependencies": [], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "DDDD", "id": "VVVV", "version_number": "2.0.0"}]"#.into()
+        } else {
+            dep_project.into()
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    let pack_meta = ModpackMeta::new("test", "1.20.1", ModLoader::Fabric);
+
+    let pinned_mod = modrinth
+        .resolve(&ModMeta::new("needs-dep").unwrap(), &pack_meta)
+        .await
+        .unwrap();
+    let dep = pinned_mod.deps.unwrap().pop_first().unwrap();
+    assert_eq!(
+        (dep.name.as_str(), dep.version.as_str()),
+        ("the-dep", "2.0.0")
+    );
+
+    // The dep resolves the same whether it is referred to by its slug (in any case) or its id
+    for project_ref in ["the-dep", "The-Dep", "DDDD"] {
+        let pinned_dep = modrinth
+            .resolve(&ModMeta::new(project_ref).unwrap(), &pack_meta)
+            .await
+            .unwrap();
+        assert_eq!(pinned_dep.version, "2.0.0");
     }
 }
+
+#[test]
+fn test_version_sources_keep_companions() {
+    let file = |filename: &str, primary: bool, file_type: Option<&str>| {
+        serde_json::json!({
+            "filename": filename,
+            "hashes": {"sha1": "abc"},
+            "primary": primary,
+            "url": format!("https://cdn.modrinth.com/{filename}"),
+            "file_type": file_type,
+        })
+    };
+    let version: ModrinthProjectVersion = serde_json::from_value(serde_json::json!({
+        "date_published": "2024-01-01T00:00:00Z",
+        "files": [
+            file("fancy-mod-1.0.0.jar", true, None),
+            file("fancy-mod-api-1.0.0.jar", false, None),
+            file("fancy-mod-1.0.0-sources.jar", false, None),
+            file("fancy-mod-1.0.0-docs.jar", false, Some("javadoc-jar")),
+            file("fancy-resources.zip", false, Some("required-resource-pack")),
+        ],
+        "loaders": ["forge"],
+        "game_versions": ["1.20.1"],
+        "project_id": "AAAA",
+        "id": "BBBB",
+        "version_number": "1.0.0",
+    }))
+    .unwrap();
+
+    let sources: Vec<(String, FileRole)> = version_sources(&version)
+        .into_iter()
+        .map(|source| match source {
+            FileSource::Download { filename, role, .. } => (filename, role),
+            FileSource::Local { .. } => panic!("Modrinth files should be downloads"),
+        })
+        .collect();
+    assert_eq!(
+        sources,
+        [
+            ("fancy-mod-1.0.0.jar".to_string(), FileRole::Primary),
+            ("fancy-mod-api-1.0.0.jar".to_string(), FileRole::Companion),
+            ("fancy-resources.zip".to_string(), FileRole::Companion),
+        ]
+    );
+}
+
+#[test]
+fn test_pinned_mod_source_url() {
+    let package: ModrinthProjectVersion = serde_json::from_str(
+        r#"{"date_published": "2024-01-01T00:00:00Z", "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "AAAA", "id": "V1", "version_number": "1.0.0"}"#,
+    )
+    .unwrap();
+    let project: ModrinthProject = serde_json::from_str(
+        r#"{"id": "AAAA", "slug": "the-mod", "client_side": "required", "server_side": "required", "source_url": "https://github.com/example/the-mod"}"#,
+    )
+    .unwrap();
+    let mod_meta = ModMeta::new("the-mod").unwrap();
+    let pinned = pinned_mod(&mod_meta, &package, &project, BTreeSet::new());
+    assert_eq!(
+        pinned.source_url.as_deref(),
+        Some("https://github.com/example/the-mod")
+    );
+
+    // Projects without a source url link to their Modrinth page instead
+    let project: ModrinthProject = serde_json::from_str(
+        r#"{"id": "AAAA", "slug": "the-mod", "client_side": "required", "server_side": "required", "source_url": null}"#,
+    )
+    .unwrap();
+    let pinned = pinned_mod(&mod_meta, &package, &project, BTreeSet::new());
+    assert_eq!(
+        pinned.source_url.as_deref(),
+        Some("https://modrinth.com/project/the-mod")
+    );
+}
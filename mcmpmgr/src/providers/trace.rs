@@ -0,0 +1,174 @@
+use serde::Serialize;
+use std::sync::Mutex;
+
+use crate::mod_meta::ModProvider;
+
+/// An http request made while resolving a mod
+#[derive(Serialize, Debug, Clone)]
+pub struct TracedRequest {
+    pub url: String,
+    /// Status of the response, or None if the response was reused from earlier in the run
+    pub status: Option<u16>,
+}
+
+/// A version of a mod that was not picked, and why
+#[derive(Serialize, Debug, Clone)]
+pub struct RejectedVersion {
+    pub version: String,
+    pub reasons: Vec<String>,
+}
+
+/// A record of a single provider's attempt at resolving a mod
+#[derive(Serialize, Debug, Clone)]
+pub struct ResolutionAttempt {
+    pub mod_name: String,
+    pub constraint: String,
+    pub provider: ModProvider,
+    pub requests: Vec<TracedRequest>,
+    /// Number of versions compatible with the loader and minecraft version
+    pub candidate_versions: usize,
+    pub rejected_versions: Vec<RejectedVersion>,
+    pub pinned_version: Option<String>,
+    pub error: Option<String>,
+}
+
+impl ResolutionAttempt {
+    pub fn new(mod_name: &str, constraint: &str, provider: ModProvider) -> Self {
+        Self {
+            mod_name: mod_name.into(),
+            constraint: constraint.into(),
+            provider,
+            requests: vec![],
+            candidate_versions: 0,
+            rejected_versions: vec![],
+            pinned_version: None,
+            error: None,
+        }
+    }
+}
+
+/// Collects a trace of every provider attempt made while resolving mods. Does nothing unless enabled
+#[derive(Default)]
+pub struct ResolutionTracer {
+    enabled: bool,
+    requests: Mutex<Vec<TracedRequest>>,
+    attempts: Mutex<Vec<ResolutionAttempt>>,
+}
+
+impl ResolutionTracer {
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn record_request(&self, url: &str, status: Option<u16>) {
+        if self.enabled {
+            self.requests
+                .lock()
+                .expect("Tracer lock should not be poisoned")
+                .push(TracedRequest {
+                    url: url.into(),
+                    status,
+                });
+        }
+    }
+
+    /// Take the requests made since the last call, so they can be attached to an attempt
+    pub fn take_requests(&self) -> Vec<TracedRequest> {
+        std::mem::take(
+            &mut *self
+                .requests
+                .lock()
+                .expect("Tracer lock should not be poisoned"),
+        )
+    }
+
+    pub fn record_attempt(&self, attempt: ResolutionAttempt) {
+        if self.enabled {
+            self.attempts
+                .lock()
+                .expect("Tracer lock should not be poisoned")
+                .push(attempt);
+        }
+    }
+
+    /// Record the outcome of the latest unfinished attempt for a mod, or a new attempt if there isn't one
+    pub fn complete_attempt(
+        &self,
+        mod_name: &str,
+        constraint: &str,
+        provider: ModProvider,
+        outcome: Result<&str, String>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let mut attempts = self
+            .attempts
+            .lock()
+            .expect("Tracer lock should not be poisoned");
+        let attempt = match attempts.iter_mut().rev().find(|a| {
+            a.mod_name == mod_name
+                && a.provider == provider
+                && a.pinned_version.is_none()
+                && a.error.is_none()
+        }) {
+            Some(attempt) => attempt,
+            None => {
+                let mut attempt = ResolutionAttempt::new(mod_name, constraint, provider);
+                attempt.requests = self.take_requests();
+                attempts.push(attempt);
+                attempts.last_mut().expect("An attempt was just added")
+            }
+        };
+        match outcome {
+            Ok(version) => attempt.pinned_version = Some(version.into()),
+            Err(e) => attempt.error = Some(e),
+        }
+    }
+
+    pub fn take_attempts(&self) -> Vec<ResolutionAttempt> {
+        std::mem::take(
+            &mut *self
+                .attempts
+                .lock()
+                .expect("Tracer lock should not be poisoned"),
+        )
+    }
+}
+
+#[test]
+fn test_resolution_tracer() {
+    let disabled = ResolutionTracer::default();
+    disabled.record_request("https://example.com", Some(200));
+    disabled.complete_attempt("sodium", "*", ModProvider::Modrinth, Ok("1.0.0"));
+    assert!(disabled.take_requests().is_empty());
+    assert!(disabled.take_attempts().is_empty());
+
+    let tracer = ResolutionTracer::enabled();
+    tracer.record_request("https://example.com/sodium", Some(200));
+    let mut attempt = ResolutionAttempt::new("sodium", "*", ModProvider::Modrinth);
+    attempt.requests = tracer.take_requests();
+    tracer.record_attempt(attempt);
+    tracer.complete_attempt("sodium", "*", ModProvider::Modrinth, Ok("1.0.0"));
+    tracer.record_request("https://example.com/missing", Some(404));
+    tracer.complete_attempt(
+        "missing",
+        "*",
+        ModProvider::Modrinth,
+        Err("Not found".into()),
+    );
+
+    let attempts = tracer.take_attempts();
+    assert_eq!(attempts.len(), 2);
+    assert_eq!(attempts[0].pinned_version.as_deref(), Some("1.0.0"));
+    assert_eq!(attempts[0].requests.len(), 1);
+    assert_eq!(attempts[1].error.as_deref(), Some("Not found"));
+    assert_eq!(attempts[1].requests[0].status, Some(404));
+}
@@ -1,144 +1,959 @@
-use anyhow::Result;
-use reqwest::{header::CONTENT_DISPOSITION, Url};
+use anyhow::{Context, Result};
+use reqwest::{
+    header::{CONTENT_DISPOSITION, RANGE},
+    StatusCode, Url,
+};
 use serde::{Deserialize, Serialize};
-use sha1::Sha1;
 use sha2::{Digest, Sha512};
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     ffi::{OsStr, OsString},
+    fmt::Display,
     path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
 };
+use tokio::{io::AsyncWriteExt, sync::mpsc::UnboundedSender};
 
 use crate::{
-    mod_meta::{ModMeta, ModProvider},
-    modpack::ModpackMeta,
-    providers::{modrinth::Modrinth, DownloadSide, FileSource, PinnedMod},
+    error::McmpmgrError,
+    ignore::IgnoreRules,
+    mod_meta::{ModMeta, ModProvider, ProjectKind},
+    modpack::{ModpackMeta, VersionSelection},
+    providers::{
+        all_hashes,
+        curseforge::{self, CurseForge},
+        loader::Loader,
+        modrinth::{Modrinth, ModrinthSearchResult, VersionChangelog},
+        strongest_hash,
+        trace::{ResolutionAttempt, ResolutionTracer},
+        verify_hashes, DownloadSide, FileRole, FileSource, PinnedMod, ProviderError,
+        ProviderErrorKind, HASH_ALGORITHMS,
+    },
+    transaction::{auto_recover, move_aside_malformed, write_atomic},
 };
 
-const MODPACK_LOCK_FILENAME: &str = "modpack.lock";
+pub(crate) const MODPACK_LOCK_FILENAME: &str = "modpack.lock";
+
+/// Extension added to files while they are being downloaded
+const PART_EXTENSION: &str = ".part";
+
+/// How many times to try a download, resuming from where the last attempt got to
+const DOWNLOAD_ATTEMPTS: usize = 3;
+
+/// How many similarly named mods to suggest when a mod can't be found
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Name of the folder in the config directory that downloaded mod files are kept in between runs
+const DOWNLOAD_CACHE_DIR_NAME: &str = "download-cache";
+
+/// Get the lowercase hex sha512 hash of some file contents
+pub(crate) fn sha512_hex(contents: &[u8]) -> String {
+    let mut hasher = Sha512::new();
+    hasher.update(contents);
+    format!("{:X}", hasher.finalize()).to_ascii_lowercase()
+}
+
+/// Which versions of a mod's dependencies get pinned alongside it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyVersions {
+    /// Pin the exact dependency versions that the mod's pinned version declares
+    Exact,
+    /// Pin the latest version of each dependency that supports the pack (ie. record the dependencies as `*`)
+    #[default]
+    Latest,
+}
+
+impl DependencyVersions {
+    /// Get the version of a dependency to pin according to this policy
+    pub fn apply(self, dep: ModMeta) -> ModMeta {
+        match self {
+            Self::Exact => dep,
+            Self::Latest => dep.version("*"),
+        }
+    }
+}
+
+impl FromStr for DependencyVersions {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "exact" => Ok(Self::Exact),
+            "latest" => Ok(Self::Latest),
+            _ => anyhow::bail!("Invalid dependency versions {s}. Expected one of: exact, latest"),
+        }
+    }
+}
+
+impl Display for DependencyVersions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact => write!(f, "exact"),
+            Self::Latest => write!(f, "latest"),
+        }
+    }
+}
+
+/// A progress update for a mod download
+#[derive(Debug, Clone)]
+pub struct DownloadProgress {
+    /// Number of mod files that have been processed
+    pub current: usize,
+    /// Total number of mod files to process
+    pub total: usize,
+    /// Name of the mod file being processed
+    pub filename: String,
+}
+
+/// Mod files already downloaded, so that they can be copied instead of downloaded again
+///
+/// Files downloaded during this run are remembered in memory. A cache with a directory also keeps a copy
+/// of every downloaded file there, named by its hash, so that later runs can reuse them
+#[derive(Debug, Default, Clone)]
+pub struct DownloadCache {
+    /// Map of (hash algorithm, hash) -> path of a downloaded file with that hash
+    files: Arc<Mutex<BTreeMap<(String, String), PathBuf>>>,
+    /// Directory downloaded files are kept in between runs
+    dir: Option<PathBuf>,
+}
+
+/// What pruning the download cache deleted
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CachePruneSummary {
+    pub removed_files: usize,
+    pub reclaimed_bytes: u64,
+}
+
+impl DownloadCache {
+    /// Get a download cache that keeps downloaded files in a directory between runs
+    pub fn in_dir(dir: PathBuf) -> Self {
+        Self {
+            files: Default::default(),
+            dir: Some(dir),
+        }
+    }
+
+    /// Get the download cache in the config directory, or one that only lasts for this run if there isn't one
+    pub fn from_config_dir() -> Self {
+        match crate::profiles::Data::get_config_folder_path() {
+            Ok(config_dir) => Self::in_dir(config_dir.join(DOWNLOAD_CACHE_DIR_NAME)),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Get the directory downloaded files are kept in between runs
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Get the path a file with a hash is kept at in the cache directory
+    fn cached_file_path(dir: &Path, algorithm: &str, hash: &str) -> PathBuf {
+        dir.join(format!("{algorithm}-{hash}"))
+    }
+
+    /// Get the contents of a previously downloaded file, if it still matches its hashes
+    fn get(&self, hashes: &BTreeMap<String, String>) -> Option<Vec<u8>> {
+        let (algorithm, hash) = strongest_hash(hashes)?;
+        let downloaded_path = self
+            .files
+            .lock()
+            .expect("Download cache lock should not be poisoned")
+            .get(&(algorithm.to_string(), hash.clone()))
+            .cloned();
+        if let Some(contents) = downloaded_path
+            .and_then(|path| std::fs::read(path).ok())
+            .filter(|contents| verify_hashes(hashes, contents).is_ok())
+        {
+            return Some(contents);
+        }
+
+        let cached_path = Self::cached_file_path(self.dir.as_ref()?, algorithm, &hash);
+        let contents = std::fs::read(&cached_path)
+            .ok()
+            .filter(|contents| verify_hashes(hashes, contents).is_ok())?;
+        // Record that the file is still in use, for pruning with --keep-days
+        let _ = mark_used(&cached_path);
+        Some(contents)
+    }
+
+    fn insert(&self, hashes: &BTreeMap<String, String>, path: &Path) {
+        if let Some((algorithm, hash)) = strongest_hash(hashes) {
+            if let Some(dir) = &self.dir {
+                if let Err(e) = Self::keep_file(dir, algorithm, &hash, path) {
+                    eprintln!(
+                        "Failed to add {} to the download cache: {e:?}",
+                        path.display()
+                    );
+                }
+            }
+            self.files
+                .lock()
+                .expect("Download cache lock should not be poisoned")
+                .insert((algorithm.into(), hash), path.into());
+        }
+    }
+
+    /// Copy a downloaded file into the cache directory, if it isn't already there
+    fn keep_file(dir: &Path, algorithm: &str, hash: &str, path: &Path) -> Result<()> {
+        let cached_path = Self::cached_file_path(dir, algorithm, hash);
+        if cached_path.is_file() {
+            return mark_used(&cached_path);
+        }
+        std::fs::create_dir_all(dir)?;
+        // Copy to a temporary file first, so an interrupted copy never looks like a cached file
+        let part_path = cached_path.with_extension("part");
+        std::fs::copy(path, &part_path)?;
+        std::fs::rename(&part_path, &cached_path)?;
+        Ok(())
+    }
+
+    /// Delete cached files that aren't pinned by any of the given locks
+    ///
+    /// If `keep_days` is set, files used within that many days are kept regardless
+    pub fn prune(
+        &self,
+        pack_locks: &[PinnedPackMeta],
+        keep_days: Option<u64>,
+    ) -> Result<CachePruneSummary> {
+        let mut summary = CachePruneSummary::default();
+        let Some(dir) = self.dir.as_ref().filter(|dir| dir.is_dir()) else {
+            return Ok(summary);
+        };
+        let pinned_files: BTreeSet<PathBuf> = pack_locks
+            .iter()
+            .flat_map(|pack_lock| pack_lock.mods.values())
+            .flat_map(|pinned_mod| pinned_mod.source.iter())
+            .flat_map(|source| source.hashes().iter())
+            .filter(|(algorithm, _)| HASH_ALGORITHMS.contains(&algorithm.as_str()))
+            .map(|(algorithm, hash)| {
+                Self::cached_file_path(dir, algorithm, &hash.to_ascii_lowercase())
+            })
+            .collect();
+        let keep_since = keep_days
+            .map(|keep_days| SystemTime::now() - Duration::from_secs(keep_days * 24 * 60 * 60));
+
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() || pinned_files.contains(&entry.path()) {
+                continue;
+            }
+            if keep_since.is_some_and(|keep_since| {
+                metadata
+                    .modified()
+                    .is_ok_and(|modified| modified >= keep_since)
+            }) {
+                continue;
+            }
+            std::fs::remove_file(entry.path()).with_context(|| {
+                format!(
+                    "Failed to delete {} from the download cache",
+                    entry.path().display()
+                )
+            })?;
+            summary.removed_files += 1;
+            summary.reclaimed_bytes += metadata.len();
+        }
+        Ok(summary)
+    }
+}
+
+/// Set the modified time of a file to now
+fn mark_used(path: &Path) -> Result<()> {
+    std::fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())?;
+    Ok(())
+}
+
+/// The newest available version of a pinned mod
+#[derive(Debug, Serialize)]
+pub struct OutdatedMod {
+    pub name: String,
+    pub current_version: String,
+    /// The newest version the mod's version constraint allows, which updating would pin, if it could be checked
+    pub update_version: Option<String>,
+    /// The newest version that could be pinned if the mod's version constraint was ignored, if it could be checked
+    pub latest_version: Option<String>,
+    /// Whether updating would change the pinned version
+    pub outdated: bool,
+    /// A beta or alpha version newer than `latest_version`, which won't be pinned unless prereleases are included
+    pub prerelease_version: Option<String>,
+}
+
+/// The changelogs of a pinned mod between two of its versions
+#[derive(Debug, Serialize)]
+pub struct ModChangelog {
+    pub name: String,
+    pub from_version: String,
+    pub to_version: String,
+    /// Changelogs of each version after `from_version` up to and including `to_version`, newest first
+    pub versions: Vec<VersionChangelog>,
+}
+
+/// Render changelogs as a markdown changelog section
+pub fn changelog_markdown(changelogs: &[ModChangelog]) -> String {
+    let mut markdown = String::from("## Changelog\n");
+    for mod_changelog in changelogs {
+        markdown += &format!(
+            "\n### {} ({} -> {})\n",
+            mod_changelog.name, mod_changelog.from_version, mod_changelog.to_version
+        );
+        for version in mod_changelog.versions.iter() {
+            let changelog = version
+                .changelog
+                .as_deref()
+                .map(str::trim)
+                .filter(|changelog| !changelog.is_empty())
+                .unwrap_or("No changelog");
+            markdown += &format!("\n#### {}\n\n{changelog}\n", version.version);
+        }
+    }
+    markdown
+}
+
+/// Mod files that downloading the pinned mods would change
+#[derive(Debug, Clone, Default)]
+pub struct ModFileChanges {
+    /// Files that would be downloaded, because they are missing or don't match their pinned hashes
+    pub to_download: Vec<String>,
+    /// Files that would be deleted, because they aren't in the pinned mods
+    pub to_delete: Vec<String>,
+}
+
+/// A difference between the modpack's mods and the lock, eg. after mods are added without pinning them
+#[derive(Debug, PartialEq, Eq)]
+pub enum LockDrift {
+    /// The mod is in the modpack but isn't pinned
+    Unpinned(String),
+    /// The pinned version of the mod isn't the version the modpack asks for
+    VersionMismatch {
+        name: String,
+        version: String,
+        pinned_version: String,
+    },
+    /// The mod is still pinned, but is no longer in the modpack
+    Removed(String),
+}
+
+impl Display for LockDrift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockDrift::Unpinned(name) => write!(f, "{name} is not pinned"),
+            LockDrift::VersionMismatch {
+                name,
+                version,
+                pinned_version,
+            } => write!(
+                f,
+                "{name} is pinned at version {pinned_version}, but the modpack asks for {version}"
+            ),
+            LockDrift::Removed(name) => {
+                write!(f, "{name} is pinned, but is no longer in the modpack")
+            }
+        }
+    }
+}
+
+/// Whether a pinned mod's download url is still live
+#[derive(Debug, Serialize)]
+pub struct UrlStatus {
+    pub name: String,
+    pub url: String,
+    /// Status of the url, if it could be reached
+    pub status: Option<u16>,
+    /// Whether the file has been removed (404 or 410)
+    pub dead: bool,
+    pub error: Option<String>,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct PinnedPackMeta {
+    /// The pinned modloader version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    loader_version: Option<String>,
+    /// Pinned mods that were added to the pack directly, rather than only as a dependency of another mod
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    top_level_mods: BTreeSet<String>,
     mods: BTreeMap<String, PinnedMod>,
     #[serde(skip_serializing, skip_deserializing)]
     modrinth: Modrinth,
+    #[serde(skip_serializing, skip_deserializing)]
+    tracer: Arc<ResolutionTracer>,
+    /// Pinned mods that belong only to disabled groups, and so shouldn't be installed
+    #[serde(skip_serializing, skip_deserializing)]
+    excluded_mods: BTreeSet<String>,
+    /// Sides to install mods on in place of their pinned sides
+    #[serde(skip_serializing, skip_deserializing)]
+    side_overrides: BTreeMap<String, DownloadSide>,
+    /// Base url of a mirror to download mod files from in place of their pinned urls
+    #[serde(skip_serializing, skip_deserializing)]
+    mirror: Option<Url>,
+    /// Files in the mods folder to never delete, from the pack's `.mcmpmgrignore`
+    #[serde(skip_serializing, skip_deserializing)]
+    ignore_rules: IgnoreRules,
+    /// Leave files in the mods folder that aren't pinned, rather than deleting them
+    #[serde(skip_serializing, skip_deserializing)]
+    keep_unpinned: bool,
+    /// If set, only these mods are installed, and nothing is deleted from the mods folder
+    #[serde(skip_serializing, skip_deserializing)]
+    only_mods: Option<BTreeSet<String>>,
+    /// Public key that downloaded mod files are verified with
+    #[serde(skip_serializing, skip_deserializing)]
+    signing_key: Option<minisign_verify::PublicKey>,
 }
 
 impl PinnedPackMeta {
     pub fn new() -> Self {
         Self {
+            loader_version: None,
+            top_level_mods: Default::default(),
             mods: Default::default(),
             modrinth: Modrinth::new(),
+            tracer: Default::default(),
+            excluded_mods: Default::default(),
+            side_overrides: Default::default(),
+            mirror: None,
+            ignore_rules: Default::default(),
+            keep_unpinned: false,
+            only_mods: None,
+            signing_key: None,
+        }
+    }
+
+    /// Download mod files from a mirror serving the same files, eg. an internal CDN.
+    /// The pinned urls' paths are kept under the mirror's base url, and files are still verified against their pinned hashes
+    pub fn set_mirror(&mut self, mirror: &str) -> Result<()> {
+        let mirror = Url::parse(mirror).with_context(|| format!("Invalid mirror url {mirror}"))?;
+        if mirror.cannot_be_a_base() {
+            anyhow::bail!("Invalid mirror url {mirror}. Expected a base url such as https://mirror.example.com")
+        }
+        self.mirror = Some(mirror);
+        Ok(())
+    }
+
+    /// Require downloaded mod files to be signed, and verify them with a minisign public key, eg. the pack's `signing_key`
+    pub fn set_signing_key(&mut self, signing_key: &str) -> Result<()> {
+        self.signing_key = Some(
+            minisign_verify::PublicKey::from_base64(signing_key.trim())
+                .with_context(|| format!("Invalid minisign public key {signing_key}"))?,
+        );
+        Ok(())
+    }
+
+    /// Only download pinned mods, leaving any other files in the mods folder alone, eg. for packs that
+    /// don't set `manage_mods_exclusively`
+    pub fn set_keep_unpinned(&mut self, keep_unpinned: bool) {
+        self.keep_unpinned = keep_unpinned;
+    }
+
+    /// Install the given mods only on the given sides, regardless of their pinned sides or groups
+    pub fn set_side_overrides(&mut self, side_overrides: &BTreeMap<String, DownloadSide>) {
+        self.side_overrides = side_overrides.clone();
+    }
+
+    /// Prefix the filenames of mods with load order hints with their place in the load order (eg. `00_`, `10_`),
+    /// so that mods sort after every mod they have to be loaded after. Mods without any hints keep their filenames
+    ///
+    /// Only the filenames mods are installed as change, so files are installed under their pinned names again
+    /// the next time mods are downloaded without prefixes
+    pub fn prefix_load_order(&mut self) -> Result<()> {
+        let mut depths = BTreeMap::new();
+        let hinted_mods: BTreeSet<&str> = self
+            .mods
+            .iter()
+            .filter(|(_, pinned_mod)| !pinned_mod.load_after.is_empty())
+            .flat_map(|(mod_name, pinned_mod)| {
+                std::iter::once(mod_name.as_str())
+                    .chain(pinned_mod.load_after.iter().map(String::as_str))
+            })
+            .filter(|mod_name| self.mods.contains_key(*mod_name))
+            .collect();
+        for mod_name in hinted_mods.iter() {
+            self.load_order_depth(mod_name, &mut depths, &mut vec![])?;
+        }
+        let prefixes: BTreeMap<String, String> = {
+            // Wide enough for every prefix to sort correctly
+            let width = depths
+                .values()
+                .max()
+                .map_or(2, |max_depth| (max_depth * 10).to_string().len().max(2));
+            hinted_mods
+                .iter()
+                .map(|mod_name| {
+                    (
+                        mod_name.to_string(),
+                        format!("{:0width$}_", depths[mod_name] * 10),
+                    )
+                })
+                .collect()
+        };
+        for (mod_name, prefix) in prefixes {
+            let pinned_mod = self
+                .mods
+                .get_mut(&mod_name)
+                .expect("Only pinned mods are prefixed");
+            for source in pinned_mod.source.iter_mut() {
+                let filename = source.filename_mut();
+                *filename = format!("{prefix}{filename}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Get how many mods a mod has to be loaded after, following its load order hints
+    fn load_order_depth<'a>(
+        &'a self,
+        mod_name: &'a str,
+        depths: &mut BTreeMap<&'a str, usize>,
+        visiting: &mut Vec<&'a str>,
+    ) -> Result<usize> {
+        if let Some(depth) = depths.get(mod_name) {
+            return Ok(*depth);
+        }
+        if visiting.contains(&mod_name) {
+            anyhow::bail!(
+                "Mods can't be prefixed in load order, since their load order hints form a cycle: {} -> {mod_name}",
+                visiting.join(" -> ")
+            )
         }
+        visiting.push(mod_name);
+        let mut depth = 0;
+        if let Some(pinned_mod) = self.mods.get(mod_name) {
+            for load_after in pinned_mod
+                .load_after
+                .iter()
+                .filter(|load_after| self.mods.contains_key(*load_after))
+            {
+                depth = depth.max(self.load_order_depth(load_after, depths, visiting)? + 1);
+            }
+        }
+        visiting.pop();
+        depths.insert(mod_name, depth);
+        Ok(depth)
+    }
+
+    /// Only install grouped mods from `enabled_groups`, along with every ungrouped mod.
+    /// Dependencies of disabled mods are skipped too, unless something else still needs them
+    pub fn select_groups(
+        &mut self,
+        pack_metadata: &ModpackMeta,
+        enabled_groups: &[String],
+    ) -> Result<()> {
+        let disabled_mods = pack_metadata.disabled_group_mods(enabled_groups)?;
+        let disabled_closure = self.dep_closure(disabled_mods.iter().cloned());
+        let enabled_closure = self.dep_closure(
+            self.mods
+                .keys()
+                .filter(|mod_name| !disabled_closure.contains(*mod_name))
+                .cloned(),
+        );
+        self.excluded_mods = disabled_closure
+            .difference(&enabled_closure)
+            .cloned()
+            .collect();
+        Ok(())
+    }
+
+    /// Only install the given mods and their dependencies, without deleting anything else from the mods folder
+    pub fn select_only(&mut self, pack_metadata: &ModpackMeta, mod_names: &[String]) -> Result<()> {
+        let mut only_mods = vec![];
+        for mod_name in mod_names.iter() {
+            let mod_name = pack_metadata.canonical_mod_name(mod_name);
+            if !self.mods.contains_key(&mod_name) {
+                anyhow::bail!("Mod {mod_name} is not pinned, so it can't be installed on its own")
+            }
+            only_mods.push(mod_name);
+        }
+        self.only_mods = Some(self.dep_closure(only_mods.into_iter()));
+        Ok(())
+    }
+
+    /// Get the given pinned mods along with all of their transitive dependencies
+    fn dep_closure(&self, mod_names: impl Iterator<Item = String>) -> BTreeSet<String> {
+        let mut closure = BTreeSet::new();
+        let mut pending: Vec<String> = mod_names.collect();
+        while let Some(mod_name) = pending.pop() {
+            if !closure.insert(mod_name.clone()) {
+                continue;
+            }
+            if let Some(deps) = self.mods.get(&mod_name).and_then(|m| m.deps.as_ref()) {
+                pending.extend(deps.iter().map(|dep| dep.name.clone()));
+            }
+        }
+        closure
+    }
+
+    /// Get the differences between the modpack's mods and the pinned mods, which are resolved by the next update
+    pub fn get_drift(&self, pack_metadata: &ModpackMeta) -> Vec<LockDrift> {
+        let mut drift = vec![];
+        for mod_meta in pack_metadata.iter_mods() {
+            if pack_metadata.forbidden_mods.contains(&mod_meta.name) {
+                continue;
+            }
+            match self.mods.get(&mod_meta.name) {
+                None => drift.push(LockDrift::Unpinned(mod_meta.name.clone())),
+                // Overridden mods are pinned at their replacement's version
+                Some(pinned_mod)
+                    if mod_meta.version != "*"
+                        && pinned_mod.version != mod_meta.version
+                        && pack_metadata.get_override(mod_meta).is_none() =>
+                {
+                    drift.push(LockDrift::VersionMismatch {
+                        name: mod_meta.name.clone(),
+                        version: mod_meta.version.clone(),
+                        pinned_version: pinned_mod.version.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for mod_name in self.top_level_mods.iter() {
+            if self.mods.contains_key(mod_name) && !pack_metadata.mods.contains_key(mod_name) {
+                drift.push(LockDrift::Removed(mod_name.clone()));
+            }
+        }
+        drift
+    }
+
+    /// Pin and unpin mods so that the lock matches the modpack again. Mods that haven't changed keep their pinned versions
+    ///
+    /// Returns whether the lock changed
+    pub async fn resolve_drift(
+        &mut self,
+        pack_metadata: &ModpackMeta,
+        dependency_versions: DependencyVersions,
+    ) -> Result<bool> {
+        let drift = self.get_drift(pack_metadata);
+        let mut removed_mods = false;
+        for drifted_mod in drift.iter() {
+            println!("{drifted_mod}");
+            match drifted_mod {
+                LockDrift::Unpinned(mod_name)
+                | LockDrift::VersionMismatch { name: mod_name, .. } => {
+                    self.pin_mod_and_deps(
+                        &pack_metadata.mods[mod_name],
+                        pack_metadata,
+                        dependency_versions,
+                    )
+                    .await?
+                }
+                // Mods that are still a dependency of another mod stay pinned
+                LockDrift::Removed(mod_name) => {
+                    self.top_level_mods.remove(mod_name);
+                    removed_mods = true;
+                }
+            }
+        }
+        if removed_mods {
+            self.prune_mods()?;
+        }
+        Ok(!drift.is_empty())
+    }
+
+    /// Whether a pinned mod should be installed for a side, taking side overrides and disabled groups into account
+    fn should_install(
+        &self,
+        mod_name: &str,
+        pinned_mod: &PinnedMod,
+        download_side: DownloadSide,
+        include_optional: bool,
+    ) -> bool {
+        if self
+            .only_mods
+            .as_ref()
+            .is_some_and(|only_mods| !only_mods.contains(mod_name))
+        {
+            return false;
+        }
+        if let Some(side) = self.side_overrides.get(mod_name) {
+            return side.contains(download_side);
+        }
+        !self.excluded_mods.contains(mod_name)
+            && pinned_mod.should_download(download_side, include_optional)
     }
 
-    /// Clears out anything not in the mods list, and then downloads anything in the mods list not present
+    /// Clears out anything not in the mods list (unless unpinned files are kept), and then downloads anything in the mods list not present
+    ///
+    /// Mods that are only optional for the download side are skipped unless `include_optional` is set
     pub async fn download_mods(
         &self,
         mods_dir: &PathBuf,
         download_side: DownloadSide,
+        include_optional: bool,
     ) -> Result<()> {
-        let files = std::fs::read_dir(mods_dir)?;
+        self.download_mods_with_cache(
+            mods_dir,
+            download_side,
+            include_optional,
+            &DownloadCache::default(),
+            None,
+        )
+        .await
+    }
+
+    /// Whether downloading mods deletes the files in the mods folder that aren't pinned
+    fn deletes_unpinned(&self) -> bool {
+        !self.keep_unpinned && self.only_mods.is_none()
+    }
+
+    /// Work out which files `download_mods` would download and delete, without changing anything
+    pub fn plan_mod_changes(
+        &self,
+        mods_dir: &Path,
+        download_side: DownloadSide,
+        include_optional: bool,
+    ) -> Result<ModFileChanges> {
+        let mut changes = ModFileChanges::default();
         let mut pinned_files_cache = BTreeSet::new();
-        for file in files.into_iter() {
-            let file = file?;
-            if file.file_type()?.is_file() {
-                let filename = file.file_name();
-                if !self.file_is_pinned(&filename, download_side, &mut pinned_files_cache) {
-                    println!(
-                        "Deleting file {:#?} as it is not in the pinned mods",
-                        filename
-                    );
-                    tokio::fs::remove_file(file.path()).await?;
+        if mods_dir.is_dir() && self.deletes_unpinned() {
+            let ignore_rules = self.mods_dir_ignore_rules(mods_dir)?;
+            for file in std::fs::read_dir(mods_dir)? {
+                let file = file?;
+                if file.file_type()?.is_file()
+                    && !ignore_rules.is_ignored(&file.file_name().to_string_lossy())
+                    && !self.file_is_pinned(
+                        &file.file_name(),
+                        download_side,
+                        include_optional,
+                        &mut pinned_files_cache,
+                    )
+                {
+                    changes
+                        .to_delete
+                        .push(file.file_name().to_string_lossy().into());
                 }
             }
         }
 
-        for (_, pinned_mod) in self.mods.iter().filter(|m| {
-            download_side == DownloadSide::Both
-                || download_side == DownloadSide::Client && m.1.client_side
-                || download_side == DownloadSide::Server && m.1.server_side
-        }) {
+        for (_, pinned_mod) in self
+            .mods
+            .iter()
+            .filter(|(name, m)| self.should_install(name, m, download_side, include_optional))
+        {
             for filesource in pinned_mod.source.iter() {
-                match filesource {
-                    crate::providers::FileSource::Download {
-                        url,
-                        sha1: _,
-                        sha512,
-                        filename,
-                    } => {
-                        if mods_dir.join(PathBuf::from(filename)).exists() {
-                            println!("Found existing mod {}", filename);
-                            continue;
-                        }
-                        println!("Downloading {} from {}", filename, url);
-                        let file_contents = reqwest::get(url).await?.bytes().await?;
-                        let mut hasher = Sha512::new();
-                        hasher.update(&file_contents);
-                        let sha512_hash = format!("{:X}", hasher.finalize()).to_ascii_lowercase();
-                        let sha512 = sha512.to_ascii_lowercase();
-                        if sha512_hash != *sha512 {
-                            eprintln!(
-                                "Sha512 hash mismatch for file {}\nExpected:\n{}\nGot:\n{}",
-                                filename, sha512, sha512_hash
-                            );
-                            anyhow::bail!(
-                                "Sha512 hash mismatch for file {}\nExpected:\n{}\nGot:\n{}",
-                                filename,
-                                sha512,
-                                sha512_hash
-                            )
-                        }
+                if let FileSource::Download {
+                    hashes, filename, ..
+                } = filesource
+                {
+                    let mod_path = mods_dir.join(filename);
+                    let up_to_date = mod_path.is_file()
+                        && verify_hashes(hashes, &std::fs::read(&mod_path)?).is_ok();
+                    if !up_to_date {
+                        changes.to_download.push(filename.clone());
+                    }
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Add the pinned mod files found in `dir` to the download cache, so that they don't need to be downloaded
+    pub fn cache_mod_files(&self, dir: &Path, download_cache: &DownloadCache) {
+        for filesource in self.mods.values().flat_map(|m| m.source.iter()) {
+            if let FileSource::Download {
+                hashes, filename, ..
+            } = filesource
+            {
+                let mod_path = dir.join(filename);
+                if mod_path.is_file() {
+                    download_cache.insert(hashes, &mod_path);
+                }
+            }
+        }
+    }
+
+    /// Same as `download_mods`, but reuses files already downloaded in this run where possible
+    ///
+    /// Progress updates are sent for each mod file if a progress sender is given
+    pub async fn download_mods_with_cache(
+        &self,
+        mods_dir: &PathBuf,
+        download_side: DownloadSide,
+        include_optional: bool,
+        download_cache: &DownloadCache,
+        progress: Option<&UnboundedSender<DownloadProgress>>,
+    ) -> Result<()> {
+        if self.deletes_unpinned() {
+            let files = std::fs::read_dir(mods_dir)?;
+            let mut pinned_files_cache = BTreeSet::new();
+            let ignore_rules = self.mods_dir_ignore_rules(mods_dir)?;
+            for file in files.into_iter() {
+                let file = file?;
+                if file.file_type()?.is_file() {
+                    let filename = file.file_name();
+                    if !ignore_rules.is_ignored(&filename.to_string_lossy())
+                        && !self.file_is_pinned(
+                            &filename,
+                            download_side,
+                            include_optional,
+                            &mut pinned_files_cache,
+                        )
+                    {
+                        println!(
+                            "Deleting file {:#?} as it is not in the pinned mods",
+                            filename
+                        );
+                        tokio::fs::remove_file(file.path()).await?;
+                    }
+                }
+            }
+        }
 
-                        tokio::fs::write(mods_dir.join(filename), file_contents).await?;
+        let filesources: Vec<&FileSource> = self
+            .mods
+            .iter()
+            .filter(|(name, m)| self.should_install(name, m, download_side, include_optional))
+            .flat_map(|(_, m)| m.source.iter())
+            .collect();
+        let send_progress = |current: usize, filename: &str| {
+            if let Some(progress) = progress {
+                // Nobody listening for progress isn't a reason to stop downloading
+                let _ = progress.send(DownloadProgress {
+                    current,
+                    total: filesources.len(),
+                    filename: filename.into(),
+                });
+            }
+        };
+
+        for (i, filesource) in filesources.iter().enumerate() {
+            match filesource {
+                crate::providers::FileSource::Download {
+                    url,
+                    hashes,
+                    filename,
+                    signature,
+                    ..
+                } => {
+                    send_progress(i, filename);
+                    let mod_path = mods_dir.join(PathBuf::from(filename));
+                    self.fetch_mod_file(url, hashes, filename, &mod_path, download_cache)
+                        .await?;
+                    if let Err(e) = self.verify_signature(signature.as_deref(), &mod_path).await {
+                        let _ = tokio::fs::remove_file(&mod_path).await;
+                        return Err(e.context(format!(
+                            "{filename} failed its signature check, so it was removed"
+                        )));
                     }
-                    crate::providers::FileSource::Local {
-                        path: _,
-                        sha1: _,
-                        sha512: _,
-                        filename: _,
-                    } => unimplemented!(),
                 }
+                crate::providers::FileSource::Local { .. } => unimplemented!(),
+            }
+        }
+        send_progress(filesources.len(), "");
+
+        Ok(())
+    }
+
+    /// Make sure a mod file matching its pinned hashes is in the mods folder, copying it from the
+    /// download cache or downloading it if needed
+    async fn fetch_mod_file(
+        &self,
+        url: &str,
+        hashes: &BTreeMap<String, String>,
+        filename: &str,
+        mod_path: &Path,
+        download_cache: &DownloadCache,
+    ) -> Result<()> {
+        if mod_path.exists() {
+            let existing_contents = tokio::fs::read(mod_path).await?;
+            if verify_hashes(hashes, &existing_contents).is_ok() {
+                println!("Found existing mod {}", filename);
+                return Ok(());
             }
+            println!(
+                "Existing mod {} does not match its pinned hash. Re-downloading...",
+                filename
+            );
         }
+        if let Some(cached_contents) = download_cache.get(hashes) {
+            println!("Copying {} from download cache", filename);
+            tokio::fs::write(mod_path, cached_contents).await?;
+            download_cache.insert(hashes, mod_path);
+            return Ok(());
+        }
+        let url = match &self.mirror {
+            Some(mirror) => mirror_url(url, mirror)?.to_string(),
+            None => url.to_string(),
+        };
+        println!("Downloading {} from {}", filename, url);
+        download_file(&url, mod_path, hashes)
+            .await
+            .with_context(|| format!("Failed to download {filename}"))?;
+        download_cache.insert(hashes, mod_path);
+        Ok(())
+    }
 
+    /// Check a mod file against its minisign signature, if the pack has a signing key
+    ///
+    /// Once a pack has a signing key, every downloaded file has to be signed
+    async fn verify_signature(&self, signature: Option<&str>, path: &Path) -> Result<()> {
+        let Some(signing_key) = &self.signing_key else {
+            return Ok(());
+        };
+        let Some(signature) = signature else {
+            anyhow::bail!("The file has no signature, but the pack requires files to be signed")
+        };
+        let signature = if signature.starts_with("https://") || signature.starts_with("http://") {
+            reqwest::get(signature)
+                .await?
+                .error_for_status()?
+                .text()
+                .await
+                .with_context(|| format!("Failed to download signature from {signature}"))?
+        } else {
+            signature.to_string()
+        };
+        let signature =
+            minisign_verify::Signature::decode(&signature).context("Invalid minisign signature")?;
+        let contents = tokio::fs::read(path).await?;
+        signing_key
+            .verify(&contents, &signature, true)
+            .context("The file doesn't match its signature")?;
         Ok(())
     }
 
+    /// Get the rules for files to never delete from a mods folder, combining the pack's
+    /// `.mcmpmgrignore` with one in the mods folder itself
+    fn mods_dir_ignore_rules(&self, mods_dir: &Path) -> Result<IgnoreRules> {
+        let mut ignore_rules = self.ignore_rules.clone();
+        ignore_rules.add_dir(mods_dir)?;
+        Ok(ignore_rules)
+    }
+
     pub fn file_is_pinned(
         &self,
         file_name: &OsStr,
         mod_side: DownloadSide,
+        include_optional: bool,
         cache: &mut BTreeSet<OsString>,
     ) -> bool {
+        // Partial downloads of pinned files are kept so that they can be resumed
+        let file_name = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_suffix(PART_EXTENSION))
+            .map_or(file_name, OsStr::new);
         if cache.contains(file_name) {
             return true;
         }
-        for (_, pinned_mod) in self.mods.iter().filter(|m| {
-            mod_side == DownloadSide::Both
-                || mod_side == DownloadSide::Client && m.1.client_side
-                || mod_side == DownloadSide::Server && m.1.server_side
-        }) {
+        for (_, pinned_mod) in self
+            .mods
+            .iter()
+            .filter(|m| self.should_install(m.0, m.1, mod_side, include_optional))
+        {
             for filesource in pinned_mod.source.iter() {
                 match filesource {
-                    crate::providers::FileSource::Download {
-                        url: _,
-                        sha1: _,
-                        sha512: _,
-                        filename,
-                    } => {
+                    crate::providers::FileSource::Download { filename, .. } => {
                         let pinned_filename = OsStr::new(filename);
                         cache.insert(pinned_filename.into());
                         if pinned_filename == file_name {
                             return true;
                         }
                     }
-                    crate::providers::FileSource::Local {
-                        path: _,
-                        sha1: _,
-                        sha512: _,
-                        filename,
-                    } => {
+                    crate::providers::FileSource::Local { filename, .. } => {
                         let pinned_filename = OsStr::new(filename);
                         cache.insert(pinned_filename.into());
                         if pinned_filename == file_name {
@@ -156,8 +971,11 @@ impl PinnedPackMeta {
         &mut self,
         mod_metadata: &ModMeta,
         pack_metadata: &ModpackMeta,
-        ignore_transitive_versions: bool,
+        dependency_versions: DependencyVersions,
     ) -> Result<()> {
+        if pack_metadata.mods.contains_key(&mod_metadata.name) {
+            self.top_level_mods.insert(mod_metadata.name.clone());
+        }
         if let Some(mod_meta) = self.mods.get(&mod_metadata.name) {
             if mod_metadata.version != "*" && mod_metadata.version == mod_meta.version {
                 // Skip already pinned mods
@@ -165,13 +983,12 @@ impl PinnedPackMeta {
                 return Ok(());
             }
         }
-        let mut deps =
-            BTreeSet::from_iter(self.pin_mod(mod_metadata, pack_metadata).await?.into_iter());
-
-        if ignore_transitive_versions {
-            // Ignore transitive dep versions
-            deps = deps.iter().map(|d| d.clone().version("*")).collect();
-        }
+        let mut deps: BTreeSet<ModMeta> = self
+            .pin_mod(mod_metadata, pack_metadata)
+            .await?
+            .into_iter()
+            .map(|dep| dependency_versions.apply(dep))
+            .collect();
 
         let pinned_version = self
             .mods
@@ -180,43 +997,362 @@ impl PinnedPackMeta {
             .version
             .clone();
 
+        // Each layer of dependencies is resolved together so that provider lookups can be batched
         while !deps.is_empty() {
-            let mut next_deps = BTreeSet::new();
+            deps = deps
+                .into_iter()
+                .map(|dep| pack_metadata.apply_dependency_override(dep, &mod_metadata.name))
+                .collect();
             for dep in deps.iter() {
                 println!(
                     "Adding mod {}@{} (dependency of {}@{})",
                     dep.name, dep.version, mod_metadata.name, pinned_version
                 );
-                next_deps.extend(self.pin_mod(dep, &pack_metadata).await?);
             }
-            deps = next_deps;
+            let layer: Vec<ModMeta> = deps.into_iter().collect();
+            deps =
+                BTreeSet::from_iter(self.pin_mods(&layer, pack_metadata).await.with_context(
+                    || format!("Failed to pin dependencies of {}", mod_metadata.name),
+                )?);
         }
 
         Ok(())
     }
 
-    /// Pin a mod version
+    /// Pin the jars in a mods folder that CurseForge recognises by their fingerprints, eg. to import a CurseForge instance
     ///
-    /// A list of dependencies to pin is included
-    pub async fn pin_mod(
+    /// Returns the recognised mods, using the Raw provider with their CurseForge download urls to add to the pack.
+    /// Only packs that use the CurseForge provider can import mods from it
+    pub async fn pin_curseforge_files(
         &mut self,
-        mod_metadata: &ModMeta,
+        mods_dir: &Path,
         pack_metadata: &ModpackMeta,
+        curseforge: &CurseForge,
     ) -> Result<Vec<ModMeta>> {
-        if pack_metadata.forbidden_mods.contains(&mod_metadata.name) {
-            println!("Skipping adding forbidden mod {}...", mod_metadata.name);
-            return Ok(vec![]);
+        if !pack_metadata
+            .default_providers_for(ProjectKind::Mod)
+            .contains(&ModProvider::CurseForge)
+        {
+            anyhow::bail!(
+                "Mods can only be imported from CurseForge in packs that use the CurseForge provider. Add it with `mcmpmgr providers add curseforge`"
+            )
+        }
+
+        let mut jars = BTreeMap::new();
+        for entry in std::fs::read_dir(mods_dir)
+            .with_context(|| format!("Cannot read mods folder {}", mods_dir.display()))?
+        {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|extension| extension == "jar") {
+                let contents = std::fs::read(&path)?;
+                jars.insert(curseforge::fingerprint(&contents), (path, contents));
+            }
+        }
+
+        let fingerprints: Vec<u32> = jars.keys().copied().collect();
+        let mut identified_files = curseforge.identify_files(&fingerprints).await?;
+        let mut imported_mods = vec![];
+        for (fingerprint, (path, contents)) in jars {
+            let Some(identified_file) = identified_files.remove(&fingerprint) else {
+                eprintln!(
+                    "CurseForge doesn't recognise {}, skipping it",
+                    path.display()
+                );
+                continue;
+            };
+            if pack_metadata.mods.contains_key(&identified_file.slug) {
+                eprintln!(
+                    "{} is already in the pack, skipping {}",
+                    identified_file.slug,
+                    path.display()
+                );
+                continue;
+            }
+            let mut pinned_mod = identified_file.pinned_mod;
+            let Some(FileSource::Download { url, hashes, .. }) = pinned_mod.source.first_mut()
+            else {
+                anyhow::bail!("CurseForge has no download for {}", path.display())
+            };
+            // Not every CurseForge file has a sha1 hash, but the file on disk is the file CurseForge has
+            if strongest_hash(hashes).is_none() {
+                *hashes = all_hashes(&contents);
+            }
+            let mod_meta = ModMeta::new(&identified_file.slug)?
+                .url(url)
+                .provider(ModProvider::Raw);
+            println!(
+                "Importing {} as {}@{}",
+                path.display(),
+                identified_file.slug,
+                pinned_mod.version
+            );
+            self.top_level_mods.insert(mod_meta.name.clone());
+            self.mods.insert(mod_meta.name.clone(), pinned_mod);
+            imported_mods.push(mod_meta);
         }
+        Ok(imported_mods)
+    }
+
+    /// Check that a mod and all of its required dependencies can be resolved for the pack, without pinning anything
+    ///
+    /// The error names the chain of dependencies leading to the first mod that can't be resolved
+    pub async fn check_compat(
+        &self,
+        mod_metadata: &ModMeta,
+        pack_metadata: &ModpackMeta,
+        dependency_versions: DependencyVersions,
+    ) -> Result<()> {
+        let mut checked_mods = BTreeSet::new();
+        let mut pending = VecDeque::from([(mod_metadata.clone(), vec![mod_metadata.name.clone()])]);
+        while let Some((mod_meta, chain)) = pending.pop_front() {
+            if !checked_mods.insert(mod_meta.name.clone()) {
+                continue;
+            }
+            let resolved = match get_resolution_target(&mod_meta, pack_metadata) {
+                Ok(Some(resolved_metadata)) => {
+                    self.resolve_mod(resolved_metadata, pack_metadata).await
+                }
+                Ok(None) => continue,
+                Err(e) => Err(e),
+            };
+            let pinned_mod = resolved.with_context(|| {
+                format!(
+                    "{} can't be resolved for minecraft {} with {}",
+                    chain.join(" -> "),
+                    pack_metadata.mc_version,
+                    pack_metadata.modloader.to_string()
+                )
+            })?;
+            for dep in pinned_mod.deps.into_iter().flatten() {
+                let dep = pack_metadata
+                    .apply_dependency_override(dependency_versions.apply(dep), &mod_meta.name);
+                let mut dep_chain = chain.clone();
+                dep_chain.push(dep.name.clone());
+                pending.push_back((dep, dep_chain));
+            }
+        }
+        Ok(())
+    }
 
+    /// Pin a mod version
+    ///
+    /// A list of dependencies to pin is included
+    pub async fn pin_mod(
+        &mut self,
+        mod_metadata: &ModMeta,
+        pack_metadata: &ModpackMeta,
+    ) -> Result<Vec<ModMeta>> {
+        self.pin_mods(std::slice::from_ref(mod_metadata), pack_metadata)
+            .await
+    }
+
+    /// Pin several mod versions, batching lookups for mods that share a provider where possible
+    ///
+    /// A list of dependencies to pin is included
+    pub async fn pin_mods(
+        &mut self,
+        mods: &[ModMeta],
+        pack_metadata: &ModpackMeta,
+    ) -> Result<Vec<ModMeta>> {
+        let mut targets = vec![];
+        for mod_metadata in mods.iter() {
+            if let Some(resolved_metadata) = get_resolution_target(mod_metadata, pack_metadata)? {
+                targets.push((mod_metadata, resolved_metadata));
+            }
+        }
+
+        // Mods that try Modrinth first are looked up together. Anything else falls back to resolving one at a time
+        let tries_modrinth_first = |mod_metadata: &ModMeta| {
+            mod_metadata
+                .providers
+                .iter()
+                .flatten()
+                .chain(pack_metadata.default_providers_for(mod_metadata.kind))
+                .next()
+                == Some(&ModProvider::Modrinth)
+        };
+        let batched_mods: Vec<&ModMeta> = targets
+            .iter()
+            .map(|(_, resolved_metadata)| *resolved_metadata)
+            .filter(|resolved_metadata| tries_modrinth_first(resolved_metadata))
+            .collect();
+        let mut batch_results = self
+            .modrinth
+            .resolve_batch(&batched_mods, pack_metadata)
+            .await
+            .into_iter();
+
+        let mut deps = vec![];
+        for (mod_metadata, resolved_metadata) in targets {
+            let pinned_mod = if tries_modrinth_first(resolved_metadata) {
+                match batch_results
+                    .next()
+                    .expect("There should be a result for each batched mod")
+                {
+                    Ok(pinned_mod) => pinned_mod,
+                    Err(e) if e.kind != ProviderErrorKind::NotFound => {
+                        return Err(provider_unavailable(
+                            resolved_metadata,
+                            ModProvider::Modrinth,
+                            e,
+                        ));
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "Failed to resolve {}@{} with provider {}: {}",
+                            resolved_metadata.name,
+                            resolved_metadata.version,
+                            ModProvider::Modrinth,
+                            e
+                        );
+                        self.resolve_mod_with_checked_providers(
+                            resolved_metadata,
+                            pack_metadata,
+                            BTreeSet::from([ModProvider::Modrinth]),
+                        )
+                        .await
+                        .map_err(|fallback_error| prefer_version_conflict(fallback_error, &e))?
+                    }
+                }
+            } else {
+                self.resolve_mod(resolved_metadata, pack_metadata).await?
+            };
+            // Sides set on the mod in the modpack win, even when it is pinned as a dependency or swapped for an override
+            let pack_mod_metadata = pack_metadata
+                .mods
+                .get(&mod_metadata.name)
+                .unwrap_or(mod_metadata);
+            let mut pinned_mod = pinned_mod
+                .with_sides_from(pack_mod_metadata)
+                .with_load_order_from(pack_mod_metadata);
+            self.rename_colliding_files(&mod_metadata.name, &mut pinned_mod.source);
+            self.mods
+                .insert(mod_metadata.name.clone(), pinned_mod.clone());
+            println!("Pinned {}@{}", mod_metadata.name, pinned_mod.version);
+            if let Some(pinned_deps) = pinned_mod.deps {
+                deps.extend(pinned_deps);
+            }
+        }
+
+        Ok(deps
+            .into_iter()
+            .filter(|d| !self.mods.contains_key(&d.name))
+            .collect())
+    }
+
+    /// Rename a mod's files that have the same filename as another pinned mod's files, by prefixing them with the mod's name.
+    /// Otherwise one would overwrite the other in the mods folder
+    fn rename_colliding_files(&self, mod_name: &str, sources: &mut [FileSource]) {
+        let other_filenames: BTreeSet<&str> = self
+            .mods
+            .iter()
+            .filter(|(pinned_mod_name, _)| *pinned_mod_name != mod_name)
+            .flat_map(|(_, pinned_mod)| pinned_mod.source.iter())
+            .map(FileSource::filename)
+            .collect();
+        for source in sources.iter_mut() {
+            let filename = source.filename_mut();
+            if other_filenames.contains(filename.as_str()) {
+                let renamed = format!("{mod_name}-{filename}");
+                eprintln!(
+                    "Warning: {filename} from {mod_name} has the same filename as another pinned mod's file. It will be installed as {renamed}"
+                );
+                *filename = renamed;
+            }
+        }
+    }
+
+    /// Get the minecraft version a pinned mod was resolved for, so that other mods can be resolved for the same version
+    ///
+    /// This is the mod's minecraft version override if it has one, or otherwise the first of the pack's
+    /// minecraft versions that the pinned version supports
+    pub async fn pinned_game_version(
+        &self,
+        mod_name: &str,
+        pack_metadata: &ModpackMeta,
+    ) -> Result<String> {
+        let Some(pinned_mod) = self.mods.get(mod_name) else {
+            anyhow::bail!("Mod {mod_name} isn't pinned in the modpack")
+        };
+        if let Some(mc_version) = pack_metadata
+            .mods
+            .get(mod_name)
+            .and_then(|mod_meta| mod_meta.mc_version.as_deref())
+        {
+            return Ok(mc_version.into());
+        }
+        let supported_game_versions = self
+            .modrinth
+            .get_game_versions(
+                pinned_mod.project_id.as_deref().unwrap_or(mod_name),
+                &pinned_mod.version,
+                pack_metadata,
+            )
+            .await
+            .with_context(|| {
+                format!("Failed to look up the minecraft versions {mod_name} supports")
+            })?;
+        let game_versions = pack_metadata.game_versions(None);
+        game_versions
+            .iter()
+            .find(|game_version| supported_game_versions.contains(game_version))
+            .cloned()
+            .ok_or(anyhow::format_err!(
+                "{mod_name}@{} doesn't support any of the pack's minecraft versions ({})",
+                pinned_mod.version,
+                game_versions.join(", ")
+            ))
+    }
+
+    /// Search Modrinth for mods the user may have meant, if a mod would be looked up on Modrinth
+    /// but there's no project with its name
+    ///
+    /// Returns `None` if the project exists or the mod isn't looked up on Modrinth
+    pub async fn suggest_mods(
+        &self,
+        mod_meta: &ModMeta,
+        pack_metadata: &ModpackMeta,
+    ) -> Result<Option<Vec<ModrinthSearchResult>>> {
+        let uses_modrinth = mod_meta.download_url.is_none()
+            && mod_meta
+                .providers
+                .as_deref()
+                .unwrap_or(pack_metadata.default_providers_for(mod_meta.kind))
+                .contains(&ModProvider::Modrinth);
+        if !uses_modrinth || self.modrinth.project_exists(&mod_meta.name).await? {
+            return Ok(None);
+        }
+        let mut suggestions = self.modrinth.search(&mod_meta.name, pack_metadata).await?;
+        suggestions.truncate(MAX_SUGGESTIONS);
+        Ok(Some(suggestions))
+    }
+
+    /// Resolve the version of a mod that would be pinned, without pinning it
+    pub async fn resolve_mod(
+        &self,
+        mod_metadata: &ModMeta,
+        pack_metadata: &ModpackMeta,
+    ) -> Result<PinnedMod> {
+        self.resolve_mod_with_checked_providers(mod_metadata, pack_metadata, BTreeSet::new())
+            .await
+    }
+
+    /// Resolve the version of a mod, skipping any providers that have already been checked
+    async fn resolve_mod_with_checked_providers(
+        &self,
+        mod_metadata: &ModMeta,
+        pack_metadata: &ModpackMeta,
+        mut checked_providers: BTreeSet<ModProvider>,
+    ) -> Result<PinnedMod> {
+        let mut modrinth_error = None;
         let mod_providers = if let Some(mod_providers) = &mod_metadata.providers {
             mod_providers
         } else {
             &vec![]
         };
-        let mut checked_providers: BTreeSet<ModProvider> = BTreeSet::new();
         for mod_provider in mod_providers
             .iter()
-            .chain(pack_metadata.default_providers.iter())
+            .chain(pack_metadata.default_providers_for(mod_metadata.kind))
         {
             if checked_providers.contains(&mod_provider) {
                 // No need to repeat a check for a provider if we have already checked it
@@ -224,26 +1360,42 @@ impl PinnedPackMeta {
             }
             checked_providers.insert(mod_provider.clone());
             match mod_provider {
-                crate::mod_meta::ModProvider::CurseForge => unimplemented!(),
+                crate::mod_meta::ModProvider::CurseForge => {
+                    // CurseForge jars can only be imported by their fingerprints, so the next provider is tried
+                    let e = ProviderError::new(
+                        ProviderErrorKind::NotFound,
+                        anyhow::format_err!(
+                            "CurseForge can't resolve mods by name. Import them with `mcmpmgr import-curseforge` instead"
+                        ),
+                    );
+                    self.tracer.complete_attempt(
+                        &mod_metadata.name,
+                        &mod_metadata.version,
+                        ModProvider::CurseForge,
+                        Err(e.to_string()),
+                    );
+                    eprintln!(
+                        "Failed to resolve {}@{} with provider {}: {}",
+                        mod_metadata.name, mod_metadata.version, mod_provider, e
+                    );
+                }
                 crate::mod_meta::ModProvider::Modrinth => {
-                    let pinned_mod = self.modrinth.resolve(&mod_metadata, pack_metadata).await;
-                    if let Ok(pinned_mod) = pinned_mod {
-                        self.mods
-                            .insert(mod_metadata.name.clone(), pinned_mod.clone());
-                        println!("Pinned {}@{}", mod_metadata.name, pinned_mod.version);
-                        if let Some(deps) = &pinned_mod.deps {
-                            return Ok(deps
-                                .iter()
-                                .filter(|d| !self.mods.contains_key(&d.name))
-                                .cloned()
-                                .collect());
+                    match self.modrinth.resolve(mod_metadata, pack_metadata).await {
+                        Ok(pinned_mod) => return Ok(pinned_mod),
+                        Err(e) if e.kind != ProviderErrorKind::NotFound => {
+                            return Err(provider_unavailable(
+                                mod_metadata,
+                                mod_provider.clone(),
+                                e,
+                            ));
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "Failed to resolve {}@{} with provider {}: {}",
+                                mod_metadata.name, mod_metadata.version, mod_provider, e
+                            );
+                            modrinth_error = Some(e);
                         }
-                        return Ok(vec![]);
-                    } else if let Err(e) = pinned_mod {
-                        eprintln!(
-                            "Failed to resolve {}@{} with provider {:#?}: {}",
-                            mod_metadata.name, mod_metadata.version, mod_provider, e
-                        );
                     }
                 }
                 crate::mod_meta::ModProvider::Raw => {
@@ -254,7 +1406,36 @@ impl PinnedPackMeta {
                             "A download url is required to pin {}",
                             mod_metadata.name
                         ))?;
-                    let file_response = reqwest::get(&url).await?;
+                    let file_response = reqwest::get(&url).await.map_err(|e| {
+                        provider_unavailable(
+                            mod_metadata,
+                            ModProvider::Raw,
+                            ProviderError::classify(e.into()),
+                        )
+                    })?;
+                    self.tracer
+                        .record_request(&url, Some(file_response.status().as_u16()));
+                    let status = file_response.status();
+                    if !status.is_success() {
+                        let e = ProviderError::new(
+                            ProviderErrorKind::from_status(status),
+                            anyhow::format_err!("Request to {url} failed with status {status}"),
+                        );
+                        if e.kind != ProviderErrorKind::NotFound {
+                            return Err(provider_unavailable(mod_metadata, ModProvider::Raw, e));
+                        }
+                        self.tracer.complete_attempt(
+                            &mod_metadata.name,
+                            &mod_metadata.version,
+                            ModProvider::Raw,
+                            Err(e.to_string()),
+                        );
+                        eprintln!(
+                            "Failed to resolve {}@{} with provider {}: {}",
+                            mod_metadata.name, mod_metadata.version, mod_provider, e
+                        );
+                        continue;
+                    }
 
                     // TODO: Get filename from content disposition
                     let _content_disposition = file_response.headers().get(CONTENT_DISPOSITION);
@@ -269,40 +1450,292 @@ impl PinnedPackMeta {
                         .ok_or(anyhow::format_err!("Cannot get filename from url {}", url))?;
 
                     let file_contents = file_response.bytes().await?;
-                    let mut sha1_hasher = Sha1::new();
-                    let mut sha512_hasher = Sha512::new();
-                    sha1_hasher.update(&file_contents);
-                    sha512_hasher.update(&file_contents);
-                    let sha1_hash = format!("{:X}", sha1_hasher.finalize()).to_ascii_lowercase();
-                    let sha512_hash =
-                        format!("{:X}", sha512_hasher.finalize()).to_ascii_lowercase();
-
-                    let pinned_mod = PinnedMod {
+                    self.tracer.complete_attempt(
+                        &mod_metadata.name,
+                        &mod_metadata.version,
+                        ModProvider::Raw,
+                        Ok("Unknown"),
+                    );
+
+                    return Ok(PinnedMod {
                         source: vec![FileSource::Download {
                             url: url.into(),
-                            sha1: sha1_hash,
-                            sha512: sha512_hash,
+                            hashes: all_hashes(&file_contents),
                             filename: filename.into(),
+                            role: FileRole::Primary,
+                            signature: None,
                         }],
                         version: "Unknown".into(),
                         deps: None,
-                        server_side: mod_metadata.server_side.unwrap_or(true),
-                        client_side: mod_metadata.client_side.unwrap_or(true),
-                    };
-                    self.mods
-                        .insert(mod_metadata.name.clone(), pinned_mod.clone());
-                    println!("Pinned {}@{}", mod_metadata.name, pinned_mod.version);
-                    return Ok(vec![]);
+                        server_side: mod_metadata.server_side.unwrap_or(true).into(),
+                        client_side: mod_metadata.client_side.unwrap_or(true).into(),
+                        project_id: None,
+                        version_type: None,
+                        load_after: Default::default(),
+                        source_url: None,
+                    });
                 }
             };
         }
 
-        anyhow::bail!(
-            "Failed to pin mod '{}' (providers={:#?}) with constraint {} and all its deps",
-            mod_metadata.name,
-            mod_metadata.providers,
-            mod_metadata.version
-        )
+        let not_found = McmpmgrError::ModNotFound {
+            name: mod_metadata.name.clone(),
+            version: mod_metadata.version.clone(),
+            providers: mod_metadata.providers.clone(),
+        }
+        .into();
+        Err(match &modrinth_error {
+            Some(e) => prefer_version_conflict(not_found, e),
+            None => not_found,
+        })
+    }
+
+    /// Check each pinned mod for a newer version without modifying the lock
+    pub async fn get_outdated_mods(&self, pack_metadata: &ModpackMeta) -> Vec<OutdatedMod> {
+        let mut outdated_mods = vec![];
+        for (mod_name, pinned_mod) in self.mods.iter() {
+            let mut outdated_mod = OutdatedMod {
+                name: mod_name.clone(),
+                current_version: pinned_mod.version.clone(),
+                update_version: None,
+                latest_version: None,
+                outdated: false,
+                prerelease_version: None,
+            };
+            let is_raw_mod = pinned_mod.version == "Unknown";
+            let mod_meta = match pack_metadata.mods.get(mod_name) {
+                Some(mod_meta) => Ok(mod_meta.clone()),
+                None => ModMeta::new(mod_name),
+            };
+            if !is_raw_mod {
+                match mod_meta {
+                    Ok(mod_meta) => match self.resolve_mod(&mod_meta, pack_metadata).await {
+                        Ok(update_mod) => {
+                            let any_version_meta = mod_meta.clone().version("*");
+                            let latest_version = if mod_meta.version == "*" {
+                                Some(update_mod.version.clone())
+                            } else {
+                                self.resolve_mod(&any_version_meta, pack_metadata)
+                                    .await
+                                    .ok()
+                                    .map(|latest_mod| latest_mod.version)
+                            };
+                            if !mod_meta.include_prerelease {
+                                let prerelease_meta = any_version_meta.include_prerelease(true);
+                                if let Ok(prerelease_mod) =
+                                    self.resolve_mod(&prerelease_meta, pack_metadata).await
+                                {
+                                    if Some(&prerelease_mod.version) != latest_version.as_ref()
+                                        && prerelease_mod.version != pinned_mod.version
+                                    {
+                                        outdated_mod.prerelease_version =
+                                            Some(prerelease_mod.version);
+                                    }
+                                }
+                            }
+                            outdated_mod.outdated = update_mod.version != pinned_mod.version;
+                            outdated_mod.update_version = Some(update_mod.version);
+                            outdated_mod.latest_version = latest_version;
+                        }
+                        Err(e) => eprintln!("Failed to check {mod_name} for updates: {e}"),
+                    },
+                    Err(e) => eprintln!("Failed to check {mod_name} for updates: {e}"),
+                }
+            }
+            outdated_mods.push(outdated_mod);
+        }
+        outdated_mods
+    }
+
+    /// Get the pinned mods whose versions differ from a previous lock, as (mod name, old version, new version)
+    pub fn get_version_changes(
+        &self,
+        previous_lock: &PinnedPackMeta,
+    ) -> Vec<(String, String, String)> {
+        self.mods
+            .iter()
+            .filter_map(|(mod_name, pinned_mod)| {
+                let previous_mod = previous_lock.mods.get(mod_name)?;
+                (previous_mod.version != pinned_mod.version).then(|| {
+                    (
+                        mod_name.clone(),
+                        previous_mod.version.clone(),
+                        pinned_mod.version.clone(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Get the changelogs of pinned mods between versions, given as (mod name, old version, new version)
+    ///
+    /// Only mods pinned from Modrinth have changelogs. Mods whose changelogs can't be fetched are skipped
+    pub async fn get_changelogs(
+        &self,
+        pack_metadata: &ModpackMeta,
+        version_changes: Vec<(String, String, String)>,
+    ) -> Vec<ModChangelog> {
+        let mut changelogs = vec![];
+        for (mod_name, from_version, to_version) in version_changes {
+            let Some(project_id) = self
+                .mods
+                .get(&mod_name)
+                .and_then(|pinned_mod| pinned_mod.project_id.as_ref())
+            else {
+                continue;
+            };
+            let mod_meta = match pack_metadata.mods.get(&mod_name) {
+                Some(mod_meta) => mod_meta.clone(),
+                None => match ModMeta::new(&mod_name) {
+                    Ok(mod_meta) => mod_meta,
+                    Err(e) => {
+                        eprintln!("Failed to get the changelog of {mod_name}: {e}");
+                        continue;
+                    }
+                },
+            };
+            match self
+                .modrinth
+                .get_changelogs(
+                    project_id,
+                    &mod_meta,
+                    pack_metadata,
+                    &from_version,
+                    &to_version,
+                )
+                .await
+            {
+                Ok(versions) => changelogs.push(ModChangelog {
+                    name: mod_name,
+                    from_version,
+                    to_version,
+                    versions,
+                }),
+                Err(e) => eprintln!("Failed to get the changelog of {mod_name}: {e}"),
+            }
+        }
+        changelogs
+    }
+
+    /// Check that every pinned download url still exists, without downloading anything
+    pub async fn verify_urls(&self) -> Vec<UrlStatus> {
+        let client = reqwest::Client::new();
+        let mut url_statuses = vec![];
+        for (mod_name, pinned_mod) in self.mods.iter() {
+            for source in pinned_mod.source.iter() {
+                let FileSource::Download { url, .. } = source else {
+                    continue;
+                };
+                let mut url_status = UrlStatus {
+                    name: mod_name.clone(),
+                    url: url.clone(),
+                    status: None,
+                    dead: false,
+                    error: None,
+                };
+                match client.head(url).send().await {
+                    Ok(response) => {
+                        let status = response.status();
+                        url_status.status = Some(status.as_u16());
+                        url_status.dead = status == reqwest::StatusCode::NOT_FOUND
+                            || status == reqwest::StatusCode::GONE;
+                    }
+                    Err(e) => url_status.error = Some(e.to_string()),
+                }
+                url_statuses.push(url_status);
+            }
+        }
+        url_statuses
+    }
+
+    /// Find new download urls for mods with dead urls, keeping the same pinned versions
+    ///
+    /// Files are first looked up by hash, since the same file may just have moved. Otherwise the same version is resolved again.
+    /// Returns the names of the mods that were repaired
+    pub async fn repair_dead_urls(
+        &mut self,
+        pack_metadata: &ModpackMeta,
+        url_statuses: &[UrlStatus],
+    ) -> Vec<String> {
+        let mut repaired_mods = vec![];
+        for url_status in url_statuses.iter().filter(|s| s.dead) {
+            let Some(pinned_mod) = self.mods.get(&url_status.name) else {
+                continue;
+            };
+            let dead_source = pinned_mod.source.iter().position(
+                |source| matches!(source, FileSource::Download { url, .. } if url == &url_status.url),
+            );
+            let Some(dead_source) = dead_source else {
+                continue;
+            };
+
+            // Modrinth can look files up by sha512 or sha1
+            let known_hash = ["sha512", "sha1"].into_iter().find_map(|algorithm| {
+                pinned_mod.source[dead_source]
+                    .hashes()
+                    .get(algorithm)
+                    .map(|hash| (algorithm, hash.clone()))
+            });
+            let new_source = match known_hash {
+                Some((algorithm, hash)) => self.modrinth.get_file_by_hash(algorithm, &hash).await,
+                None => Err(anyhow::format_err!("No hash to look up")),
+            };
+            if let Ok(mut new_source) = new_source {
+                // Keep installing the file under the same name, eg. if it was renamed to avoid a collision
+                *new_source.filename_mut() = pinned_mod.source[dead_source].filename().into();
+                if let FileSource::Download { url, .. } = &new_source {
+                    if url != &url_status.url {
+                        println!("Repaired {}: {} -> {url}", url_status.name, url_status.url);
+                        self.mods
+                            .get_mut(&url_status.name)
+                            .expect("Mod should still be pinned")
+                            .source[dead_source] = new_source;
+                        repaired_mods.push(url_status.name.clone());
+                        continue;
+                    }
+                }
+            }
+
+            let mod_meta = pack_metadata
+                .mods
+                .get(&url_status.name)
+                .cloned()
+                .unwrap_or(ModMeta {
+                    name: url_status.name.clone(),
+                    ..Default::default()
+                })
+                .version(&pinned_mod.version);
+            let resolved = match get_resolution_target(&mod_meta, pack_metadata) {
+                Ok(Some(resolved_metadata)) => {
+                    self.resolve_mod(resolved_metadata, pack_metadata).await
+                }
+                Ok(None) => continue,
+                Err(e) => Err(e),
+            };
+            match resolved {
+                Ok(mut resolved) if resolved.version == pinned_mod.version => {
+                    println!(
+                        "Repaired {}@{} by resolving it again",
+                        url_status.name, resolved.version
+                    );
+                    self.rename_colliding_files(&url_status.name, &mut resolved.source);
+                    self.mods
+                        .get_mut(&url_status.name)
+                        .expect("Mod should still be pinned")
+                        .source = resolved.source;
+                    repaired_mods.push(url_status.name.clone());
+                }
+                Ok(resolved) => eprintln!(
+                    "No replacement for {}@{} found (newest available is {})",
+                    url_status.name, pinned_mod.version, resolved.version
+                ),
+                Err(e) => eprintln!(
+                    "No replacement for {}@{} found: {e}",
+                    url_status.name, pinned_mod.version
+                ),
+            }
+        }
+        repaired_mods
     }
 
     fn get_dependent_mods(&self, mod_name: &str) -> BTreeSet<String> {
@@ -320,12 +1753,10 @@ impl PinnedPackMeta {
         dependent_mods
     }
 
-    pub fn remove_mod(
-        &mut self,
-        mod_name: &str,
-        pack_metadata: &ModpackMeta,
-        force: bool,
-    ) -> Result<()> {
+    /// Remove a mod from the lock
+    ///
+    /// Unless `prune` is false, any mods that are no longer needed by the pack are removed too
+    pub fn remove_mod(&mut self, mod_name: &str, force: bool, prune: bool) -> Result<()> {
         if !self.mods.contains_key(mod_name) {
             eprintln!(
                 "Skipping removing non-existent mod {} from modpack",
@@ -350,20 +1781,21 @@ impl PinnedPackMeta {
         if let Some(removed_mod) = removed_mod {
             println!("Removed mod {}@{}", mod_name, removed_mod.version);
         }
-        self.prune_mods(pack_metadata)?;
+        self.top_level_mods.remove(mod_name);
+        if prune {
+            self.prune_mods()?;
+        }
         Ok(())
     }
 
-    /// Remove all mods from lockfile that aren't in the pack metadata or depended on by another mod
-    fn prune_mods(&mut self, pack_metadata: &ModpackMeta) -> Result<()> {
+    /// Remove all mods from lockfile that aren't top-level mods or (transitively) depended on by one
+    fn prune_mods(&mut self) -> Result<()> {
+        let required_mods = self.dep_closure(self.top_level_mods.iter().cloned());
         let mods_to_remove: BTreeSet<String> = self
             .mods
             .keys()
-            .filter(|mod_name| {
-                !pack_metadata.mods.contains_key(*mod_name)
-                    && self.get_dependent_mods(mod_name).len() == 0
-            })
-            .map(|mod_name| mod_name.into())
+            .filter(|mod_name| !required_mods.contains(*mod_name))
+            .cloned()
             .collect();
 
         for mod_name in mods_to_remove {
@@ -376,22 +1808,185 @@ impl PinnedPackMeta {
         Ok(())
     }
 
+    /// Start recording every provider attempt made while resolving mods
+    pub fn enable_trace(&mut self) {
+        self.tracer = Arc::new(ResolutionTracer::enabled());
+        self.modrinth.set_tracer(self.tracer.clone());
+    }
+
+    /// Pin the newest or oldest compatible versions of mods, instead of following the pack's version selection
+    pub fn set_version_selection(&mut self, version_selection: VersionSelection) {
+        self.modrinth.set_version_selection(version_selection);
+    }
+
+    /// Take the provider attempts recorded so far
+    pub fn take_trace(&self) -> Vec<ResolutionAttempt> {
+        self.tracer.take_attempts()
+    }
+
+    /// Get the pinned mods, keyed by mod name
+    pub fn get_pinned_mods(&self) -> &BTreeMap<String, PinnedMod> {
+        &self.mods
+    }
+
+    /// Get the pinned modloader version, if any
+    pub fn loader_version(&self) -> Option<&str> {
+        self.loader_version.as_deref()
+    }
+
+    /// Pin the modloader version the pack asks for, or the latest stable version if it doesn't specify one
+    ///
+    /// Failing to look up the latest version is not fatal, since mods can still be pinned without it
+    pub async fn pin_loader_version(&mut self, modpack_meta: &ModpackMeta) {
+        if let Some(loader_version) = &modpack_meta.loader_version {
+            self.loader_version = Some(loader_version.clone());
+            return;
+        }
+        match Loader::new()
+            .get_latest_version(&modpack_meta.modloader, &modpack_meta.mc_version)
+            .await
+        {
+            Ok(loader_version) => {
+                println!(
+                    "Pinned {} loader version {loader_version}",
+                    modpack_meta.modloader.to_string()
+                );
+                self.loader_version = Some(loader_version);
+            }
+            Err(e) => eprintln!(
+                "Warning: Failed to find a {} loader version for minecraft {}: {e}",
+                modpack_meta.modloader.to_string(),
+                modpack_meta.mc_version
+            ),
+        }
+    }
+
     pub async fn init(
         &mut self,
         modpack_meta: &ModpackMeta,
-        ignore_transitive_versions: bool,
+        dependency_versions: DependencyVersions,
     ) -> Result<()> {
+        self.pin_loader_version(modpack_meta).await;
         for mod_meta in modpack_meta.iter_mods() {
-            self.pin_mod_and_deps(mod_meta, modpack_meta, ignore_transitive_versions)
+            self.pin_mod_and_deps(mod_meta, modpack_meta, dependency_versions)
                 .await?;
         }
         Ok(())
     }
 
-    pub fn save_to_file(&self, path: &PathBuf) -> Result<()> {
-        std::fs::write(
+    /// Re-resolve only the pinned dependencies, keeping the pins of the modpack's own mods as they are
+    ///
+    /// Dependencies are re-pinned from the dependencies of the pinned mods, so dependencies no longer needed are dropped
+    pub async fn upgrade_deps(
+        &mut self,
+        modpack_meta: &ModpackMeta,
+        dependency_versions: DependencyVersions,
+    ) -> Result<()> {
+        self.mods
+            .retain(|mod_name, _| modpack_meta.mods.contains_key(mod_name));
+        for mod_name in modpack_meta.mods.keys() {
+            if !self.mods.contains_key(mod_name) {
+                eprintln!("Warning: {mod_name} is not pinned. Run a full update to pin it");
+            }
+        }
+
+        let top_level_deps: Vec<(String, Vec<ModMeta>)> = self
+            .mods
+            .iter()
+            .map(|(mod_name, pinned_mod)| {
+                (
+                    mod_name.clone(),
+                    pinned_mod.deps.iter().flatten().cloned().collect(),
+                )
+            })
+            .collect();
+        for (mod_name, deps) in top_level_deps {
+            let mut deps: BTreeSet<ModMeta> = deps
+                .into_iter()
+                .filter(|dep| !self.mods.contains_key(&dep.name))
+                .map(|dep| dependency_versions.apply(dep))
+                .collect();
+            while !deps.is_empty() {
+                let layer: Vec<ModMeta> = deps
+                    .into_iter()
+                    .map(|dep| modpack_meta.apply_dependency_override(dep, &mod_name))
+                    .collect();
+                deps = BTreeSet::from_iter(
+                    self.pin_mods(&layer, modpack_meta)
+                        .await
+                        .with_context(|| format!("Failed to pin dependencies of {mod_name}"))?,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Clean up a lock that was edited by hand, without resolving anything again
+    ///
+    /// Duplicate files of a mod are removed, hashes are lowercased, empty dependency lists are cleared and top level mods
+    /// that are no longer pinned are forgotten. Returns a description of each fix
+    pub fn normalize(&mut self) -> Vec<String> {
+        let mut fixes = vec![];
+        for (mod_name, pinned_mod) in self.mods.iter_mut() {
+            let mut filenames = BTreeSet::new();
+            let source_count = pinned_mod.source.len();
+            pinned_mod
+                .source
+                .retain(|source| filenames.insert(source.filename().to_string()));
+            if pinned_mod.source.len() != source_count {
+                fixes.push(format!(
+                    "Removed {} duplicate file(s) of {mod_name}",
+                    source_count - pinned_mod.source.len()
+                ));
+            }
+            for source in pinned_mod.source.iter_mut() {
+                if source
+                    .hashes()
+                    .values()
+                    .any(|hash| hash.chars().any(|c| c.is_ascii_uppercase()))
+                {
+                    source
+                        .hashes_mut()
+                        .values_mut()
+                        .for_each(|hash| hash.make_ascii_lowercase());
+                    fixes.push(format!("Lowercased the hashes of {}", source.filename()));
+                }
+            }
+            if pinned_mod.deps.as_ref().is_some_and(BTreeSet::is_empty) {
+                pinned_mod.deps = None;
+                fixes.push(format!("Cleared the empty dependency list of {mod_name}"));
+            }
+        }
+        let mods = &self.mods;
+        self.top_level_mods.retain(|mod_name| {
+            let pinned = mods.contains_key(mod_name);
+            if !pinned {
+                fixes.push(format!(
+                    "Removed {mod_name} from the top level mods, since it isn't pinned"
+                ));
+            }
+            pinned
+        });
+        fixes
+    }
+
+    /// Record the pinned mods that are in the modpack as top-level mods, for locks written before top-level mods were.
+    /// Otherwise the first mod added to such a lock would be its only top-level mod, and every other mod would be pruned
+    fn fill_top_level_mods(&mut self, pack_metadata: &ModpackMeta) {
+        self.top_level_mods = self
+            .mods
+            .keys()
+            .filter(|mod_name| pack_metadata.mods.contains_key(*mod_name))
+            .cloned()
+            .collect();
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        write_atomic(
             path,
-            toml::to_string(self).expect("Pinned pack meta should be serializable"),
+            toml::to_string(self)
+                .expect("Pinned pack meta should be serializable")
+                .as_bytes(),
         )?;
         // println!("Saved modpack.lock to {}", path.display());
         Ok(())
@@ -410,7 +2005,16 @@ impl PinnedPackMeta {
 
     pub async fn load_from_directory(
         directory: &Path,
-        ignore_transitive_versions: bool,
+        dependency_versions: DependencyVersions,
+    ) -> Result<Self> {
+        let mut modpack_lock = Self::read_from_directory(directory, dependency_versions).await?;
+        modpack_lock.ignore_rules.add_dir(directory)?;
+        Ok(modpack_lock)
+    }
+
+    async fn read_from_directory(
+        directory: &Path,
+        dependency_versions: DependencyVersions,
     ) -> Result<Self> {
         let modpack_lock_file_path = directory.join(PathBuf::from(MODPACK_LOCK_FILENAME));
         if !modpack_lock_file_path.exists() {
@@ -418,36 +2022,61 @@ impl PinnedPackMeta {
             new_modpack_lock
                 .init(
                     &ModpackMeta::load_from_directory(directory)?,
-                    ignore_transitive_versions,
+                    dependency_versions,
                 )
                 .await?;
             return Ok(new_modpack_lock);
         };
-        let modpack_lock_contents = std::fs::read_to_string(modpack_lock_file_path)?;
-        Ok(toml::from_str(&modpack_lock_contents)?)
+        let modpack_lock_contents = std::fs::read_to_string(&modpack_lock_file_path)?;
+        match toml::from_str::<Self>(&modpack_lock_contents) {
+            Ok(mut modpack_lock) => {
+                if modpack_lock.top_level_mods.is_empty() && !modpack_lock.mods.is_empty() {
+                    modpack_lock
+                        .fill_top_level_mods(&ModpackMeta::load_from_directory(directory)?);
+                }
+                Ok(modpack_lock)
+            }
+            Err(e) if auto_recover() => {
+                let malformed_path = move_aside_malformed(&modpack_lock_file_path)?;
+                eprintln!(
+                    "{} is malformed ({}). Moved it to {} and regenerating it...",
+                    modpack_lock_file_path.display(),
+                    e.message(),
+                    malformed_path.display()
+                );
+                let mut new_modpack_lock = Self::new();
+                new_modpack_lock
+                    .init(
+                        &ModpackMeta::load_from_directory(directory)?,
+                        dependency_versions,
+                    )
+                    .await?;
+                new_modpack_lock.save_to_file(&modpack_lock_file_path)?;
+                Ok(new_modpack_lock)
+            }
+            Err(e) => Err(anyhow::Error::new(e).context(format!(
+                "{} is malformed, eg. since writing it was interrupted. Run `mcmpmgr update` to regenerate it, or rerun with --auto-recover to do so automatically",
+                modpack_lock_file_path.display()
+            ))),
+        }
     }
 
-    pub async fn load_from_current_directory(ignore_transitive_versions: bool) -> Result<Self> {
-        Self::load_from_directory(&std::env::current_dir()?, ignore_transitive_versions).await
+    pub async fn load_from_current_directory(
+        dependency_versions: DependencyVersions,
+    ) -> Result<Self> {
+        Self::load_from_directory(&std::env::current_dir()?, dependency_versions).await
     }
 
     /// Load a pack from a git repo cloned to a temporary directory
     pub async fn load_from_git_repo(
         git_url: &str,
-        ignore_transitive_versions: bool,
+        dependency_versions: DependencyVersions,
     ) -> Result<(Self, tempfile::TempDir)> {
-        let pack_dir = tempfile::tempdir()?;
-        println!(
-            "Cloning modpack from git repo {} to {:#?}...",
-            git_url,
-            pack_dir.path()
-        );
-        let _repo = git2::Repository::clone(git_url, pack_dir.path())?;
+        let pack_dir = clone_git_repo(git_url)?;
 
         let modpack_meta = ModpackMeta::load_from_directory(pack_dir.path())?;
         let pinned_pack_meta =
-            PinnedPackMeta::load_from_directory(pack_dir.path(), ignore_transitive_versions)
-                .await?;
+            PinnedPackMeta::load_from_directory(pack_dir.path(), dependency_versions).await?;
 
         println!(
             "Loaded modpack '{}' (MC {} - {}) from git",
@@ -459,3 +2088,1651 @@ impl PinnedPackMeta {
         Ok((pinned_pack_meta, pack_dir))
     }
 }
+
+/// Download a file into place, keeping what has been downloaded so far in a `.part` file beside it,
+/// so that an interrupted download can be resumed with a range request (in this run or a later one)
+///
+/// The whole file is verified against its hashes before it is moved into place
+async fn download_file(url: &str, path: &Path, hashes: &BTreeMap<String, String>) -> Result<()> {
+    let mut part_path = OsString::from(path.as_os_str());
+    part_path.push(PART_EXTENSION);
+    let part_path = PathBuf::from(part_path);
+    let client = reqwest::Client::new();
+    let mut attempt = 1;
+    while let Err(e) = download_part(&client, url, &part_path).await {
+        if attempt == DOWNLOAD_ATTEMPTS {
+            return Err(e.context(format!(
+                "Gave up after {DOWNLOAD_ATTEMPTS} attempts. Run again to resume the download"
+            )));
+        }
+        eprintln!("Download from {url} was interrupted ({e}). Resuming...");
+        attempt += 1;
+    }
+
+    let contents = tokio::fs::read(&part_path).await?;
+    if let Err(e) = verify_hashes(hashes, &contents) {
+        // Resuming from a bad partial download would never succeed, so the next attempt starts over
+        let _ = tokio::fs::remove_file(&part_path).await;
+        return Err(e);
+    }
+    tokio::fs::rename(&part_path, path).await?;
+    Ok(())
+}
+
+/// Download the rest of a file into its `.part` file, resuming after the bytes already in it if the server supports it
+async fn download_part(client: &reqwest::Client, url: &str, part_path: &Path) -> Result<()> {
+    let downloaded = tokio::fs::metadata(part_path)
+        .await
+        .map_or(0, |metadata| metadata.len());
+    let mut request = client.get(url);
+    if downloaded > 0 {
+        request = request.header(RANGE, format!("bytes={downloaded}-"));
+    }
+    let response = request.send().await?;
+    let (mut response, mut part_file) = match response.status() {
+        StatusCode::PARTIAL_CONTENT if downloaded > 0 => {
+            let part_file = tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(part_path)
+                .await?;
+            (response, part_file)
+        }
+        // Everything was already downloaded
+        StatusCode::RANGE_NOT_SATISFIABLE if downloaded > 0 => return Ok(()),
+        // The server doesn't support ranges, so the download starts over
+        _ => (
+            response.error_for_status()?,
+            tokio::fs::File::create(part_path).await?,
+        ),
+    };
+    while let Some(chunk) = response.chunk().await? {
+        part_file.write_all(&chunk).await?;
+    }
+    part_file.flush().await?;
+    Ok(())
+}
+
+/// Point a download url at a mirror, keeping the url's path and query under the mirror's base url
+fn mirror_url(url: &str, mirror: &Url) -> Result<Url> {
+    let url = Url::parse(url).with_context(|| format!("Invalid download url {url}"))?;
+    let mut mirrored_url = mirror.clone();
+    mirrored_url.set_path(&format!(
+        "{}/{}",
+        mirror.path().trim_end_matches('/'),
+        url.path().trim_start_matches('/')
+    ));
+    mirrored_url.set_query(url.query());
+    Ok(mirrored_url)
+}
+
+/// Get the mod that should actually be resolved in place of a mod, or None if it shouldn't be pinned at all
+///
+/// Overridden mods stay pinned under their original name, but resolve to their replacement.
+/// Mods outside the pack's allowlist are an error, since skipping them could leave out a required dependency
+fn get_resolution_target<'a>(
+    mod_metadata: &'a ModMeta,
+    pack_metadata: &'a ModpackMeta,
+) -> Result<Option<&'a ModMeta>> {
+    if pack_metadata.forbidden_mods.contains(&mod_metadata.name) {
+        println!("Skipping adding forbidden mod {}...", mod_metadata.name);
+        return Ok(None);
+    }
+
+    if let Some(replacement) = pack_metadata.get_override(mod_metadata) {
+        if pack_metadata.forbidden_mods.contains(&replacement.name) {
+            println!(
+                "Skipping adding {} since its replacement {} is forbidden...",
+                mod_metadata.name, replacement.name
+            );
+            return Ok(None);
+        }
+        check_mod_allowed(&replacement.name, pack_metadata)?;
+        println!(
+            "Using {}@{} in place of {}",
+            replacement.name, replacement.version, mod_metadata.name
+        );
+        Ok(Some(replacement))
+    } else {
+        check_mod_allowed(&mod_metadata.name, pack_metadata)?;
+        Ok(Some(mod_metadata))
+    }
+}
+
+fn check_mod_allowed(mod_name: &str, pack_metadata: &ModpackMeta) -> Result<()> {
+    if !pack_metadata.is_mod_allowed(mod_name) {
+        anyhow::bail!(
+            "Mod {mod_name} is not in the modpack's allowed mods. Use `mcmpmgr allow {mod_name}` to allow it"
+        );
+    }
+    Ok(())
+}
+
+/// Provider failures other than a missing mod stop resolution rather than silently trying the next provider
+fn provider_unavailable(
+    mod_metadata: &ModMeta,
+    mod_provider: ModProvider,
+    e: ProviderError,
+) -> anyhow::Error {
+    let hint = match e.kind {
+        ProviderErrorKind::Auth => "Check your credentials for it",
+        _ => "Check your connection or try again later",
+    };
+    McmpmgrError::ProviderUnavailable {
+        name: mod_metadata.name.clone(),
+        version: mod_metadata.version.clone(),
+        provider: mod_provider,
+        kind: e.kind,
+        message: e.to_string(),
+        hint,
+    }
+    .into()
+}
+
+/// Report why a provider's version of a mod didn't suit the modpack, rather than just that no provider had the mod
+fn prefer_version_conflict(error: anyhow::Error, provider_error: &ProviderError) -> anyhow::Error {
+    match (McmpmgrError::find(&error), provider_error.mcmpmgr_error()) {
+        (
+            Some(McmpmgrError::ModNotFound { .. }),
+            Some(conflict @ McmpmgrError::VersionConflict { .. }),
+        ) => conflict.clone().into(),
+        _ => error,
+    }
+}
+
+/// Get the id of the commit checked out in a git repo
+pub fn git_head_commit(repo_dir: &Path) -> Result<String> {
+    let repo = git2::Repository::open(repo_dir)?;
+    let head = repo.head()?.peel_to_commit()?;
+    Ok(head.id().to_string())
+}
+
+/// Count the commits checked out in a git repo since `commit`
+///
+/// Returns `None` if `commit` isn't in the checked out history, eg. since it was force pushed over
+pub fn git_commits_since(repo_dir: &Path, commit: &str) -> Result<Option<usize>> {
+    let repo = git2::Repository::open(repo_dir)?;
+    let head = repo.head()?.peel_to_commit()?.id();
+    let Ok(commit) = git2::Oid::from_str(commit) else {
+        return Ok(None);
+    };
+    if repo.find_commit(commit).is_err()
+        || (head != commit && !repo.graph_descendant_of(head, commit)?)
+    {
+        return Ok(None);
+    }
+    let (ahead, _) = repo.graph_ahead_behind(head, commit)?;
+    Ok(Some(ahead))
+}
+
+/// Clone a modpack git repo into a new temporary directory
+pub fn clone_git_repo(git_url: &str) -> Result<tempfile::TempDir> {
+    let pack_dir = tempfile::tempdir()?;
+    println!(
+        "Cloning modpack from git repo {} to {:#?}...",
+        git_url,
+        pack_dir.path()
+    );
+    let _repo = git2::Repository::clone(git_url, pack_dir.path())?;
+    Ok(pack_dir)
+}
+
+#[tokio::test]
+async fn test_download_mods_replaces_corrupt_file() {
+    use crate::test_server::{self, MockResponse};
+
+    let jar_contents: &[u8] = b"definitely a real mod jar";
+    let addr = test_server::serve(move |_| MockResponse::new("200 OK", jar_contents)).await;
+
+    let mods_dir = tempfile::tempdir().unwrap();
+    let mods_path = mods_dir.path().to_path_buf();
+    std::fs::write(mods_path.join("test-mod.jar"), b"truncated").unwrap();
+
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "test-mod".into(),
+        PinnedMod::test("1.0.0").download(
+            &format!("http://{addr}/test-mod.jar"),
+            BTreeMap::from([("sha512".into(), sha512_hex(jar_contents))]),
+        ),
+    );
+
+    pack_lock
+        .download_mods(&mods_path, DownloadSide::Both, false)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        std::fs::read(mods_path.join("test-mod.jar")).unwrap(),
+        jar_contents,
+        "Corrupt mod file was not replaced with the pinned download"
+    );
+}
+
+#[tokio::test]
+async fn test_pin_loader_version_from_pack() {
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric)
+        .loader_version("0.16.5");
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.pin_loader_version(&modpack_meta).await;
+    assert_eq!(pack_lock.loader_version(), Some("0.16.5"));
+
+    let pack_lock: PinnedPackMeta = toml::from_str(&toml::to_string(&pack_lock).unwrap()).unwrap();
+    assert_eq!(pack_lock.loader_version(), Some("0.16.5"));
+}
+
+#[tokio::test]
+async fn test_pin_mods_rejects_disallowed_deps() {
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric);
+    modpack_meta.allow_mod("sodium");
+
+    let mut pack_lock = PinnedPackMeta::new();
+    let result = pack_lock
+        .pin_mods(&[ModMeta::new("fabric-api").unwrap()], &modpack_meta)
+        .await;
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("not in the modpack's allowed mods"));
+    assert!(pack_lock.get_pinned_mods().is_empty());
+}
+
+#[tokio::test]
+async fn test_verify_urls() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let status = if request.starts_with("HEAD /live.jar") {
+            "200 OK"
+        } else {
+            "404 Not Found"
+        };
+        MockResponse::new(status, "")
+    })
+    .await;
+
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "dead-mod".into(),
+        PinnedMod::test("1.0.0").download(&format!("http://{addr}/dead.jar"), Default::default()),
+    );
+    pack_lock.mods.insert(
+        "live-mod".into(),
+        PinnedMod::test("1.0.0").download(&format!("http://{addr}/live.jar"), Default::default()),
+    );
+
+    let url_statuses = pack_lock.verify_urls().await;
+    assert_eq!(url_statuses.len(), 2);
+    assert_eq!(url_statuses[0].name, "dead-mod");
+    assert!(url_statuses[0].dead);
+    assert_eq!(url_statuses[0].status, Some(404));
+    assert_eq!(url_statuses[1].name, "live-mod");
+    assert!(!url_statuses[1].dead);
+}
+
+#[test]
+fn test_select_groups() {
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric);
+    modpack_meta.add_mod_to_group("farming", "farmers-delight");
+    modpack_meta.add_mod_to_group("storage", "storage-drawers");
+
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "farmers-delight".into(),
+        PinnedMod::test("1.0.0").deps(&["fabric-api", "farming-lib"]),
+    );
+    pack_lock.mods.insert(
+        "storage-drawers".into(),
+        PinnedMod::test("1.0.0").deps(&["fabric-api"]),
+    );
+    pack_lock.mods.insert(
+        "sodium".into(),
+        PinnedMod::test("1.0.0").deps(&["fabric-api"]),
+    );
+    pack_lock
+        .mods
+        .insert("fabric-api".into(), PinnedMod::test("1.0.0"));
+    pack_lock
+        .mods
+        .insert("farming-lib".into(), PinnedMod::test("1.0.0"));
+
+    pack_lock
+        .select_groups(&modpack_meta, &["storage".into()])
+        .unwrap();
+    // Shared dependencies are still installed for the ungrouped mods
+    assert_eq!(
+        pack_lock.excluded_mods,
+        BTreeSet::from(["farmers-delight".into(), "farming-lib".into()])
+    );
+
+    pack_lock
+        .select_groups(&modpack_meta, &["farming".into(), "storage".into()])
+        .unwrap();
+    assert!(pack_lock.excluded_mods.is_empty());
+
+    assert!(pack_lock
+        .select_groups(&modpack_meta, &["missing".into()])
+        .is_err());
+}
+
+#[test]
+fn test_side_overrides() {
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "debug-mod".into(),
+        PinnedMod::test("1.0.0").sides(
+            crate::providers::SideSupport::Required,
+            crate::providers::SideSupport::Unsupported,
+        ),
+    );
+    let debug_mod = &pack_lock.mods["debug-mod"];
+    assert!(!pack_lock.should_install("debug-mod", debug_mod, DownloadSide::Server, false));
+
+    pack_lock.set_side_overrides(&BTreeMap::from([("debug-mod".into(), DownloadSide::Both)]));
+    let debug_mod = &pack_lock.mods["debug-mod"];
+    assert!(pack_lock.should_install("debug-mod", debug_mod, DownloadSide::Server, false));
+}
+
+#[test]
+fn test_dependency_versions() {
+    let dep = ModMeta::new("sodium").unwrap().version("mc1.20.1-0.5.3");
+    assert_eq!(
+        DependencyVersions::Exact.apply(dep.clone()).version,
+        "mc1.20.1-0.5.3"
+    );
+    assert_eq!(DependencyVersions::Latest.apply(dep).version, "*");
+    assert_eq!(
+        "Exact".parse::<DependencyVersions>().unwrap(),
+        DependencyVersions::Exact
+    );
+    assert_eq!(
+        DependencyVersions::Latest
+            .to_string()
+            .parse::<DependencyVersions>()
+            .unwrap(),
+        DependencyVersions::Latest
+    );
+    assert!("locked".parse::<DependencyVersions>().is_err());
+}
+
+#[test]
+fn test_prune_mods() {
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric);
+    for mod_name in ["create", "fabric-api", "sodium"] {
+        modpack_meta = modpack_meta
+            .add_mod(&ModMeta::new(mod_name).unwrap())
+            .unwrap();
+    }
+
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "create".into(),
+        PinnedMod::test("1.0.0").deps(&["fabric-api", "porting-lib"]),
+    );
+    pack_lock.mods.insert(
+        "porting-lib".into(),
+        PinnedMod::test("1.0.0").deps(&["forge-config-api"]),
+    );
+    pack_lock
+        .mods
+        .insert("forge-config-api".into(), PinnedMod::test("1.0.0"));
+    pack_lock
+        .mods
+        .insert("fabric-api".into(), PinnedMod::test("1.0.0"));
+    pack_lock.mods.insert(
+        "sodium".into(),
+        PinnedMod::test("1.0.0").deps(&["fabric-api"]),
+    );
+    pack_lock.top_level_mods =
+        BTreeSet::from(["create".into(), "fabric-api".into(), "sodium".into()]);
+
+    // fabric-api is both a top-level mod and a dependency of create, so only create's other deps are pruned
+    modpack_meta = modpack_meta.remove_mod("create");
+    // Deps are left pinned until the next prune when not pruning
+    pack_lock.remove_mod("create", false, false).unwrap();
+    assert!(pack_lock.mods.contains_key("porting-lib"));
+    assert!(pack_lock.mods.contains_key("forge-config-api"));
+    pack_lock.prune_mods().unwrap();
+    assert_eq!(
+        pack_lock.mods.keys().cloned().collect::<BTreeSet<String>>(),
+        BTreeSet::from(["fabric-api".into(), "sodium".into()])
+    );
+
+    // Once fabric-api is no longer top-level, it is still kept while sodium depends on it
+    modpack_meta = modpack_meta.remove_mod("fabric-api");
+    pack_lock.top_level_mods.remove("fabric-api");
+    pack_lock.prune_mods().unwrap();
+    assert!(pack_lock.mods.contains_key("fabric-api"));
+
+    modpack_meta = modpack_meta.remove_mod("sodium");
+    pack_lock.remove_mod("sodium", false, true).unwrap();
+    assert!(pack_lock.mods.is_empty());
+    assert!(pack_lock.top_level_mods.is_empty());
+}
+
+#[tokio::test]
+async fn test_load_malformed_lock() {
+    let pack_dir = tempfile::tempdir().unwrap();
+    for malformed_lock in ["", "[mods.sodium\nversion = "] {
+        std::fs::write(pack_dir.path().join(MODPACK_LOCK_FILENAME), malformed_lock).unwrap();
+        let e = PinnedPackMeta::load_from_directory(pack_dir.path(), DependencyVersions::Latest)
+            .await
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("is malformed"));
+        assert!(e.to_string().contains("mcmpmgr update"));
+    }
+}
+
+#[tokio::test]
+async fn test_check_compat() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        // lib has no versions for the pack, so framework and anything depending on it can't be added
+        let body = if request.starts_with("GET /v2/projects?") {
+            r#"[
+                {"id": "AAAA", "slug": "addon", "client_side": "required", "server_side": "required"},
+                {"id": "FFFF", "slug": "framework", "client_side": "required", "server_side": "required"},
+                {"id": "LLLL", "slug": "lib", "client_side": "required", "server_side": "required"}
+            ]"#
+        } else if request.starts_with("GET /v2/project/addon/version") {
+            r#"[{"date_published": "2024-01-01T00:00:00Z", "dependencies": [{"dependency_type": "required", "project_id": "FFFF"}], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "AAAA", "id": "A100", "version_number": "1.0.0"}]"#
+        } else if request.starts_with("GET /v2/project/framework/version") {
+            r#"[{"date_published": "2024-01-01T00:00:00Z", "dependencies": [{"dependency_type": "required", "project_id": "LLLL"}], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "FFFF", "id": "F100", "version_number": "1.0.0"}]"#
+        } else if request.starts_with("GET /v2/project/LLLL ") {
+            r#"{"id": "LLLL", "slug": "lib", "client_side": "required", "server_side": "required"}"#
+        } else if request.starts_with("GET /v2/project/FFFF ") {
+            r#"{"id": "FFFF", "slug": "framework", "client_side": "required", "server_side": "required"}"#
+        } else if request.starts_with("GET /v2/project/FFFF/version") {
+            r#"[{"date_published": "2024-01-01T00:00:00Z", "dependencies": [{"dependency_type": "required", "project_id": "LLLL"}], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "FFFF", "id": "F100", "version_number": "1.0.0"}]"#
+        } else {
+            "[]"
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric);
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+
+    let e = pack_lock
+        .check_compat(
+            &ModMeta::new("addon").unwrap(),
+            &modpack_meta,
+            DependencyVersions::Latest,
+        )
+        .await
+        .err()
+        .unwrap();
+    assert!(e
+        .to_string()
+        .contains("addon -> framework can't be resolved for minecraft 1.20.1"));
+    assert!(pack_lock.mods.is_empty());
+}
+
+#[tokio::test]
+async fn test_pin_curseforge_files() {
+    use crate::test_server::{self, MockResponse};
+
+    let mods_dir = tempfile::tempdir().unwrap();
+    std::fs::write(mods_dir.path().join("jei.jar"), "jei contents").unwrap();
+    std::fs::write(mods_dir.path().join("unknown.jar"), "unknown contents").unwrap();
+    std::fs::write(mods_dir.path().join("notes.txt"), "not a mod").unwrap();
+    let jei_fingerprint = curseforge::fingerprint(b"jei contents");
+
+    let addr = test_server::serve(move |request| {
+        if request.starts_with("POST /v1/fingerprints ") {
+            MockResponse::json(format!(
+                r#"{{"data": {{"exactMatches": [{{"file": {{"modId": 238222, "displayName": "JEI 15.2.0.27",
+                    "fileName": "jei-15.2.0.27.jar", "downloadUrl": "https://edge.forgecdn.net/files/jei-15.2.0.27.jar",
+                    "hashes": [], "releaseType": 1, "fileFingerprint": {jei_fingerprint}}}}}]}}}}"#
+            ))
+        } else {
+            MockResponse::json(r#"{"data": [{"id": 238222, "slug": "jei"}]}"#)
+        }
+    })
+    .await;
+    let curseforge = CurseForge::with_api_url(&format!("http://{addr}"));
+
+    // Packs that don't use the CurseForge provider can't import from it
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Forge);
+    let mut pack_lock = PinnedPackMeta::new();
+    assert!(pack_lock
+        .pin_curseforge_files(mods_dir.path(), &modpack_meta, &curseforge)
+        .await
+        .is_err());
+    assert!(pack_lock.mods.is_empty());
+
+    let modpack_meta = modpack_meta.provider(ModProvider::CurseForge);
+    let imported_mods = pack_lock
+        .pin_curseforge_files(mods_dir.path(), &modpack_meta, &curseforge)
+        .await
+        .unwrap();
+    assert_eq!(imported_mods.len(), 1);
+    assert_eq!(imported_mods[0].name, "jei");
+    assert_eq!(imported_mods[0].providers, Some(vec![ModProvider::Raw]));
+    assert_eq!(
+        imported_mods[0].download_url.as_deref(),
+        Some("https://edge.forgecdn.net/files/jei-15.2.0.27.jar")
+    );
+    assert!(pack_lock.top_level_mods.contains("jei"));
+    let jei = &pack_lock.mods["jei"];
+    assert_eq!(jei.project_id.as_deref(), Some("238222"));
+    // Files CurseForge has no sha1 for are verified against the file on disk
+    let FileSource::Download { hashes, .. } = &jei.source[0] else {
+        panic!("jei should be pinned to a download")
+    };
+    assert_eq!(hashes, &all_hashes(b"jei contents"));
+}
+
+#[test]
+fn test_rename_colliding_files() {
+    let source = |filename: &str| FileSource::Download {
+        url: format!("https://example.com/{filename}"),
+        hashes: BTreeMap::new(),
+        filename: filename.into(),
+        role: FileRole::Primary,
+        signature: None,
+    };
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "fancy-lib".into(),
+        PinnedMod {
+            source: vec![source("lib.jar")],
+            ..PinnedMod::test("1.0.0")
+        },
+    );
+
+    let mut sources = vec![source("lib.jar"), source("other.jar")];
+    pack_lock.rename_colliding_files("plain-lib", &mut sources);
+    assert_eq!(sources[0].filename(), "plain-lib-lib.jar");
+    assert_eq!(sources[1].filename(), "other.jar");
+    // A mod's own files don't collide with themselves, eg. when it is pinned again
+    let mut sources = vec![source("lib.jar")];
+    pack_lock.rename_colliding_files("fancy-lib", &mut sources);
+    assert_eq!(sources[0].filename(), "lib.jar");
+}
+
+#[tokio::test]
+async fn test_download_file_resumes() {
+    use crate::test_server::{self, MockResponse};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let contents: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    let supports_ranges = Arc::new(AtomicBool::new(true));
+    let server_contents = contents.clone();
+    let server_supports_ranges = supports_ranges.clone();
+    let addr = test_server::serve(move |request| {
+        let range_start = request
+            .to_lowercase()
+            .lines()
+            .find_map(|line| line.strip_prefix("range: bytes=").map(String::from))
+            .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok())
+            .filter(|_| server_supports_ranges.load(Ordering::SeqCst));
+        match range_start {
+            Some(range_start) => {
+                MockResponse::new("206 Partial Content", &server_contents[range_start..]).header(
+                    &format!(
+                        "Content-Range: bytes {range_start}-{}/{}",
+                        server_contents.len() - 1,
+                        server_contents.len()
+                    ),
+                )
+            }
+            // Full downloads are cut off halfway through while ranges are supported
+            None if server_supports_ranges.load(Ordering::SeqCst) => {
+                MockResponse::new("200 OK", server_contents.clone())
+                    .cut_off_at(server_contents.len() / 2)
+            }
+            None => MockResponse::new("200 OK", server_contents.clone()),
+        }
+    })
+    .await;
+
+    let mods_dir = tempfile::tempdir().unwrap();
+    let mod_path = mods_dir.path().join("big.jar");
+    let url = format!("http://{addr}/big.jar");
+    download_file(&url, &mod_path, &all_hashes(&contents))
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&mod_path).unwrap(), contents);
+    assert!(!mods_dir.path().join("big.jar.part").exists());
+
+    // A server that ignores ranges sends the whole file again, replacing the partial download
+    supports_ranges.store(false, Ordering::SeqCst);
+    std::fs::remove_file(&mod_path).unwrap();
+    std::fs::write(mods_dir.path().join("big.jar.part"), b"stale").unwrap();
+    download_file(&url, &mod_path, &all_hashes(&contents))
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&mod_path).unwrap(), contents);
+
+    // A bad partial download is thrown away so the next attempt starts over
+    std::fs::remove_file(&mod_path).unwrap();
+    assert!(download_file(&url, &mod_path, &all_hashes(b"other"))
+        .await
+        .is_err());
+    assert!(!mod_path.exists());
+    assert!(!mods_dir.path().join("big.jar.part").exists());
+}
+
+#[test]
+fn test_mirror_url() {
+    let url = "https://cdn.modrinth.com/data/AANobbMI/versions/1.0.0/sodium.jar";
+    assert_eq!(
+        mirror_url(url, &Url::parse("https://cdn.example.com").unwrap())
+            .unwrap()
+            .as_str(),
+        "https://cdn.example.com/data/AANobbMI/versions/1.0.0/sodium.jar"
+    );
+    assert_eq!(
+        mirror_url(
+            &format!("{url}?token=abc"),
+            &Url::parse("http://mirror.internal:8080/modrinth/").unwrap()
+        )
+        .unwrap()
+        .as_str(),
+        "http://mirror.internal:8080/modrinth/data/AANobbMI/versions/1.0.0/sodium.jar?token=abc"
+    );
+
+    let mut pack_lock = PinnedPackMeta::new();
+    assert!(pack_lock.set_mirror("not a url").is_err());
+    assert!(pack_lock.set_mirror("mailto:mirror@example.com").is_err());
+    pack_lock.set_mirror("https://cdn.example.com").unwrap();
+}
+
+#[test]
+fn test_prune_mods_diamond() {
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric);
+    for mod_name in ["create-addon", "create-other-addon"] {
+        modpack_meta = modpack_meta
+            .add_mod(&ModMeta::new(mod_name).unwrap())
+            .unwrap();
+    }
+
+    // create-addon -> (addon-lib -> addon-lib-core, create) -> fabric-api
+    // create-other-addon -> create -> fabric-api
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "create-addon".into(),
+        PinnedMod::test("1.0.0").deps(&["addon-lib", "create"]),
+    );
+    pack_lock.mods.insert(
+        "create-other-addon".into(),
+        PinnedMod::test("1.0.0").deps(&["create"]),
+    );
+    pack_lock.mods.insert(
+        "addon-lib".into(),
+        PinnedMod::test("1.0.0").deps(&["addon-lib-core", "fabric-api"]),
+    );
+    pack_lock
+        .mods
+        .insert("addon-lib-core".into(), PinnedMod::test("1.0.0"));
+    pack_lock.mods.insert(
+        "create".into(),
+        PinnedMod::test("1.0.0").deps(&["fabric-api"]),
+    );
+    pack_lock
+        .mods
+        .insert("fabric-api".into(), PinnedMod::test("1.0.0"));
+    pack_lock.top_level_mods = BTreeSet::from(["create-addon".into(), "create-other-addon".into()]);
+
+    // The whole orphaned chain is pruned, while the mods still reachable from the other addon are kept
+    modpack_meta = modpack_meta.remove_mod("create-addon");
+    pack_lock.remove_mod("create-addon", false, true).unwrap();
+    assert_eq!(
+        pack_lock.mods.keys().cloned().collect::<BTreeSet<String>>(),
+        BTreeSet::from([
+            "create".into(),
+            "create-other-addon".into(),
+            "fabric-api".into()
+        ])
+    );
+}
+
+#[test]
+fn test_plan_mod_changes() {
+    let mods_dir = tempfile::tempdir().unwrap();
+    std::fs::write(mods_dir.path().join("sodium.jar"), b"sodium").unwrap();
+    std::fs::write(mods_dir.path().join("lithium.jar"), b"outdated lithium").unwrap();
+    std::fs::write(mods_dir.path().join("removed-mod.jar"), b"removed").unwrap();
+
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "sodium".into(),
+        PinnedMod::test("1.0.0").download("https://example.com/sodium.jar", all_hashes(b"sodium")),
+    );
+    pack_lock.mods.insert(
+        "lithium".into(),
+        PinnedMod::test("1.0.0")
+            .download("https://example.com/lithium.jar", all_hashes(b"lithium")),
+    );
+    pack_lock.mods.insert(
+        "iris".into(),
+        PinnedMod::test("1.0.0").download("https://example.com/iris.jar", all_hashes(b"iris")),
+    );
+
+    let changes = pack_lock
+        .plan_mod_changes(mods_dir.path(), DownloadSide::Client, false)
+        .unwrap();
+    assert_eq!(changes.to_download, vec!["iris.jar", "lithium.jar"]);
+    assert_eq!(changes.to_delete, vec!["removed-mod.jar"]);
+    // Nothing is changed
+    assert!(mods_dir.path().join("removed-mod.jar").exists());
+}
+
+#[tokio::test]
+async fn test_ignored_files_survive_sync() {
+    let pack_dir = tempfile::tempdir().unwrap();
+    let mods_dir = tempfile::tempdir().unwrap();
+    std::fs::write(pack_dir.path().join(MODPACK_LOCK_FILENAME), "[mods]\n").unwrap();
+    std::fs::write(
+        pack_dir.path().join(crate::ignore::IGNORE_FILENAME),
+        "perf-*.jar\n",
+    )
+    .unwrap();
+    std::fs::write(
+        mods_dir.path().join(crate::ignore::IGNORE_FILENAME),
+        "manual.jar\n",
+    )
+    .unwrap();
+    for filename in [
+        "perf-sodium.jar",
+        "manual.jar",
+        "unpinned.jar",
+        "unpinned.jar.part",
+    ] {
+        std::fs::write(mods_dir.path().join(filename), filename).unwrap();
+    }
+
+    let pack_lock =
+        PinnedPackMeta::load_from_directory(pack_dir.path(), DependencyVersions::Latest)
+            .await
+            .unwrap();
+    let mut changes = pack_lock
+        .plan_mod_changes(mods_dir.path(), DownloadSide::Client, false)
+        .unwrap();
+    changes.to_delete.sort();
+    assert_eq!(changes.to_delete, ["unpinned.jar", "unpinned.jar.part"]);
+
+    pack_lock
+        .download_mods(&mods_dir.path().to_path_buf(), DownloadSide::Client, false)
+        .await
+        .unwrap();
+    let mut remaining: Vec<String> = std::fs::read_dir(mods_dir.path())
+        .unwrap()
+        .map(|file| file.unwrap().file_name().to_string_lossy().into())
+        .collect();
+    remaining.sort();
+    assert_eq!(
+        remaining,
+        [
+            crate::ignore::IGNORE_FILENAME,
+            "manual.jar",
+            "perf-sodium.jar"
+        ]
+    );
+}
+
+#[test]
+fn test_get_drift() {
+    let mut pack_lock = PinnedPackMeta::new();
+    for mod_name in ["sodium", "lithium", "optifine"] {
+        pack_lock
+            .mods
+            .insert(mod_name.into(), PinnedMod::test("1.0.0"));
+        pack_lock.top_level_mods.insert(mod_name.into());
+    }
+
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric);
+    for mod_name in ["sodium", "lithium@2.0.0", "create"] {
+        modpack_meta = modpack_meta
+            .add_mod(&ModMeta::new(mod_name).unwrap())
+            .unwrap();
+    }
+    assert_eq!(
+        pack_lock.get_drift(&modpack_meta),
+        vec![
+            LockDrift::Unpinned("create".into()),
+            LockDrift::VersionMismatch {
+                name: "lithium".into(),
+                version: "2.0.0".into(),
+                pinned_version: "1.0.0".into()
+            },
+            LockDrift::Removed("optifine".into()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_side_override_survives_update() {
+    use crate::providers::SideSupport;
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let zoom_version = r#"{"date_published": "2024-01-01T00:00:00Z", "dependencies": [], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "ZZZZ", "id": "Z100", "version_number": "1.0.0"}"#;
+        let body = if request.starts_with("GET /v2/projects?") && request.contains("addon") {
+            r#"[{"id": "AAAA", "slug": "addon", "client_side": "required", "server_side": "required"}]"#.to_string()
+        } else if request.starts_with("GET /v2/projects?") {
+            r#"[{"id": "ZZZZ", "slug": "zoom", "client_side": "required", "server_side": "required"}]"#.into()
+        } else if request.starts_with("GET /v2/project/addon/version") {
+            r#"[{"date_published": "2024-01-01T00:00:00Z", "dependencies": [{"dependency_type": "required", "project_id": "ZZZZ", "version_id": "Z100"}], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "AAAA", "id": "A100", "version_number": "1.0.0"}]"#.into()
+        } else {
+            format!("[{zoom_version}]")
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    // zoom is both-sided on Modrinth, but forced client only in the pack.
+    // addon is pinned first, so zoom is first pinned as addon's dependency
+    let mut zoom = ModMeta::new("zoom@1.0.0").unwrap();
+    zoom.client_side = Some(true);
+    zoom.server_side = Some(false);
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric)
+        .loader_version("0.16.5")
+        .add_mod(&ModMeta::new("addon").unwrap())
+        .unwrap()
+        .add_mod(&zoom)
+        .unwrap();
+
+    for _ in 0..2 {
+        let mut pack_lock = PinnedPackMeta::new();
+        pack_lock.modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+        pack_lock
+            .init(&modpack_meta, DependencyVersions::Latest)
+            .await
+            .unwrap();
+        let pinned_zoom = &pack_lock.get_pinned_mods()["zoom"];
+        assert_eq!(pinned_zoom.client_side, SideSupport::Required);
+        assert_eq!(pinned_zoom.server_side, SideSupport::Unsupported);
+
+        // The override is kept through the lock file too
+        let pack_lock: PinnedPackMeta =
+            toml::from_str(&toml::to_string(&pack_lock).unwrap()).unwrap();
+        assert_eq!(
+            pack_lock.get_pinned_mods()["zoom"].server_side,
+            SideSupport::Unsupported
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_upgrade_deps() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        // Only the dependency should be looked up
+        let body = if request.starts_with("GET /v2/projects?") && request.contains("lib") {
+            r#"[{"id": "LLLL", "slug": "lib", "client_side": "required", "server_side": "required"}]"#
+        } else if request.starts_with("GET /v2/project/lib/version") {
+            r#"[{"date_published": "2024-01-01T00:00:00Z", "dependencies": [], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "LLLL", "id": "L200", "version_number": "2.0.0"}]"#
+        } else {
+            "[]"
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric)
+        .add_mod(&ModMeta::new("addon").unwrap())
+        .unwrap();
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    pack_lock.mods.insert(
+        "addon".into(),
+        PinnedMod::test("1.0.0").deps(&["lib@1.0.0"]),
+    );
+    pack_lock
+        .mods
+        .insert("lib".into(), PinnedMod::test("1.0.0"));
+    pack_lock
+        .mods
+        .insert("old-lib".into(), PinnedMod::test("1.0.0"));
+
+    pack_lock
+        .upgrade_deps(&modpack_meta, DependencyVersions::Latest)
+        .await
+        .unwrap();
+    let pinned_mods = pack_lock.get_pinned_mods();
+    assert_eq!(pinned_mods["addon"].version, "1.0.0");
+    assert_eq!(pinned_mods["lib"].version, "2.0.0");
+    assert!(!pinned_mods.contains_key("old-lib"));
+}
+
+#[tokio::test]
+async fn test_get_outdated_mods() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let project_id = if request.contains("sodium") {
+            "SSSS"
+        } else {
+            "LLLL"
+        };
+        let version = |version_number: &str, date_published: &str| {
+            format!(
+                r#"{{"date_published": "{date_published}", "dependencies": [], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "{project_id}", "id": "{project_id}{version_number}", "version_number": "{version_number}", "version_type": "release"}}"#
+            )
+        };
+        let body = if request.starts_with("GET /v2/projects?") {
+            format!(
+                r#"[{{"id": "{project_id}", "slug": "{}", "client_side": "required", "server_side": "required"}}]"#,
+                if project_id == "SSSS" { "sodium" } else { "lithium" }
+            )
+        } else if request.contains("/version") {
+            format!(
+                "[{}, {}]",
+                version("2.0.0", "2024-02-01T00:00:00Z"),
+                version("1.0.0", "2024-01-01T00:00:00Z")
+            )
+        } else {
+            "[]".into()
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    // sodium is held back to 1.0.0 by the pack, while lithium can update to any version
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric)
+        .add_mod(&ModMeta::new("sodium@1.0.0").unwrap())
+        .unwrap()
+        .add_mod(&ModMeta::new("lithium").unwrap())
+        .unwrap();
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    pack_lock
+        .mods
+        .insert("sodium".into(), PinnedMod::test("1.0.0"));
+    pack_lock
+        .mods
+        .insert("lithium".into(), PinnedMod::test("1.0.0"));
+
+    let outdated_mods = pack_lock.get_outdated_mods(&modpack_meta).await;
+    let lithium = &outdated_mods[0];
+    assert_eq!(lithium.name, "lithium");
+    assert!(lithium.outdated);
+    assert_eq!(lithium.update_version.as_deref(), Some("2.0.0"));
+    assert_eq!(lithium.latest_version.as_deref(), Some("2.0.0"));
+    let sodium = &outdated_mods[1];
+    assert_eq!(sodium.name, "sodium");
+    assert!(!sodium.outdated);
+    assert_eq!(sodium.update_version.as_deref(), Some("1.0.0"));
+    assert_eq!(sodium.latest_version.as_deref(), Some("2.0.0"));
+}
+
+#[tokio::test]
+async fn test_get_changelogs() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let body = if request.starts_with("GET /v2/project/SSSS/version") {
+            r#"[
+                {"date_published": "2024-03-01T00:00:00Z", "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "SSSS", "id": "S300", "version_number": "3.0.0", "changelog": "Faster chunks"},
+                {"date_published": "2024-02-01T00:00:00Z", "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "SSSS", "id": "S200", "version_number": "2.0.0"},
+                {"date_published": "2024-01-01T00:00:00Z", "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "SSSS", "id": "S100", "version_number": "1.0.0", "changelog": "Initial release"}
+            ]"#
+        } else {
+            "[]"
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric);
+    let mut previous_lock = PinnedPackMeta::new();
+    previous_lock
+        .mods
+        .insert("sodium".into(), PinnedMod::test("1.0.0").project_id("SSSS"));
+    previous_lock
+        .mods
+        .insert("raw-mod".into(), PinnedMod::test("Unknown"));
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    pack_lock
+        .mods
+        .insert("sodium".into(), PinnedMod::test("3.0.0").project_id("SSSS"));
+    pack_lock
+        .mods
+        .insert("raw-mod".into(), PinnedMod::test("Unknown"));
+    pack_lock.mods.insert(
+        "new-mod".into(),
+        PinnedMod::test("1.0.0").project_id("NNNN"),
+    );
+
+    let version_changes = pack_lock.get_version_changes(&previous_lock);
+    assert_eq!(
+        version_changes,
+        [("sodium".into(), "1.0.0".into(), "3.0.0".into())]
+    );
+    let changelogs = pack_lock
+        .get_changelogs(&modpack_meta, version_changes)
+        .await;
+    assert_eq!(
+        changelog_markdown(&changelogs),
+        "## Changelog\n\n### sodium (1.0.0 -> 3.0.0)\n\n#### 3.0.0\n\nFaster chunks\n\n#### 2.0.0\n\nNo changelog\n"
+    );
+}
+
+#[test]
+fn test_prune_download_cache() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let downloads_dir = tempfile::tempdir().unwrap();
+    let download = |filename: &str, contents: &[u8]| {
+        let path = downloads_dir.path().join(filename);
+        std::fs::write(&path, contents).unwrap();
+        (all_hashes(contents), path)
+    };
+    let (pinned_hashes, pinned_path) = download("pinned.jar", b"pinned");
+    let (old_hashes, old_path) = download("old.jar", b"no longer pinned");
+    let (recent_hashes, recent_path) = download("recent.jar", b"recently used");
+
+    let download_cache = DownloadCache::in_dir(cache_dir.path().into());
+    download_cache.insert(&pinned_hashes, &pinned_path);
+    download_cache.insert(&old_hashes, &old_path);
+    download_cache.insert(&recent_hashes, &recent_path);
+    // Files are kept between runs, even once the downloaded copies are gone
+    std::fs::remove_dir_all(downloads_dir.path()).unwrap();
+    let download_cache = DownloadCache::in_dir(cache_dir.path().into());
+    assert_eq!(
+        download_cache.get(&old_hashes).unwrap(),
+        b"no longer pinned"
+    );
+
+    let (algorithm, hash) = strongest_hash(&old_hashes).unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(DownloadCache::cached_file_path(
+            cache_dir.path(),
+            algorithm,
+            &hash,
+        ))
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60))
+        .unwrap();
+
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "pinned".into(),
+        PinnedMod::test("1.0.0").download("https://example.com/pinned.jar", pinned_hashes.clone()),
+    );
+
+    let summary = download_cache
+        .prune(std::slice::from_ref(&pack_lock), Some(7))
+        .unwrap();
+    assert_eq!(
+        summary,
+        CachePruneSummary {
+            removed_files: 1,
+            reclaimed_bytes: b"no longer pinned".len() as u64,
+        }
+    );
+    assert!(download_cache.get(&old_hashes).is_none());
+    assert!(download_cache.get(&recent_hashes).is_some());
+
+    download_cache.prune(&[pack_lock], None).unwrap();
+    assert!(download_cache.get(&recent_hashes).is_none());
+    assert_eq!(download_cache.get(&pinned_hashes).unwrap(), b"pinned");
+}
+
+#[tokio::test]
+async fn test_suggest_mods() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let (status, body) = if request.starts_with("GET /v2/project/jei ") {
+            ("404 Not Found", "")
+        } else if request.starts_with("GET /v2/project/sodium ") {
+            (
+                "200 OK",
+                r#"{"id": "AANobbMI", "slug": "sodium", "client_side": "required", "server_side": "unsupported"}"#,
+            )
+        } else if request.starts_with("GET /v2/search?query=jei&") {
+            (
+                "200 OK",
+                r#"{"hits": [
+                    {"slug": "jei-fabric", "title": "Just Enough Items", "description": "View items and recipes", "downloads": 1000},
+                    {"slug": "emi", "title": "EMI", "description": "A featureful recipe viewer", "downloads": 500}
+                ]}"#,
+            )
+        } else {
+            ("404 Not Found", "")
+        };
+        MockResponse::json_status(status, body)
+    })
+    .await;
+
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric);
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+
+    let suggestions = pack_lock
+        .suggest_mods(&ModMeta::new("jei").unwrap(), &modpack_meta)
+        .await
+        .unwrap()
+        .unwrap();
+    let slugs: Vec<&str> = suggestions.iter().map(|s| s.slug.as_str()).collect();
+    assert_eq!(slugs, ["jei-fabric", "emi"]);
+
+    // Nothing is suggested for mods that exist, or that aren't looked up on Modrinth
+    assert!(pack_lock
+        .suggest_mods(&ModMeta::new("sodium").unwrap(), &modpack_meta)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(pack_lock
+        .suggest_mods(
+            &ModMeta::new("jei")
+                .unwrap()
+                .url("https://example.com/jei.jar"),
+            &modpack_meta
+        )
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_normalize_lock() {
+    let mut pack_lock: PinnedPackMeta = toml::from_str(
+        r#"
+        top_level_mods = ["sodium", "removed-mod"]
+
+        [mods.sodium]
+        version = "1.0.0"
+        deps = []
+        server_side = "Unsupported"
+        client_side = "Required"
+
+        [[mods.sodium.source]]
+        [mods.sodium.source.Download]
+        url = "https://example.com/sodium.jar"
+        sha1 = "ABC"
+        filename = "sodium.jar"
+
+        [[mods.sodium.source]]
+        [mods.sodium.source.Download]
+        url = "https://example.com/sodium.jar"
+        sha1 = "ABC"
+        filename = "sodium.jar"
+        "#,
+    )
+    .unwrap();
+
+    let fixes = pack_lock.normalize();
+    assert_eq!(
+        fixes,
+        [
+            "Removed 1 duplicate file(s) of sodium",
+            "Lowercased the hashes of sodium.jar",
+            "Cleared the empty dependency list of sodium",
+            "Removed removed-mod from the top level mods, since it isn't pinned",
+        ]
+    );
+    let sodium = &pack_lock.get_pinned_mods()["sodium"];
+    assert_eq!(sodium.source.len(), 1);
+    assert_eq!(sodium.source[0].hashes()["sha1"], "abc");
+    assert!(sodium.deps.is_none());
+    assert_eq!(pack_lock.top_level_mods, BTreeSet::from(["sodium".into()]));
+
+    // Normalizing is idempotent, and the legacy hash fields are written in their current form
+    let normalized = toml::to_string(&pack_lock).unwrap();
+    assert!(normalized.contains("hashes"));
+    let mut pack_lock: PinnedPackMeta = toml::from_str(&normalized).unwrap();
+    assert!(pack_lock.normalize().is_empty());
+    assert_eq!(toml::to_string(&pack_lock).unwrap(), normalized);
+}
+
+#[tokio::test]
+async fn test_verify_signature() {
+    const PUBLIC_KEY: &str = "RWQBAgMEBQYHCAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQBAgMEBQYHCFw2wYDWFe5OJCY3kL6MSBDLCkDEWw8bTBhiL2OXiZFPU3O/s4LZ2n8fapnESgOry9CdWGybCPzOmIAQxAT56QI=
+trusted comment: timestamp:1700000000\tfile:signed-mod.jar\thashed
+5K6AAx4/pzkKvtHEOVMR3Y4rVowFKfA1bF9sw/MlLX4P59DQxaDXwFVv2F+3+j/c8IyqeyG+eiXBJQotwsfTDQ==
+";
+    let mods_dir = tempfile::tempdir().unwrap();
+    let mod_path = mods_dir.path().join("signed-mod.jar");
+    std::fs::write(&mod_path, b"signed mod jar").unwrap();
+
+    let mut pack_lock = PinnedPackMeta::new();
+    // Nothing is checked until the pack has a signing key
+    assert!(pack_lock
+        .verify_signature(Some("not a signature"), &mod_path)
+        .await
+        .is_ok());
+
+    assert!(pack_lock.set_signing_key("not a key").is_err());
+    pack_lock.set_signing_key(PUBLIC_KEY).unwrap();
+    assert!(pack_lock
+        .verify_signature(Some(SIGNATURE), &mod_path)
+        .await
+        .is_ok());
+
+    std::fs::write(&mod_path, b"tampered mod jar").unwrap();
+    assert!(pack_lock
+        .verify_signature(Some(SIGNATURE), &mod_path)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_verify_signature_requires_signature() {
+    let mods_dir = tempfile::tempdir().unwrap();
+    let mod_path = mods_dir.path().join("unsigned-mod.jar");
+    std::fs::write(&mod_path, b"unsigned mod jar").unwrap();
+
+    let mut pack_lock = PinnedPackMeta::new();
+    assert!(pack_lock.verify_signature(None, &mod_path).await.is_ok());
+    pack_lock
+        .set_signing_key("RWQBAgMEBQYHCAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4")
+        .unwrap();
+    let e = pack_lock
+        .verify_signature(None, &mod_path)
+        .await
+        .unwrap_err();
+    assert!(e.to_string().contains("has no signature"));
+}
+
+#[test]
+fn test_git_commits_since() {
+    let repo_dir = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init(repo_dir.path()).unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    let commit = |message: &str| {
+        let tree_id = repo.index().unwrap().write_tree().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            parent.as_ref().into_iter().collect::<Vec<_>>().as_slice(),
+        )
+        .unwrap()
+        .to_string()
+    };
+
+    let installed_commit = commit("Initial pack");
+    assert_eq!(git_head_commit(repo_dir.path()).unwrap(), installed_commit);
+    assert_eq!(
+        git_commits_since(repo_dir.path(), &installed_commit).unwrap(),
+        Some(0)
+    );
+    commit("Add sodium");
+    let latest_commit = commit("Update sodium");
+    assert_eq!(
+        git_commits_since(repo_dir.path(), &installed_commit).unwrap(),
+        Some(2)
+    );
+    assert_eq!(git_head_commit(repo_dir.path()).unwrap(), latest_commit);
+    // Commits that aren't in the history can't be counted from
+    assert_eq!(
+        git_commits_since(repo_dir.path(), &"0".repeat(40)).unwrap(),
+        None
+    );
+    assert_eq!(
+        git_commits_since(repo_dir.path(), "not a commit").unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_prefix_load_order() {
+    let mut pack_lock = PinnedPackMeta::new();
+    for (mod_name, filename, load_after) in [
+        ("unhinted", "unhinted.jar", &[][..]),
+        ("core", "core.jar", &[]),
+        ("addon", "addon.jar", &["core"]),
+        // Hints on mods that aren't pinned are ignored
+        (
+            "addon-extra",
+            "addon-extra.jar",
+            &["addon", "core", "not-pinned"],
+        ),
+    ] {
+        pack_lock.mods.insert(
+            mod_name.into(),
+            PinnedMod::test("1.0.0")
+                .download(
+                    &format!("https://example.com/{filename}"),
+                    Default::default(),
+                )
+                .load_after(load_after),
+        );
+    }
+    // The hints are kept in the lock
+    let lock_contents = toml::to_string(&pack_lock).unwrap();
+    assert!(lock_contents.contains("load_after = [\"core\"]"));
+    assert_eq!(lock_contents.matches("load_after").count(), 2);
+
+    pack_lock.prefix_load_order().unwrap();
+    let filenames: BTreeMap<&str, &str> = pack_lock
+        .mods
+        .iter()
+        .map(|(mod_name, pinned_mod)| (mod_name.as_str(), pinned_mod.source[0].filename()))
+        .collect();
+    assert_eq!(
+        filenames,
+        BTreeMap::from([
+            ("addon", "10_addon.jar"),
+            ("addon-extra", "20_addon-extra.jar"),
+            ("core", "00_core.jar"),
+            ("unhinted", "unhinted.jar"),
+        ])
+    );
+
+    pack_lock.mods.insert(
+        "core".into(),
+        PinnedMod::test("1.0.0")
+            .download("https://example.com/core.jar", Default::default())
+            .load_after(&["addon-extra"]),
+    );
+    assert!(pack_lock
+        .prefix_load_order()
+        .unwrap_err()
+        .to_string()
+        .contains("cycle"));
+}
+
+#[tokio::test]
+async fn test_pinned_game_version() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let body = if request.starts_with("GET /v2/project/PPPP/version") {
+            r#"[
+                {"date_published": "2024-02-01T00:00:00Z", "files": [], "loaders": ["fabric"], "game_versions": ["1.20.2"], "project_id": "PPPP", "id": "P200", "version_number": "2.0.0"},
+                {"date_published": "2024-01-01T00:00:00Z", "files": [], "loaders": ["fabric"], "game_versions": ["1.20", "1.20.1"], "project_id": "PPPP", "id": "P100", "version_number": "1.0.0"}
+            ]"#
+        } else {
+            "[]"
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.2", crate::modpack::ModLoader::Fabric);
+    modpack_meta.accepted_mc_versions = vec!["1.20.1".into(), "1.20".into()];
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    pack_lock
+        .mods
+        .insert("parent".into(), PinnedMod::test("1.0.0").project_id("PPPP"));
+
+    // The first of the pack's minecraft versions that the pinned version supports
+    assert_eq!(
+        pack_lock
+            .pinned_game_version("parent", &modpack_meta)
+            .await
+            .unwrap(),
+        "1.20.1"
+    );
+    pack_lock
+        .mods
+        .insert("parent".into(), PinnedMod::test("2.0.0").project_id("PPPP"));
+    assert_eq!(
+        pack_lock
+            .pinned_game_version("parent", &modpack_meta)
+            .await
+            .unwrap(),
+        "1.20.2"
+    );
+    // A minecraft version override on the mod is used as is
+    modpack_meta.mods.insert(
+        "parent".into(),
+        ModMeta::new("parent").unwrap().mc_version("1.20"),
+    );
+    assert_eq!(
+        pack_lock
+            .pinned_game_version("parent", &modpack_meta)
+            .await
+            .unwrap(),
+        "1.20"
+    );
+    assert!(pack_lock
+        .pinned_game_version("not-pinned", &modpack_meta)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_resolve_mod_errors() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let (status, body) = if request.contains("flaky-mod") {
+            ("503 Service Unavailable", "{}")
+        } else if request.starts_with("GET /v2/projects") && request.contains("old-mod") {
+            (
+                "200 OK",
+                r#"[{"id": "AAAA", "slug": "old-mod", "client_side": "required", "server_side": "required"}]"#,
+            )
+        } else if request.starts_with("GET /v2/project/old-mod/version") {
+            (
+                "200 OK",
+                r#"[{"date_published": "2023-01-01T00:00:00Z", "dependencies": [], "files": [], "loaders": ["fabric"], "game_versions": ["1.19.2"], "project_id": "AAAA", "id": "BBBB", "version_number": "1.0.0"}]"#,
+            )
+        } else {
+            ("200 OK", "[]")
+        };
+        MockResponse::json_status(status, body)
+    })
+    .await;
+
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    let pack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric)
+        .strict_mc_version(true);
+    let resolve_error = |mod_name: &str| {
+        let mod_meta = ModMeta::new(mod_name).unwrap();
+        let pack_lock = &pack_lock;
+        let pack_meta = &pack_meta;
+        async move {
+            pack_lock
+                .resolve_mod(&mod_meta, pack_meta)
+                .await
+                .err()
+                .unwrap()
+        }
+    };
+
+    let error = resolve_error("missing-mod").await;
+    assert!(matches!(
+        McmpmgrError::find(&error),
+        Some(McmpmgrError::ModNotFound { name, .. }) if name == "missing-mod"
+    ));
+    // Saying why the mod can't be used is more useful than saying it wasn't found
+    let error = resolve_error("old-mod").await;
+    assert!(matches!(
+        McmpmgrError::find(&error),
+        Some(McmpmgrError::VersionConflict { supported, .. }) if supported == "1.19.2"
+    ));
+    let error = resolve_error("flaky-mod").await;
+    assert!(matches!(
+        McmpmgrError::find(&error),
+        Some(McmpmgrError::ProviderUnavailable {
+            kind: ProviderErrorKind::Transient,
+            ..
+        })
+    ));
+
+    // CurseForge is skipped, so the mod is still resolved with the next provider
+    let mod_meta = ModMeta::new("old-mod")
+        .unwrap()
+        .provider(ModProvider::CurseForge)
+        .provider(ModProvider::Modrinth);
+    let error = pack_lock
+        .resolve_mod(&mod_meta, &pack_meta)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(
+        McmpmgrError::find(&error),
+        Some(McmpmgrError::VersionConflict { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_keep_unpinned() {
+    let mods_dir = tempfile::tempdir().unwrap();
+    std::fs::write(mods_dir.path().join("personal.jar"), "personal.jar").unwrap();
+
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.set_keep_unpinned(true);
+    let changes = pack_lock
+        .plan_mod_changes(mods_dir.path(), DownloadSide::Client, false)
+        .unwrap();
+    assert!(changes.to_delete.is_empty());
+    pack_lock
+        .download_mods(&mods_dir.path().to_path_buf(), DownloadSide::Client, false)
+        .await
+        .unwrap();
+    assert!(mods_dir.path().join("personal.jar").is_file());
+
+    pack_lock.set_keep_unpinned(false);
+    pack_lock
+        .download_mods(&mods_dir.path().to_path_buf(), DownloadSide::Client, false)
+        .await
+        .unwrap();
+    assert!(!mods_dir.path().join("personal.jar").exists());
+}
+
+#[tokio::test]
+async fn test_resolve_drift_unpins_removed_mods() {
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock
+        .mods
+        .insert("sodium".into(), PinnedMod::test("1.0.0").deps(&["indium"]));
+    pack_lock
+        .mods
+        .insert("indium".into(), PinnedMod::test("1.0.0"));
+    pack_lock.mods.insert(
+        "optifine".into(),
+        PinnedMod::test("1.0.0").deps(&["optifine-dep"]),
+    );
+    pack_lock
+        .mods
+        .insert("optifine-dep".into(), PinnedMod::test("1.0.0"));
+    for mod_name in ["sodium", "indium", "optifine"] {
+        pack_lock.top_level_mods.insert(mod_name.into());
+    }
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric)
+        .add_mod(&ModMeta::new("sodium").unwrap())
+        .unwrap();
+
+    assert!(pack_lock
+        .resolve_drift(&modpack_meta, DependencyVersions::Latest)
+        .await
+        .unwrap());
+    // indium was removed from the pack, but sodium still depends on it
+    let pinned_mods: Vec<&String> = pack_lock.mods.keys().collect();
+    assert_eq!(pinned_mods, ["indium", "sodium"]);
+    assert!(!pack_lock
+        .resolve_drift(&modpack_meta, DependencyVersions::Latest)
+        .await
+        .unwrap());
+}
+
+#[test]
+fn test_select_only() {
+    let hashes = BTreeMap::from([("sha1".to_string(), "0".repeat(40))]);
+    let mut pack_lock = PinnedPackMeta::new();
+    pack_lock.mods.insert(
+        "sodium".into(),
+        PinnedMod::test("1.0.0")
+            .download("https://example.com/sodium.jar", hashes.clone())
+            .deps(&["indium"]),
+    );
+    pack_lock.mods.insert(
+        "indium".into(),
+        PinnedMod::test("1.0.0").download("https://example.com/indium.jar", hashes.clone()),
+    );
+    pack_lock.mods.insert(
+        "lithium".into(),
+        PinnedMod::test("1.0.0").download("https://example.com/lithium.jar", hashes.clone()),
+    );
+    let modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric);
+    let mods_dir = tempfile::tempdir().unwrap();
+    std::fs::write(mods_dir.path().join("unpinned.jar"), "unpinned").unwrap();
+
+    assert!(pack_lock
+        .select_only(&modpack_meta, &["missing".into()])
+        .is_err());
+    pack_lock
+        .select_only(&modpack_meta, &["Sodium".into()])
+        .unwrap();
+    let changes = pack_lock
+        .plan_mod_changes(mods_dir.path(), DownloadSide::Client, false)
+        .unwrap();
+    // Dependencies of the selected mods are installed too, and nothing is deleted
+    assert_eq!(changes.to_download, ["indium.jar", "sodium.jar"]);
+    assert!(changes.to_delete.is_empty());
+}
+
+#[tokio::test]
+async fn test_old_lock_keeps_mods_through_add_and_remove() {
+    use crate::test_server::{self, MockResponse};
+
+    let addr = test_server::serve(|request| {
+        let body = if request.starts_with("GET /v2/projects?") {
+            r#"[{"id": "IIII", "slug": "iris", "client_side": "required", "server_side": "unsupported"}]"#
+        } else {
+            r#"[{"date_published": "2024-01-01T00:00:00Z", "dependencies": [], "files": [], "loaders": ["fabric"], "game_versions": ["1.20.1"], "project_id": "IIII", "id": "I100", "version_number": "1.0.0"}]"#
+        };
+        MockResponse::json(body)
+    })
+    .await;
+
+    let pack_dir = tempfile::tempdir().unwrap();
+    let mut modpack_meta = ModpackMeta::new("test", "1.20.1", crate::modpack::ModLoader::Fabric)
+        .add_mod(&ModMeta::new("sodium").unwrap())
+        .unwrap()
+        .add_mod(&ModMeta::new("lithium").unwrap())
+        .unwrap();
+    modpack_meta.save_to_dir(pack_dir.path()).unwrap();
+    // Locks written before top-level mods were recorded have no top_level_mods
+    std::fs::write(
+        pack_dir.path().join(MODPACK_LOCK_FILENAME),
+        r#"[mods.sodium]
+source = []
+version = "1.0.0"
+deps = [{ name = "fabric-api", version = "*" }]
+server_side = "Required"
+client_side = "Required"
+
+[mods.lithium]
+source = []
+version = "1.0.0"
+server_side = "Required"
+client_side = "Required"
+
+[mods.fabric-api]
+source = []
+version = "1.0.0"
+server_side = "Required"
+client_side = "Required"
+"#,
+    )
+    .unwrap();
+
+    let mut pack_lock =
+        PinnedPackMeta::load_from_directory(pack_dir.path(), DependencyVersions::Latest)
+            .await
+            .unwrap();
+    pack_lock.modrinth = Modrinth::with_api_url(&format!("http://{addr}"));
+    let iris = ModMeta::new("iris").unwrap();
+    modpack_meta = modpack_meta.add_mod(&iris).unwrap();
+    pack_lock
+        .pin_mod_and_deps(&iris, &modpack_meta, DependencyVersions::Latest)
+        .await
+        .unwrap();
+    modpack_meta = modpack_meta.remove_mod("iris");
+    pack_lock.remove_mod("iris", false, true).unwrap();
+
+    let pinned_mods: Vec<&String> = pack_lock.get_pinned_mods().keys().collect();
+    assert_eq!(pinned_mods, ["fabric-api", "lithium", "sodium"]);
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// GitHub API url of the latest mcmpmgr release
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/WarrenHood/MCModpackManager/releases/latest";
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    html_url: String,
+}
+
+/// A newer mcmpmgr release than the one running
+#[derive(Debug)]
+pub struct AvailableUpdate {
+    pub version: semver::Version,
+    /// Page the release can be downloaded from
+    pub url: String,
+}
+
+/// Get the version of a release tag if it is newer than `current_version`
+///
+/// Anything before the version number in the tag (eg. `v` or `mcmpmgr-v`) is ignored
+fn newer_version(current_version: &str, tag: &str) -> Option<semver::Version> {
+    let current_version = semver::Version::parse(current_version).ok()?;
+    let tag_version =
+        semver::Version::parse(tag.trim_start_matches(|c: char| !c.is_ascii_digit())).ok()?;
+    (tag_version > current_version).then_some(tag_version)
+}
+
+async fn get_latest_release(url: &str) -> Result<GithubRelease> {
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("mcmpmgr/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(5))
+        .build()?;
+    Ok(client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// Check GitHub for a newer release of mcmpmgr
+///
+/// Returns `None` if the running version is up to date
+pub async fn check_for_update() -> Result<Option<AvailableUpdate>> {
+    let release = get_latest_release(LATEST_RELEASE_URL).await?;
+    Ok(
+        newer_version(env!("CARGO_PKG_VERSION"), &release.tag_name).map(|version| {
+            AvailableUpdate {
+                version,
+                url: release.html_url,
+            }
+        }),
+    )
+}
+
+#[test]
+fn test_newer_version() {
+    assert_eq!(
+        newer_version("0.2.0", "v0.3.1"),
+        Some(semver::Version::new(0, 3, 1))
+    );
+    assert_eq!(
+        newer_version("0.2.0", "mcmpmgr-v1.0.0"),
+        Some(semver::Version::new(1, 0, 0))
+    );
+    assert_eq!(newer_version("0.2.0", "v0.2.0"), None);
+    assert_eq!(newer_version("0.2.0", "v0.1.9"), None);
+    assert_eq!(newer_version("0.2.0", "nightly"), None);
+}
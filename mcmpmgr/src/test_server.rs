@@ -0,0 +1,79 @@
+//! A local HTTP server for tests, that answers requests with canned responses
+
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A response for the test server to send
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: String,
+    headers: Vec<String>,
+    body: Vec<u8>,
+    /// Number of body bytes to send before closing the connection, to simulate a dropped download
+    sent_len: Option<usize>,
+}
+
+impl MockResponse {
+    pub fn new(status: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: status.into(),
+            headers: vec![],
+            body: body.into(),
+            sent_len: None,
+        }
+    }
+
+    /// A successful json response
+    pub fn json(body: impl Into<Vec<u8>>) -> Self {
+        Self::new("200 OK", body).header("Content-Type: application/json")
+    }
+
+    /// A json response with a status other than 200 OK
+    pub fn json_status(status: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self::new(status, body).header("Content-Type: application/json")
+    }
+
+    pub fn header(mut self, header: &str) -> Self {
+        self.headers.push(header.into());
+        self
+    }
+
+    /// Close the connection after sending only part of the body
+    pub fn cut_off_at(mut self, sent_len: usize) -> Self {
+        self.sent_len = Some(sent_len);
+        self
+    }
+}
+
+/// Start a test server in the background, returning its address
+///
+/// Each request is answered with the response `respond` gives for the raw request text,
+/// eg. `GET /v2/project/sodium HTTP/1.1\r\n...`
+pub async fn serve<F>(respond: F) -> SocketAddr
+where
+    F: Fn(&str) -> MockResponse + Send + Sync + 'static,
+{
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let request_len = socket.read(&mut request).await.unwrap();
+            let response = respond(&String::from_utf8_lossy(&request[..request_len]));
+            let mut head = format!("HTTP/1.1 {}\r\n", response.status);
+            for header in response.headers.iter() {
+                head += &format!("{header}\r\n");
+            }
+            head += &format!(
+                "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                response.body.len()
+            );
+            let sent_len = response.sent_len.unwrap_or(response.body.len());
+            // The client may hang up early, eg. after a HEAD request
+            let _ = socket.write_all(head.as_bytes()).await;
+            let _ = socket.write_all(&response.body[..sent_len]).await;
+        }
+    });
+    addr
+}
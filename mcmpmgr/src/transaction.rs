@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use std::{
+    ffi::OsString,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    modpack::{ModpackMeta, MODPACK_FILENAME},
+    resolver::{PinnedPackMeta, MODPACK_LOCK_FILENAME},
+};
+
+/// Atomically replace the contents of a file
+///
+/// The contents are written to a temporary file beside `path`, which is then renamed over it,
+/// so an interrupted write never leaves a partially written file behind
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut transaction = FileTransaction::new();
+    transaction.stage(path, contents)?;
+    transaction.commit()
+}
+
+static AUTO_RECOVER: AtomicBool = AtomicBool::new(false);
+
+/// Set whether malformed state files (eg. the lock file) are moved aside and regenerated instead of being an error,
+/// for the rest of the process
+pub fn set_auto_recover(auto_recover: bool) {
+    AUTO_RECOVER.store(auto_recover, Ordering::SeqCst);
+}
+
+pub fn auto_recover() -> bool {
+    AUTO_RECOVER.load(Ordering::SeqCst)
+}
+
+/// Move a malformed file aside so that it can be regenerated, keeping it to recover anything from by hand
+///
+/// Returns where the file was moved to
+pub fn move_aside_malformed(path: &Path) -> Result<PathBuf> {
+    let mut malformed_path = OsString::from(path.as_os_str());
+    malformed_path.push(".malformed");
+    let malformed_path = PathBuf::from(malformed_path);
+    std::fs::rename(path, &malformed_path).with_context(|| {
+        format!(
+            "Failed to move malformed {} to {}",
+            path.display(),
+            malformed_path.display()
+        )
+    })?;
+    Ok(malformed_path)
+}
+
+pub const INSTANCE_LOCK_FILENAME: &str = ".mcmpmgr.lock";
+
+/// An advisory lock that stops multiple mcmpmgr instances from changing the same directory at once
+///
+/// The lock is released when this is dropped
+pub struct InstanceLock {
+    _lock_file: fslock::LockFile,
+}
+
+impl InstanceLock {
+    /// Lock a directory, failing straight away if another instance already holds the lock
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        let lock_path = dir.join(INSTANCE_LOCK_FILENAME);
+        let mut lock_file = fslock::LockFile::open(&lock_path)
+            .with_context(|| format!("Failed to open lock file {}", lock_path.display()))?;
+        if !lock_file.try_lock_with_pid()? {
+            anyhow::bail!(
+                "Another instance of mcmpmgr is running in {} (lock file: {})",
+                dir.display(),
+                lock_path.display()
+            );
+        }
+        Ok(Self {
+            _lock_file: lock_file,
+        })
+    }
+}
+
+/// Save the modpack meta and lock in a directory together, so they can't end up out of sync
+pub fn save_pack_and_lock(
+    dir: &Path,
+    modpack_meta: &ModpackMeta,
+    pack_lock: &PinnedPackMeta,
+) -> Result<()> {
+    let mut transaction = FileTransaction::new();
+    let modpack_meta_path = dir.join(MODPACK_FILENAME);
+    let existing_contents = std::fs::read_to_string(&modpack_meta_path).ok();
+    transaction.stage(
+        &modpack_meta_path,
+        modpack_meta
+            .to_toml_string(existing_contents.as_deref())?
+            .as_bytes(),
+    )?;
+    transaction.stage(
+        &dir.join(MODPACK_LOCK_FILENAME),
+        toml::to_string(pack_lock)?.as_bytes(),
+    )?;
+    transaction.commit()
+}
+
+/// A group of file writes that should either all be applied or not at all
+///
+/// Every file is written to a temporary file first, and only swapped into place once all of them are prepared.
+/// The previous contents are kept as `.bak` files until the commit completes, and are restored if it fails.
+#[derive(Default)]
+pub struct FileTransaction {
+    staged: Vec<(PathBuf, tempfile::NamedTempFile)>,
+}
+
+impl FileTransaction {
+    pub fn new() -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+
+    /// Prepare new contents for a file without touching the file itself
+    pub fn stage(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut temp_file = tempfile::NamedTempFile::new_in(dir)
+            .with_context(|| format!("Failed to create a temporary file in {}", dir.display()))?;
+        temp_file.write_all(contents)?;
+        temp_file.as_file().sync_all()?;
+        self.staged.push((path.to_path_buf(), temp_file));
+        Ok(())
+    }
+
+    /// Swap all staged files into place
+    pub fn commit(self) -> Result<()> {
+        // Back up everything first so that a failure part way through can be undone
+        let mut backups: Vec<(PathBuf, Option<PathBuf>)> = vec![];
+        for (path, _) in self.staged.iter() {
+            let backup = if path.exists() {
+                let backup = backup_path(path);
+                if let Err(e) = std::fs::copy(path, &backup) {
+                    remove_backups(&backups);
+                    return Err(e).with_context(|| format!("Failed to back up {}", path.display()));
+                }
+                Some(backup)
+            } else {
+                None
+            };
+            backups.push((path.clone(), backup));
+        }
+
+        let mut replaced: Vec<(PathBuf, Option<PathBuf>)> = vec![];
+        for ((path, temp_file), backup) in self.staged.into_iter().zip(backups.iter()) {
+            if let Err(e) = temp_file.persist(&path) {
+                restore_backups(&replaced);
+                remove_backups(&backups);
+                return Err(e.error).with_context(|| format!("Failed to write {}", path.display()));
+            }
+            replaced.push(backup.clone());
+        }
+
+        remove_backups(&backups);
+        Ok(())
+    }
+}
+
+/// Get the path a file is backed up to while a transaction is committed
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = OsString::from(path.as_os_str());
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+/// Put back the original contents of files that were already replaced
+fn restore_backups(replaced: &[(PathBuf, Option<PathBuf>)]) {
+    for (path, backup) in replaced {
+        let restore_result = match backup {
+            Some(backup) => std::fs::copy(backup, path).map(|_| ()),
+            None => std::fs::remove_file(path),
+        };
+        if let Err(e) = restore_result {
+            eprintln!("Failed to restore {}: {e}", path.display());
+        }
+    }
+}
+
+fn remove_backups(backups: &[(PathBuf, Option<PathBuf>)]) {
+    for backup in backups.iter().filter_map(|(_, backup)| backup.as_ref()) {
+        let _ = std::fs::remove_file(backup);
+    }
+}
+
+#[test]
+fn test_instance_lock() {
+    let dir = tempfile::tempdir().unwrap();
+    let lock = InstanceLock::acquire(dir.path()).unwrap();
+    assert!(InstanceLock::acquire(dir.path()).is_err());
+    drop(lock);
+    assert!(InstanceLock::acquire(dir.path()).is_ok());
+}
+
+#[test]
+fn test_move_aside_malformed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("modpack.lock");
+    std::fs::write(&path, "[mods").unwrap();
+
+    let malformed_path = move_aside_malformed(&path).unwrap();
+    assert_eq!(malformed_path, dir.path().join("modpack.lock.malformed"));
+    assert!(!path.exists());
+    assert_eq!(std::fs::read_to_string(malformed_path).unwrap(), "[mods");
+}
+
+#[test]
+fn test_file_transaction() {
+    let dir = tempfile::tempdir().unwrap();
+    let existing = dir.path().join("modpack.toml");
+    let new = dir.path().join("modpack.lock");
+    std::fs::write(&existing, "old").unwrap();
+
+    let mut transaction = FileTransaction::new();
+    transaction.stage(&existing, b"new meta").unwrap();
+    transaction.stage(&new, b"new lock").unwrap();
+    // Nothing is written until the transaction is committed
+    assert_eq!(std::fs::read_to_string(&existing).unwrap(), "old");
+    assert!(!new.exists());
+
+    transaction.commit().unwrap();
+    assert_eq!(std::fs::read_to_string(&existing).unwrap(), "new meta");
+    assert_eq!(std::fs::read_to_string(&new).unwrap(), "new lock");
+    assert!(!backup_path(&existing).exists());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+}
@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use notify::RecursiveMode;
+use std::{path::Path, time::Duration};
+
+use crate::{
+    modpack::{ModpackMeta, MODPACK_FILENAME},
+    profiles::Profile,
+    resolver::{DependencyVersions, PinnedPackMeta},
+    transaction::{InstanceLock, INSTANCE_LOCK_FILENAME},
+};
+
+/// How long the pack directory has to stay unchanged before changes to it are applied,
+/// so that a burst of saves is only applied once
+const DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// Check whether a changed path in a pack directory is part of the pack, rather than git's or mcmpmgr's own bookkeeping
+fn is_pack_change(pack_dir: &Path, path: &Path) -> bool {
+    let path = path.strip_prefix(pack_dir).unwrap_or(path);
+    !path
+        .components()
+        .any(|component| component.as_os_str() == ".git")
+        && path
+            .file_name()
+            .is_none_or(|file_name| file_name != INSTANCE_LOCK_FILENAME)
+}
+
+/// Pin any mods added to or changed in the pack's metadata, and unpin any removed from it
+async fn resolve_pack_changes(pack_dir: &Path) -> Result<()> {
+    // Held while the lock is updated, so it doesn't race other mcmpmgr commands run in the pack
+    let _instance_lock = InstanceLock::acquire(pack_dir)?;
+    let modpack_meta = ModpackMeta::load_from_directory(pack_dir)?;
+    let mut pack_lock =
+        PinnedPackMeta::load_from_directory(pack_dir, DependencyVersions::Latest).await?;
+    if pack_lock
+        .resolve_drift(&modpack_meta, DependencyVersions::Latest)
+        .await?
+    {
+        pack_lock.save_to_dir(&pack_dir.to_path_buf())?;
+    }
+    Ok(())
+}
+
+/// Install a profile whenever the files of its local pack in `pack_dir` change, until interrupted
+///
+/// The pack's mods are re-resolved first whenever its metadata changes. Failures are reported
+/// and then wait for the next change, so that a half-edited pack doesn't stop the watch
+pub async fn watch_pack(pack_dir: &Path, profile: &Profile) -> Result<()> {
+    // Watchers report absolute paths, so the pack's own paths have to be absolute to compare against
+    let pack_dir = &pack_dir
+        .canonicalize()
+        .with_context(|| format!("Cannot find pack directory {}", pack_dir.display()))?;
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let mut debouncer = notify_debouncer_mini::new_debouncer(DEBOUNCE, move |events| {
+        // The receiver is only dropped once the watch has stopped
+        let _ = sender.send(events);
+    })?;
+    debouncer
+        .watcher()
+        .watch(pack_dir, RecursiveMode::Recursive)
+        .with_context(|| format!("Cannot watch {}", pack_dir.display()))?;
+
+    let mut metadata_changed = true;
+    loop {
+        let result = async {
+            if metadata_changed {
+                resolve_pack_changes(pack_dir).await?;
+            }
+            profile.install().await
+        }
+        .await;
+        match result {
+            Ok(()) => println!("Installed pack to {}", profile.instance_folder.display()),
+            Err(e) => eprintln!("Failed to install pack: {e:?}"),
+        }
+        // Anything written while installing, eg. the lock, isn't a change to apply. Writes are only
+        // reported once they've settled, so wait for those before dropping them
+        tokio::time::sleep(DEBOUNCE * 2).await;
+        while receiver.try_recv().is_ok() {}
+        println!("Watching {} for changes...", pack_dir.display());
+
+        let modpack_meta_path = pack_dir.join(MODPACK_FILENAME);
+        loop {
+            let events = receiver
+                .recv()
+                .await
+                .context("Stopped watching for changes")?
+                .with_context(|| format!("Failed to watch {}", pack_dir.display()))?;
+            let mut changed_paths = events
+                .iter()
+                .map(|event| event.path.as_path())
+                .filter(|path| is_pack_change(pack_dir, path))
+                .peekable();
+            if changed_paths.peek().is_some() {
+                metadata_changed = changed_paths.any(|path| path == modpack_meta_path);
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_is_pack_change() {
+    let pack_dir = Path::new("/packs/test");
+    assert!(is_pack_change(pack_dir, &pack_dir.join("config/mod.toml")));
+    assert!(is_pack_change(pack_dir, &pack_dir.join(MODPACK_FILENAME)));
+    assert!(is_pack_change(pack_dir, pack_dir));
+    assert!(!is_pack_change(pack_dir, &pack_dir.join(".git/HEAD")));
+    assert!(!is_pack_change(pack_dir, &pack_dir.join(".git")));
+    assert!(!is_pack_change(
+        pack_dir,
+        &pack_dir.join(INSTANCE_LOCK_FILENAME)
+    ));
+    // Only the pack's own git directory is left out, not files that happen to be under a .git in a parent directory
+    assert!(is_pack_change(
+        Path::new("/repo/.git/packs/test"),
+        Path::new("/repo/.git/packs/test/config/mod.toml")
+    ));
+}